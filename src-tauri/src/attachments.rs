@@ -0,0 +1,319 @@
+//! Files attached to notes.
+//!
+//! Uploaded files are stored once in `attachments/` under the notes
+//! directory, named after the SHA-256 of their contents plus the original
+//! extension (`attachments/<hash>.png`), so the same image pasted twice is
+//! only stored once. Notes list the files they use in the `attachments`
+//! metadata field; files linked from the note body count as well. Files
+//! neither listed nor linked by any note are removed by [`collect_garbage`].
+//!
+//! Files placed in `attachments/` by importers keep their original paths and
+//! are served the same way, but are never garbage collected.
+
+use crate::{load_note_local, load_notes_local, resolve_notes_dir, save_block_local, NoteBlock};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+/// Uploads younger than this are never collected, so a file uploaded just
+/// before the link to it is saved into the note survives.
+const GC_GRACE_PERIOD: Duration = Duration::from_secs(60 * 60);
+
+const MIME_TYPES: &[(&str, &str)] = &[
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("svg", "image/svg+xml"),
+    ("avif", "image/avif"),
+    ("bmp", "image/bmp"),
+    ("ico", "image/x-icon"),
+    ("pdf", "application/pdf"),
+    ("zip", "application/zip"),
+    ("json", "application/json"),
+    ("txt", "text/plain; charset=utf-8"),
+    ("md", "text/markdown; charset=utf-8"),
+    ("csv", "text/csv; charset=utf-8"),
+    ("html", "text/html; charset=utf-8"),
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("ogg", "audio/ogg"),
+    ("wav", "audio/wav"),
+    ("mp4", "video/mp4"),
+    ("webm", "video/webm"),
+    ("mov", "video/quicktime"),
+];
+
+const DEFAULT_MIME_TYPE: &str = "application/octet-stream";
+
+/// MIME type for a file name, based on its extension.
+pub fn mime_type(name: &str) -> &'static str {
+    let ext = Path::new(name)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .unwrap_or_default();
+    MIME_TYPES
+        .iter()
+        .find(|(e, _)| *e == ext)
+        .map(|(_, mime)| *mime)
+        .unwrap_or(DEFAULT_MIME_TYPE)
+}
+
+/// File extension for a MIME type, for uploads without a file name.
+fn extension_for(mime: &str) -> Option<&'static str> {
+    let essence = mime.split(';').next().unwrap_or("").trim();
+    MIME_TYPES
+        .iter()
+        .find(|(_, m)| m.split(';').next() == Some(essence))
+        .map(|(ext, _)| *ext)
+}
+
+/// A stored attachment, as returned to clients after an upload.
+#[derive(Serialize, Debug, Clone)]
+pub struct StoredAttachment {
+    /// File name inside `attachments/`, as listed in note metadata
+    pub file: String,
+    /// Path relative to the notes directory, for use in markdown links
+    pub path: String,
+    /// Original file name
+    pub name: String,
+    pub size: u64,
+    #[serde(rename = "mimeType")]
+    pub mime_type: String,
+    /// Ready-made markdown link (an image embed for images)
+    pub markdown: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct GcReport {
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+    pub removed: Vec<String>,
+    pub bytes: u64,
+}
+
+impl GcReport {
+    pub fn summary(&self) -> String {
+        let verb = if self.dry_run { "Would remove" } else { "Removed" };
+        format!("{} {} unused attachments ({} bytes)\n", verb, self.removed.len(), self.bytes)
+    }
+}
+
+/// Whether `file` is a content-addressed name as created by [`store`].
+fn is_content_addressed(file: &str) -> bool {
+    let hash = file.split_once('.').map_or(file, |(hash, _)| hash);
+    hash.len() == 64 && hash.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b))
+}
+
+/// Stores `data` in the attachment directory and returns its description.
+///
+/// The extension comes from `name` if it has one, otherwise from
+/// `content_type`.
+pub fn store(
+    data: &[u8],
+    name: Option<&str>,
+    content_type: Option<&str>,
+    custom_path: Option<&Path>,
+) -> Result<StoredAttachment, String> {
+    if data.is_empty() {
+        return Err("Attachment is empty".to_string());
+    }
+
+    let name = name
+        .and_then(|n| Path::new(n).file_name())
+        .and_then(|n| n.to_str())
+        .filter(|n| !n.is_empty())
+        .map(String::from);
+    let ext = name
+        .as_deref()
+        .and_then(|n| Path::new(n).extension())
+        .and_then(|e| e.to_str())
+        .filter(|e| !e.is_empty() && e.chars().all(|c| c.is_ascii_alphanumeric()))
+        .map(|e| e.to_ascii_lowercase())
+        .or_else(|| content_type.and_then(extension_for).map(String::from));
+
+    let hash: String = Sha256::digest(data).iter().map(|b| format!("{:02x}", b)).collect();
+    let file = match &ext {
+        Some(ext) => format!("{}.{}", hash, ext),
+        None => hash,
+    };
+
+    let dir = resolve_notes_dir(custom_path)?.join(ATTACHMENTS_DIR);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    let dest = dir.join(&file);
+    if dest.exists() {
+        // Same contents already stored; refresh the time so GC sees it as new
+        let _ = fs::File::options().append(true).open(&dest).and_then(|f| f.set_modified(SystemTime::now()));
+    } else {
+        fs::write(&dest, data).map_err(|e| format!("Failed to write attachment: {}", e))?;
+    }
+
+    let name = name.unwrap_or_else(|| file.clone());
+    let mime = mime_type(&file);
+    let path = format!("{}/{}", ATTACHMENTS_DIR, file);
+    let markdown = if mime.starts_with("image/") {
+        format!("![{}]({})", name, path)
+    } else {
+        format!("[{}]({})", name, path)
+    };
+
+    Ok(StoredAttachment {
+        file,
+        path,
+        name,
+        size: data.len() as u64,
+        mime_type: mime.to_string(),
+        markdown,
+    })
+}
+
+/// Stores `data` and records it in the metadata of note `id`.
+pub fn attach(
+    id: &str,
+    data: &[u8],
+    name: Option<&str>,
+    content_type: Option<&str>,
+    custom_path: Option<&Path>,
+) -> Result<StoredAttachment, String> {
+    let mut block = load_note_local(id, custom_path)?.ok_or(format!("Note {} not found", id))?;
+    let stored = store(data, name, content_type, custom_path)?;
+    let files = block.attachments.get_or_insert_with(Vec::new);
+    if !files.contains(&stored.file) {
+        files.push(stored.file.clone());
+        save_block_local(block, custom_path)?;
+    }
+    Ok(stored)
+}
+
+/// Removes `file` from the metadata of note `id`. The file itself stays
+/// until the next garbage collection. Returns whether it was listed.
+pub fn detach(id: &str, file: &str, custom_path: Option<&Path>) -> Result<bool, String> {
+    let mut block = load_note_local(id, custom_path)?.ok_or(format!("Note {} not found", id))?;
+    // An empty list is kept, so the files are not taken from the old one
+    let files = block.attachments.get_or_insert_with(Vec::new);
+    let before = files.len();
+    files.retain(|f| f != file);
+    if files.len() == before {
+        return Ok(false);
+    }
+    save_block_local(block, custom_path)?;
+    Ok(true)
+}
+
+/// Content-addressed attachments linked from a note body.
+pub fn linked_files(content: &str) -> Vec<String> {
+    let prefix = format!("{}/", ATTACHMENTS_DIR);
+    let mut files = Vec::new();
+    for (start, _) in content.match_indices(&prefix) {
+        let rest = &content[start + prefix.len()..];
+        let end = rest
+            .find(|c: char| !(c.is_ascii_alphanumeric() || c == '.'))
+            .unwrap_or(rest.len());
+        let file = rest[..end].trim_end_matches('.');
+        if is_content_addressed(file) && !files.iter().any(|f| f == file) {
+            files.push(file.to_string());
+        }
+    }
+    files
+}
+
+/// Adds the attachments linked from the body of `block` to its metadata.
+/// Notes whose content is about to be encrypted need this, or garbage
+/// collection could no longer see what they link to.
+pub(crate) fn record_linked(block: &mut NoteBlock) {
+    for file in linked_files(&block.content) {
+        crate::import::push_unique(block.attachments.get_or_insert_with(Vec::new), file);
+    }
+}
+
+/// Resolves a path below `attachments/`, rejecting anything that would
+/// escape the directory.
+fn attachment_path(rel: &str, custom_path: Option<&Path>) -> Result<Option<PathBuf>, String> {
+    let rel = Path::new(rel);
+    if rel.as_os_str().is_empty() || !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+        return Ok(None);
+    }
+    Ok(Some(resolve_notes_dir(custom_path)?.join(ATTACHMENTS_DIR).join(rel)))
+}
+
+/// Reads an attachment by its path below `attachments/`, with its MIME type.
+pub fn read(rel: &str, custom_path: Option<&Path>) -> Result<Option<(Vec<u8>, &'static str)>, String> {
+    let Some(path) = attachment_path(rel, custom_path)? else { return Ok(None) };
+    if !path.is_file() {
+        return Ok(None);
+    }
+    let data = fs::read(&path).map_err(|e| format!("Failed to read attachment: {}", e))?;
+    Ok(Some((data, mime_type(rel))))
+}
+
+/// Removes content-addressed attachments that no active or archived note
+/// lists in its metadata or links from its body.
+pub fn collect_garbage(custom_path: Option<&Path>, dry_run: bool) -> Result<GcReport, String> {
+    let dir = resolve_notes_dir(custom_path)?.join(ATTACHMENTS_DIR);
+    let mut report = GcReport { dry_run, ..Default::default() };
+    if !dir.exists() {
+        return Ok(report);
+    }
+
+    let mut used: HashSet<String> = HashSet::new();
+    for subdir in [None, Some("archive")] {
+        for note in load_notes_local(subdir, custom_path)? {
+            used.extend(linked_files(&note.content));
+            used.extend(note.attachments.into_iter().flatten());
+        }
+    }
+
+    let now = SystemTime::now();
+    let entries = fs::read_dir(&dir).map_err(|e| format!("Failed to read directory: {}", e))?;
+    for entry in entries.flatten() {
+        let file = entry.file_name().to_string_lossy().to_string();
+        let Ok(meta) = entry.metadata() else { continue };
+        if !meta.is_file() || !is_content_addressed(&file) || used.contains(&file) {
+            continue;
+        }
+        let age = meta.modified().ok().and_then(|m| now.duration_since(m).ok()).unwrap_or_default();
+        if age < GC_GRACE_PERIOD {
+            continue;
+        }
+
+        if !dry_run {
+            fs::remove_file(entry.path()).map_err(|e| format!("Failed to remove {}: {}", file, e))?;
+        }
+        report.bytes += meta.len();
+        report.removed.push(file);
+    }
+
+    report.removed.sort();
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saves_without_the_metadata_keep_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Some(dir.path());
+        let image = format!("{}.png", "ab".repeat(32));
+        let note = NoteBlock::test("1", "Packing list");
+        save_block_local(NoteBlock { attachments: Some(vec![image.clone()]), ..note.clone() }, path).unwrap();
+
+        // A client that predates attachments
+        save_block_local(NoteBlock { title: "Renamed".to_string(), ..note.clone() }, path).unwrap();
+        let saved = load_note_local("1", path).unwrap().unwrap();
+        assert_eq!(saved.title, "Renamed");
+        assert_eq!(saved.attachments, Some(vec![image.clone()]));
+
+        assert!(detach("1", &image, path).unwrap());
+        save_block_local(note, path).unwrap();
+        assert_eq!(load_note_local("1", path).unwrap().unwrap().attachments, None);
+    }
+}
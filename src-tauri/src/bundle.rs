@@ -0,0 +1,348 @@
+//! Whole-vault export and restore.
+//!
+//! A bundle holds every active and archived note with its metadata. The JSON
+//! format is just the [`Bundle`] itself; the zip format stores the same
+//! `bundle.json` next to the raw markdown files, in folders for their
+//! notebooks, and the `attachments` directory, so it can also be browsed by
+//! hand.
+//!
+//! On a server with end-to-end encryption the notes are ciphertext, so the
+//! bundle carries the keyring too; without it a restore could not be
+//! decrypted.
+
+use crate::e2e::KEYRING_FILE;
+use crate::notebooks::{self, move_note_local};
+use crate::{
+    archive_block_local, is_note_id, load_notes_local, resolve_notes_dir, unarchive_block_local, write_block_local,
+    NoteBlock,
+};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io::{Cursor, Read, Write};
+use std::path::Path;
+
+pub const BUNDLE_FORMAT: &str = "zenus-bundle";
+pub const BUNDLE_VERSION: u32 = 1;
+
+const BUNDLE_FILE: &str = "bundle.json";
+
+#[derive(clap::ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum BundleFormat {
+    /// Zip archive with bundle.json, markdown files and attachments
+    #[default]
+    Zip,
+    /// Single JSON document with all notes (no attachments)
+    Json,
+}
+
+impl BundleFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            BundleFormat::Zip => "zip",
+            BundleFormat::Json => "json",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            BundleFormat::Zip => "application/zip",
+            BundleFormat::Json => "application/json",
+        }
+    }
+
+    /// Default file name for an export made now.
+    pub fn file_name(self) -> String {
+        format!("zenus-export-{}.{}", chrono::Local::now().format("%Y%m%d-%H%M%S"), self.extension())
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+pub struct Bundle {
+    pub format: String,
+    pub version: u32,
+    #[serde(rename = "exportedAt")]
+    pub exported_at: String,
+    #[serde(rename = "appVersion")]
+    pub app_version: String,
+    pub notes: Vec<NoteBlock>,
+    #[serde(default)]
+    pub archived: Vec<NoteBlock>,
+    /// The end-to-end encryption keyring, as stored by the server
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keyring: Option<serde_json::Value>,
+}
+
+/// What a restore wrote.
+#[derive(Serialize, Debug, Default)]
+pub struct RestoreReport {
+    pub notes: usize,
+    pub archived: usize,
+    pub attachments: usize,
+    /// The end-to-end encryption keyring was written
+    pub keyring: bool,
+}
+
+impl RestoreReport {
+    pub fn summary(&self) -> String {
+        let mut summary = format!(
+            "Restored {} notes, {} archived notes and {} attachments\n",
+            self.notes, self.archived, self.attachments
+        );
+        if self.keyring {
+            summary.push_str("Restored the end-to-end encryption keyring\n");
+        }
+        summary
+    }
+}
+
+/// The keyring in `notes_dir`, if end-to-end encryption is on.
+fn read_keyring(notes_dir: &Path) -> Result<Option<serde_json::Value>, String> {
+    let path = notes_dir.join(KEYRING_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", KEYRING_FILE, e))?;
+    serde_json::from_slice(&data).map(Some).map_err(|e| format!("Invalid {}: {}", KEYRING_FILE, e))
+}
+
+/// Collects every note in the local notes directory into a bundle.
+pub fn build_bundle(custom_path: Option<&Path>) -> Result<Bundle, String> {
+    Ok(Bundle {
+        format: BUNDLE_FORMAT.to_string(),
+        version: BUNDLE_VERSION,
+        exported_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        notes: load_notes_local(None, custom_path)?,
+        archived: load_notes_local(Some(notebooks::ARCHIVE_DIR), custom_path)?,
+        keyring: read_keyring(&resolve_notes_dir(custom_path)?)?,
+    })
+}
+
+/// Exports the local notes directory in the given format.
+pub fn export_local(custom_path: Option<&Path>, format: BundleFormat) -> Result<Vec<u8>, String> {
+    let bundle = build_bundle(custom_path)?;
+    match format {
+        BundleFormat::Json => {
+            serde_json::to_vec_pretty(&bundle).map_err(|e| format!("Failed to serialize bundle: {}", e))
+        }
+        BundleFormat::Zip => write_zip(&bundle, &resolve_notes_dir(custom_path)?),
+    }
+}
+
+fn write_zip(bundle: &Bundle, notes_dir: &Path) -> Result<Vec<u8>, String> {
+    let zip_err = |e: zip::result::ZipError| format!("Failed to write zip: {}", e);
+    let io_err = |e: std::io::Error| format!("Failed to write zip: {}", e);
+
+    let mut zip = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    let options = zip::write::SimpleFileOptions::default();
+
+    let json = serde_json::to_vec_pretty(bundle).map_err(|e| format!("Failed to serialize bundle: {}", e))?;
+    zip.start_file(BUNDLE_FILE, options).map_err(zip_err)?;
+    zip.write_all(&json).map_err(io_err)?;
+
+    for (archived, prefix) in [(false, "notes"), (true, "archive")] {
+        for (path, notebook) in notebooks::note_files(notes_dir, archived) {
+            let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let file_name = path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+            let name = match notebook {
+                Some(notebook) => format!("{}/{}/{}", prefix, notebook, file_name),
+                None => format!("{}/{}", prefix, file_name),
+            };
+            zip.start_file(name, options).map_err(zip_err)?;
+            zip.write_all(&data).map_err(io_err)?;
+        }
+    }
+
+    add_dir_to_zip(&mut zip, &notes_dir.join("attachments"), "attachments", options)?;
+
+    zip.finish().map(|cursor| cursor.into_inner()).map_err(zip_err)
+}
+
+fn add_dir_to_zip(
+    zip: &mut zip::ZipWriter<Cursor<Vec<u8>>>,
+    dir: &Path,
+    prefix: &str,
+    options: zip::write::SimpleFileOptions,
+) -> Result<(), String> {
+    let Ok(entries) = fs::read_dir(dir) else { return Ok(()) };
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = format!("{}/{}", prefix, entry.file_name().to_string_lossy());
+        if path.is_dir() {
+            add_dir_to_zip(zip, &path, &name, options)?;
+        } else {
+            let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            zip.start_file(name, options).map_err(|e| format!("Failed to write zip: {}", e))?;
+            zip.write_all(&data).map_err(|e| format!("Failed to write zip: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Attachment files from a zip bundle as (path below `attachments/`, contents).
+pub type BundleAttachments = Vec<(String, Vec<u8>)>;
+
+/// Parses a bundle in either format.
+pub fn read_bundle(data: &[u8]) -> Result<(Bundle, BundleAttachments), String> {
+    // Zip files start with the "PK" local file header signature
+    if !data.starts_with(b"PK") {
+        return Ok((parse_bundle_json(data)?, Vec::new()));
+    }
+
+    let mut archive = zip::ZipArchive::new(Cursor::new(data)).map_err(|e| format!("Invalid zip bundle: {}", e))?;
+    let mut bundle = None;
+    let mut attachments = Vec::new();
+
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i).map_err(|e| format!("Invalid zip bundle: {}", e))?;
+        // enclosed_name rejects absolute paths and `..` components
+        let Some(name) = file.enclosed_name() else { continue };
+        let name = name.to_string_lossy().replace('\\', "/");
+
+        if name == BUNDLE_FILE {
+            let mut json = Vec::new();
+            file.read_to_end(&mut json).map_err(|e| format!("Failed to read {}: {}", BUNDLE_FILE, e))?;
+            bundle = Some(parse_bundle_json(&json)?);
+        } else if let Some(rel) = name.strip_prefix("attachments/") {
+            if file.is_dir() || rel.is_empty() {
+                continue;
+            }
+            let mut contents = Vec::new();
+            file.read_to_end(&mut contents).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+            attachments.push((rel.to_string(), contents));
+        }
+    }
+
+    let bundle = bundle.ok_or(format!("Zip file has no {}", BUNDLE_FILE))?;
+    Ok((bundle, attachments))
+}
+
+fn parse_bundle_json(data: &[u8]) -> Result<Bundle, String> {
+    let bundle: Bundle = serde_json::from_slice(data).map_err(|e| format!("Invalid bundle: {}", e))?;
+    if bundle.format != BUNDLE_FORMAT {
+        return Err(format!("Not a Zenus bundle (format '{}')", bundle.format));
+    }
+    if bundle.version > BUNDLE_VERSION {
+        return Err(format!(
+            "Bundle version {} is newer than this version of Zenus supports ({})",
+            bundle.version, BUNDLE_VERSION
+        ));
+    }
+    Ok(bundle)
+}
+
+/// Restores a bundle into the local notes directory.
+///
+/// Notes keep their IDs, timestamps, notebooks and archive state, replacing
+/// notes with the same ID. Notes that are not in the bundle are left alone.
+/// The keyring of an end-to-end encrypted bundle is restored too, unless
+/// the directory already has a different one, in which case nothing is
+/// written.
+pub fn restore_local(data: &[u8], custom_path: Option<&Path>) -> Result<RestoreReport, String> {
+    let (bundle, attachments) = read_bundle(data)?;
+    // Note IDs become file names, so one that is a path rejects the bundle
+    // before anything is written
+    if let Some(block) = bundle.notes.iter().chain(&bundle.archived).find(|block| !is_note_id(&block.id)) {
+        return Err(format!("Invalid note ID {:?} in bundle", block.id));
+    }
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let mut report = RestoreReport::default();
+
+    if let Some(keyring) = &bundle.keyring {
+        match read_keyring(&notes_dir)? {
+            Some(existing) if existing == *keyring => {}
+            Some(_) => {
+                return Err("The bundle uses a different end-to-end encryption keyring than this server".to_string());
+            }
+            None => {
+                fs::create_dir_all(&notes_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+                let json = serde_json::to_vec_pretty(keyring).map_err(|e| format!("Failed to serialize keyring: {}", e))?;
+                fs::write(notes_dir.join(KEYRING_FILE), json).map_err(|e| format!("Failed to write {}: {}", KEYRING_FILE, e))?;
+                report.keyring = true;
+            }
+        }
+    }
+
+    for (rel, contents) in &attachments {
+        let dest = notes_dir.join("attachments").join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+        fs::write(&dest, contents).map_err(|e| format!("Failed to write attachment {}: {}", rel, e))?;
+        report.attachments += 1;
+    }
+
+    for (archived, blocks) in [(false, bundle.notes), (true, bundle.archived)] {
+        for block in blocks {
+            let id = block.id.clone();
+            let location = notebooks::find_note(&notes_dir, &id);
+            let was_archived = location.as_ref().is_some_and(|l| l.archived);
+            let updated_at = block.updated_at.clone().unwrap_or_else(|| chrono::Utc::now().to_rfc3339());
+
+            // New notes are written into their notebook, existing ones moved there first
+            if location.is_some_and(|l| l.notebook != block.notebook) {
+                move_note_local(&id, block.notebook.as_deref(), custom_path)?;
+            }
+
+            // write_block_local keeps archived notes in the archive, so fix up afterwards
+            write_block_local(block, custom_path, updated_at)?;
+            if archived && !was_archived {
+                archive_block_local(id, custom_path)?;
+                report.archived += 1;
+            } else if !archived && was_archived {
+                unarchive_block_local(id, custom_path)?;
+                report.notes += 1;
+            } else if archived {
+                report.archived += 1;
+            } else {
+                report.notes += 1;
+            }
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keyring_survives_export_and_restore() {
+        let temp = tempfile::tempdir().unwrap();
+        let [source, dest, other] = ["source", "dest", "other"].map(|name| temp.path().join(name));
+        for dir in [&source, &dest, &other] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(source.join(KEYRING_FILE), r#"{"format":"zenus-e2e-keyring","version":1,"keys":[{"id":"a"}]}"#).unwrap();
+        fs::write(other.join(KEYRING_FILE), r#"{"format":"zenus-e2e-keyring","version":1,"keys":[{"id":"b"}]}"#).unwrap();
+
+        let expected: serde_json::Value = serde_json::from_str(r#"{"format":"zenus-e2e-keyring","version":1,"keys":[{"id":"a"}]}"#).unwrap();
+        // The first restore writes the keyring, the second finds it in place
+        for (format, written) in [(BundleFormat::Json, true), (BundleFormat::Zip, false)] {
+            let data = export_local(Some(&source), format).unwrap();
+            assert_eq!(restore_local(&data, Some(&dest)).map(|report| report.keyring), Ok(written));
+            assert_eq!(read_keyring(&dest).unwrap(), Some(expected.clone()));
+            assert!(restore_local(&data, Some(&other)).is_err());
+        }
+        assert_eq!(read_keyring(&other).unwrap().unwrap()["keys"][0]["id"], "b");
+    }
+
+    #[test]
+    fn note_ids_that_are_paths_reject_the_bundle() {
+        let temp = tempfile::tempdir().unwrap();
+        let (source, dest) = (temp.path().join("source"), temp.path().join("a").join("b"));
+        crate::save_block_local(NoteBlock::test("1", "Fine"), Some(&source)).unwrap();
+        crate::save_block_local(NoteBlock::test("2", "Escapes"), Some(&source)).unwrap();
+
+        let mut bundle: serde_json::Value = serde_json::from_slice(&export_local(Some(&source), BundleFormat::Json).unwrap()).unwrap();
+        bundle["notes"][1]["id"] = serde_json::json!("../../x");
+        let data = serde_json::to_vec(&bundle).unwrap();
+
+        assert!(restore_local(&data, Some(&dest)).unwrap_err().contains("Invalid note ID"));
+        assert!(!temp.path().join("x.md").exists());
+        // The valid note is not restored either
+        assert!(!dest.exists());
+    }
+}
@@ -0,0 +1,227 @@
+//! iCalendar (RFC 5545) export of tasks.
+//!
+//! Tasks with a due date become events: all-day ones for
+//! `@due(YYYY-MM-DD)` and half-hour ones at the due time otherwise, with an
+//! alarm at the `@remind(...)` time. As to-dos every task is exported, with
+//! its due date if it has one. The UID of an entry is derived from the note
+//! ID and the task's text rather than its line, so subscribed calendars
+//! update the entry instead of adding another when lines above it change.
+
+use crate::reminders::{local_time, parse_when, ReminderSettings};
+use crate::tasks::{parse_tasks, Task, TaskFilter};
+use crate::NoteBlock;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+/// Longest content line in octets before it is folded.
+const MAX_LINE: usize = 75;
+
+/// How long an event for a task with a due time lasts.
+const EVENT_MINUTES: i64 = 30;
+
+/// What tasks are exported as.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum CalendarKind {
+    /// `VEVENT`, shown by every calendar app
+    #[default]
+    Event,
+    /// `VTODO`, shown by apps with a task list
+    Todo,
+}
+
+/// Escapes a TEXT value.
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Appends a content line, folded so no line is longer than 75 octets.
+fn push_line(out: &mut String, line: &str) {
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > MAX_LINE {
+            out.push_str("\r\n ");
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out.push_str("\r\n");
+}
+
+fn format_utc(t: DateTime<Utc>) -> String {
+    t.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// A UID that stays the same as long as the task's note and text do.
+/// `seen` tells apart tasks with the same text in one note.
+fn task_uid(task: &Task, seen: &mut HashMap<(String, String), usize>) -> String {
+    let hash = Sha256::digest(format!("{}\n{}", task.note_id, task.text).as_bytes());
+    let hex: String = hash.iter().take(8).map(|b| format!("{:02x}", b)).collect();
+    let count = seen.entry((task.note_id.clone(), task.text.clone())).or_default();
+    *count += 1;
+    match *count {
+        1 => format!("{}-{}@zenus", task.note_id, hex),
+        n => format!("{}-{}-{}@zenus", task.note_id, hex, n),
+    }
+}
+
+/// Writes the date properties of `task` and returns whether it has any.
+fn push_due(out: &mut String, task: &Task, kind: CalendarKind, settings: &ReminderSettings) -> bool {
+    let Some(due) = task.due.as_deref() else { return false };
+    let Some((date, time)) = parse_when(due) else { return false };
+    match (kind, time) {
+        (CalendarKind::Event, None) => {
+            push_line(out, &format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")));
+            push_line(out, &format!("DTEND;VALUE=DATE:{}", (date + chrono::Duration::days(1)).format("%Y%m%d")));
+        }
+        (CalendarKind::Todo, None) => push_line(out, &format!("DUE;VALUE=DATE:{}", date.format("%Y%m%d"))),
+        (_, Some(_)) => {
+            let Some(at) = local_time(due, settings) else { return false };
+            let at = at.with_timezone(&Utc);
+            if kind == CalendarKind::Event {
+                push_line(out, &format!("DTSTART:{}", format_utc(at)));
+                push_line(out, &format!("DTEND:{}", format_utc(at + chrono::Duration::minutes(EVENT_MINUTES))));
+            } else {
+                push_line(out, &format!("DUE:{}", format_utc(at)));
+            }
+        }
+    }
+    true
+}
+
+/// The tasks of `notes` that match `filter` as an iCalendar file.
+pub fn tasks_calendar(notes: &[NoteBlock], filter: &TaskFilter, kind: CalendarKind, settings: &ReminderSettings) -> String {
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//Zenus//Tasks//EN");
+    push_line(&mut out, "CALSCALE:GREGORIAN");
+    push_line(&mut out, "X-WR-CALNAME:Zenus Tasks");
+
+    let mut seen = HashMap::new();
+    for note in notes {
+        // The time of the last change, so the output only changes with the note
+        let stamp = note.updated_at.as_deref()
+            .and_then(|t| DateTime::parse_from_rfc3339(t).ok())
+            .map(|t| t.with_timezone(&Utc))
+            .unwrap_or_else(Utc::now);
+
+        for task in parse_tasks(note).into_iter().filter(|t| filter.matches(t)) {
+            let uid = task_uid(&task, &mut seen);
+            let component = if kind == CalendarKind::Event { "VEVENT" } else { "VTODO" };
+            let mut entry = String::new();
+            push_line(&mut entry, &format!("BEGIN:{}", component));
+            push_line(&mut entry, &format!("UID:{}", uid));
+            push_line(&mut entry, &format!("DTSTAMP:{}", format_utc(stamp)));
+            if !push_due(&mut entry, &task, kind, settings) && kind == CalendarKind::Event {
+                continue;
+            }
+
+            let summary = if task.done && kind == CalendarKind::Event { format!("✓ {}", task.text) } else { task.text.clone() };
+            push_line(&mut entry, &format!("SUMMARY:{}", escape(&summary)));
+            push_line(&mut entry, &format!("DESCRIPTION:{}", escape(&task.note_title)));
+            if !task.tags.is_empty() {
+                let tags: Vec<String> = task.tags.iter().map(|t| escape(t)).collect();
+                push_line(&mut entry, &format!("CATEGORIES:{}", tags.join(",")));
+            }
+            if kind == CalendarKind::Todo {
+                push_line(&mut entry, if task.done { "STATUS:COMPLETED" } else { "STATUS:NEEDS-ACTION" });
+            } else {
+                push_line(&mut entry, "TRANSP:TRANSPARENT");
+            }
+
+            let alarm = task.remind.as_deref().filter(|_| !task.done).and_then(|r| local_time(r, settings));
+            if let Some(at) = alarm {
+                push_line(&mut entry, "BEGIN:VALARM");
+                push_line(&mut entry, "ACTION:DISPLAY");
+                push_line(&mut entry, &format!("DESCRIPTION:{}", escape(&task.text)));
+                push_line(&mut entry, &format!("TRIGGER;VALUE=DATE-TIME:{}", format_utc(at.with_timezone(&Utc))));
+                push_line(&mut entry, "END:VALARM");
+            }
+            push_line(&mut entry, &format!("END:{}", component));
+            out.push_str(&entry);
+        }
+    }
+
+    push_line(&mut out, "END:VCALENDAR");
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn note(content: &str) -> NoteBlock {
+        NoteBlock { updated_at: Some("2026-10-19T08:00:00+00:00".to_string()), ..NoteBlock::test("1", content) }
+    }
+
+    fn uids(calendar: &str) -> Vec<&str> {
+        calendar.lines().filter_map(|l| l.strip_prefix("UID:")).collect()
+    }
+
+    #[test]
+    fn escapes_text_values() {
+        assert_eq!(escape(r"a,b;c\d"), r"a\,b\;c\\d");
+        assert_eq!(escape("line\r\nnext"), "line\\nnext");
+    }
+
+    #[test]
+    fn folds_long_lines_between_characters() {
+        let line = format!("SUMMARY:{}", "ä€😀x".repeat(30));
+        let mut out = String::new();
+        push_line(&mut out, &line);
+
+        assert!(out.ends_with("\r\n"));
+        let lines: Vec<&str> = out.trim_end_matches("\r\n").split("\r\n").collect();
+        assert!(lines.len() > 1);
+        assert!(lines.iter().all(|l| l.len() <= MAX_LINE));
+        assert!(lines[1..].iter().all(|l| l.starts_with(' ')));
+        // Unfolding gives back the original line
+        assert_eq!(out.trim_end_matches("\r\n").replace("\r\n ", ""), line);
+    }
+
+    #[test]
+    fn short_lines_are_not_folded() {
+        let mut out = String::new();
+        push_line(&mut out, &"x".repeat(MAX_LINE));
+        assert_eq!(out, format!("{}\r\n", "x".repeat(MAX_LINE)));
+    }
+
+    #[test]
+    fn uids_stay_the_same_when_lines_move() {
+        let settings = ReminderSettings::default();
+        let filter = TaskFilter::default();
+        let before = tasks_calendar(&[note("- [ ] A @due(2026-11-01)\n- [ ] B @due(2026-11-02)")], &filter, CalendarKind::Event, &settings);
+        let after = tasks_calendar(&[note("Intro\n\n- [ ] B @due(2026-11-02)\n- [ ] A @due(2026-11-01)")], &filter, CalendarKind::Event, &settings);
+
+        let (mut before, mut after) = (uids(&before), uids(&after));
+        assert_eq!(before.len(), 2);
+        before.sort();
+        after.sort();
+        assert_eq!(before, after);
+    }
+
+    #[test]
+    fn tasks_with_the_same_text_get_different_uids() {
+        let calendar = tasks_calendar(&[note("- [ ] Call\n- [ ] Call\n- [ ] No date")], &TaskFilter::default(), CalendarKind::Todo, &ReminderSettings::default());
+        let uids = uids(&calendar);
+        assert_eq!(uids.len(), 3);
+        assert_ne!(uids[0], uids[1]);
+        assert!(uids[1].ends_with("-2@zenus"));
+    }
+}
@@ -0,0 +1,621 @@
+//! `zenus-cli`: script the notes from a shell without the GUI.
+//!
+//! Every subcommand works on a [`NoteStore`], so the same invocation works
+//! against a local notes directory (`--path`) or a server (`--url`). Locked
+//! notes are listed and can be tagged, archived or removed, but their
+//! content is never shown or searched.
+
+use crate::calendar::{tasks_calendar, CalendarKind};
+use crate::config::{resolve_mode, Config};
+use crate::daily::{parse_date, DailyTemplate};
+use crate::import::push_unique;
+use crate::reminders::ReminderSettings;
+use crate::store::NoteStore;
+use crate::tasks::{TaskFilter, TaskUpdate};
+use crate::templates::TemplateRequest;
+use crate::{generate_note_id, note_lock, notebooks, NoteBlock};
+use clap::{Parser, Subcommand};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+use std::fs;
+use std::io::{IsTerminal as _, Read as _, Write as _};
+use std::path::PathBuf;
+
+#[derive(Parser, Debug)]
+#[command(version, about = "Zenus CLI - Read and edit notes from the command line", long_about = None)]
+pub struct CliArgs {
+    /// Path to the TOML config file [default: ~/.config/zenus/config.toml]
+    #[arg(long, env = "ZENUS_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// URL of the Zenus server to work against
+    #[arg(long, env = "ZENUS_URL")]
+    pub url: Option<String>,
+
+    /// Authentication token/password for the server
+    #[arg(long, env = "ZENUS_AUTH", hide_env_values = true)]
+    pub auth: Option<String>,
+
+    /// Custom path for notes directory
+    #[arg(long, env = "ZENUS_PATH")]
+    pub path: Option<String>,
+
+    /// File holding the end-to-end encryption or vault passphrase
+    #[arg(long, env = "ZENUS_PASSPHRASE_FILE")]
+    pub passphrase_file: Option<PathBuf>,
+
+    /// Print JSON instead of text
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    #[command(subcommand)]
+    pub command: CliCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// List notes as `id<TAB>title<TAB>tags`
+    List {
+        /// List archived notes instead
+        #[arg(long)]
+        archived: bool,
+
+        /// Only list notes with this tag
+        #[arg(long)]
+        tag: Option<String>,
+
+        /// Only list notes directly in this notebook, or at the top level
+        /// with ""
+        #[arg(long)]
+        notebook: Option<String>,
+    },
+    /// Print a note
+    Show {
+        id: String,
+    },
+    /// Print the daily note for a date, creating it from the template
+    Daily {
+        /// `YYYY-MM-DD`, `today`, `yesterday` or `tomorrow`
+        #[arg(default_value = "today")]
+        date: String,
+    },
+    /// Create a note and print its ID
+    New {
+        /// Title of the note
+        #[arg(default_value = "")]
+        title: String,
+
+        /// Body of the note
+        #[arg(long)]
+        content: Option<String>,
+
+        /// Tag to add, may be repeated
+        #[arg(long = "tag")]
+        tags: Vec<String>,
+
+        /// Write the body in $EDITOR
+        #[arg(long)]
+        edit: bool,
+
+        /// Fill in this template from the `templates` directory
+        #[arg(long, conflicts_with = "content")]
+        template: Option<String>,
+
+        /// Value of a template prompt variable as `name=value`, may be
+        /// repeated; missing ones are asked for on a terminal
+        #[arg(long = "var", requires = "template")]
+        vars: Vec<String>,
+
+        /// Notebook to create the note in, like `work/projects`
+        #[arg(long)]
+        notebook: Option<String>,
+    },
+    /// List the templates as `name<TAB>title<TAB>prompt variables`
+    Templates,
+    /// Edit a note's body in $EDITOR, or rename it with --title
+    Edit {
+        id: String,
+
+        /// New title; the body is left alone
+        #[arg(long)]
+        title: Option<String>,
+    },
+    /// Append text to the end of a note
+    Append {
+        id: String,
+
+        /// Text to append, joined with spaces
+        #[arg(required = true)]
+        text: Vec<String>,
+    },
+    /// Save stdin as a new note and print its ID, or append it to a note
+    Capture {
+        /// Title of the new note [default: Captured <date and time>]
+        #[arg(long, conflicts_with = "append_to")]
+        title: Option<String>,
+
+        /// Tag to add to the new note, may be repeated
+        #[arg(long = "tag", conflicts_with = "append_to")]
+        tags: Vec<String>,
+
+        /// Append to this note instead of creating one
+        #[arg(long)]
+        append_to: Option<String>,
+    },
+    /// Add tags to a note, or remove them with --remove
+    Tag {
+        id: String,
+
+        #[arg(required = true)]
+        tags: Vec<String>,
+
+        #[arg(long)]
+        remove: bool,
+    },
+    /// List tasks from all notes as `id:line<TAB>[ ]<TAB>text<TAB>due`
+    Tasks {
+        /// Only open tasks
+        #[arg(long, conflicts_with = "done")]
+        open: bool,
+
+        /// Only finished tasks
+        #[arg(long)]
+        done: bool,
+
+        /// Only tasks in notes with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Export tasks with a due date as an iCalendar file
+    Ics {
+        /// Write to this file instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// Export to-dos, including tasks without a due date, instead of
+        /// calendar events
+        #[arg(long)]
+        todo: bool,
+
+        /// Only open tasks
+        #[arg(long)]
+        open: bool,
+
+        /// Only tasks in notes with this tag
+        #[arg(long)]
+        tag: Option<String>,
+    },
+    /// Tick the task on a line of a note, as listed by `tasks`
+    Check {
+        id: String,
+        line: usize,
+
+        /// Untick it instead
+        #[arg(long)]
+        uncheck: bool,
+    },
+    /// Move a note to the archive
+    Archive {
+        id: String,
+    },
+    /// Move a note into a notebook, or to the top level without one
+    Mv {
+        id: String,
+
+        /// Notebook like `work/projects`, created if needed
+        notebook: Option<String>,
+    },
+    /// List, create, rename or delete notebooks
+    Notebook {
+        #[command(subcommand)]
+        command: NotebookCommand,
+    },
+    /// Delete a note for good
+    Rm {
+        id: String,
+    },
+    /// Find notes whose title, body or tags contain the query
+    Search {
+        query: String,
+
+        /// Search archived notes instead
+        #[arg(long)]
+        archived: bool,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+pub enum NotebookCommand {
+    /// List notebooks as `path<TAB>number of notes`
+    List,
+    /// Create a notebook and any parents it needs
+    Create {
+        path: String,
+    },
+    /// Rename a notebook or move it into another one
+    Rename {
+        from: String,
+        to: String,
+    },
+    /// Delete an empty notebook
+    Rm {
+        path: String,
+    },
+}
+
+/// Runs `args`, resolving unset flags from the config file.
+pub fn run(args: CliArgs) -> Result<(), String> {
+    let file = Config::load(args.config.as_deref())?;
+    let daily = file.daily_template();
+    let reminders = file.reminders()?;
+    let (url, path) = resolve_mode(args.url, args.path, &file)?;
+    let path = path.map(PathBuf::from);
+
+    let store = NoteStore::open(
+        url.as_deref(),
+        args.auth.or(file.auth).as_deref(),
+        path.as_deref(),
+        args.passphrase_file.or(file.passphrase_file).as_deref(),
+    )?;
+
+    if let (NoteStore::Local(path), Some(settings)) = (&store, file.git) {
+        crate::git::enable(path.as_deref(), settings)?;
+    }
+
+    let mut out = String::new();
+    let result = run_command(&store, args.command, args.json, &daily, &reminders, &mut out);
+    // Commit right away, as this process will be gone before the delay
+    if let Err(e) = crate::git::flush() {
+        eprintln!("Warning: {}", e);
+    }
+    result?;
+
+    // A reader that stops early, like `head`, is not an error
+    match std::io::stdout().write_all(out.as_bytes()) {
+        Err(e) if e.kind() != std::io::ErrorKind::BrokenPipe => Err(format!("Failed to write output: {}", e)),
+        _ => Ok(()),
+    }
+}
+
+fn run_command(
+    store: &NoteStore,
+    command: CliCommand,
+    json: bool,
+    daily: &DailyTemplate,
+    reminders: &ReminderSettings,
+    out: &mut String,
+) -> Result<(), String> {
+    match command {
+        CliCommand::List { archived, tag, notebook } => {
+            let tag = tag.as_deref().map(normalize_tag);
+            let notebook = notebook.as_deref().map(|n| notebooks::normalize_optional(Some(n))).transpose()?;
+            let notes: Vec<NoteBlock> = store.notes(archived)?
+                .into_iter()
+                .filter(|n| tag.as_ref().is_none_or(|t| n.tags.contains(t)))
+                .filter(|n| notebook.as_ref().is_none_or(|nb| *nb == n.notebook))
+                .map(note_lock::redact)
+                .collect();
+            print_list(&notes, json, out)
+        }
+        CliCommand::Show { id } => {
+            let (note, _) = store.get(&id)?;
+            print_note(&note, json, out)
+        }
+        CliCommand::Daily { date } => {
+            let note = store.daily(parse_date(&date)?, daily)?;
+            print_note(&note, json, out)
+        }
+        CliCommand::New { title, content: _, tags, edit, template: Some(name), vars, notebook } => {
+            let mut request = TemplateRequest {
+                title: (!title.is_empty()).then_some(title),
+                tags: tags.iter().map(|t| normalize_tag(t)).collect(),
+                values: BTreeMap::new(),
+            };
+            for var in &vars {
+                let (key, value) = var.split_once('=').ok_or(format!("Expected name=value, got {:?}", var))?;
+                request.values.insert(key.trim().to_string(), value.to_string());
+            }
+            prompt_missing_values(store, &name, &mut request.values)?;
+
+            let mut note = store.from_template(&name, &request)?;
+            if notebook.is_some() {
+                store.move_note(&note.id, notebook.as_deref())?;
+            }
+            if edit {
+                note.content = edit_text(&note.content)?;
+                store.save(note.clone())?;
+            }
+            let _ = writeln!(out, "{}", note.id);
+            Ok(())
+        }
+        CliCommand::Templates => {
+            let templates = store.templates()?;
+            if json {
+                return print_json(&templates, out);
+            }
+            for template in templates {
+                let title = template.title.as_deref().unwrap_or("");
+                let _ = writeln!(out, "{}\t{}\t{}", template.name, title, template.variables.join(", "));
+            }
+            Ok(())
+        }
+        CliCommand::New { title, content, tags, edit, template: None, notebook, .. } => {
+            let notebook = notebooks::normalize_optional(notebook.as_deref())?;
+            let mut content = content.unwrap_or_default();
+            if edit {
+                content = edit_text(&content)?;
+            }
+            let id = create_note(store, title, content, &tags, notebook)?;
+            let _ = writeln!(out, "{}", id);
+            Ok(())
+        }
+        CliCommand::Edit { id, title } => {
+            let (mut note, _) = store.get(&id)?;
+            if let Some(title) = title {
+                note.title = title;
+                return store.save(note);
+            }
+
+            ensure_unlocked(&note)?;
+            let content = edit_text(&note.content)?;
+            if content == note.content {
+                eprintln!("No changes");
+                return Ok(());
+            }
+            note.content = content;
+            store.save(note)
+        }
+        CliCommand::Append { id, text } => store.append(&id, &text.join(" ")),
+        CliCommand::Capture { title, tags, append_to } => {
+            let mut text = String::new();
+            std::io::stdin().read_to_string(&mut text).map_err(|e| format!("Failed to read stdin: {}", e))?;
+            let text = text.trim_end();
+            if text.is_empty() {
+                return Err("Nothing to capture on stdin".to_string());
+            }
+
+            if let Some(id) = append_to {
+                return store.append(&id, text);
+            }
+            let title = title.unwrap_or_else(|| format!("Captured {}", chrono::Local::now().format("%Y-%m-%d %H:%M")));
+            let id = create_note(store, title, text.to_string(), &tags, None)?;
+            let _ = writeln!(out, "{}", id);
+            Ok(())
+        }
+        CliCommand::Tag { id, tags, remove } => {
+            let (mut note, _) = store.get(&id)?;
+            for tag in tags.iter().map(|t| normalize_tag(t)) {
+                if remove {
+                    note.tags.retain(|t| *t != tag);
+                } else {
+                    push_unique(&mut note.tags, tag);
+                }
+            }
+            store.save(note)
+        }
+        CliCommand::Tasks { open, done, tag } => {
+            let filter = TaskFilter {
+                done: (open || done).then_some(done),
+                tag: tag.as_deref().map(normalize_tag),
+            };
+            let tasks = store.tasks(&filter)?;
+            if json {
+                return print_json(&tasks, out);
+            }
+            for task in tasks {
+                let checkbox = if task.done { "[x]" } else { "[ ]" };
+                let due = task.due.as_deref().unwrap_or("");
+                let _ = writeln!(out, "{}:{}\t{}\t{}\t{}", task.note_id, task.line, checkbox, task.text, due);
+            }
+            Ok(())
+        }
+        CliCommand::Ics { output, todo, open, tag } => {
+            // Parsed here rather than on the server, so this also works with
+            // end-to-end encryption
+            let filter = TaskFilter { done: open.then_some(false), tag: tag.as_deref().map(normalize_tag) };
+            let kind = if todo { CalendarKind::Todo } else { CalendarKind::Event };
+            let calendar = tasks_calendar(&store.notes(false)?, &filter, kind, reminders);
+            match output {
+                Some(path) => fs::write(&path, calendar).map_err(|e| format!("Failed to write {}: {}", path.display(), e)),
+                None => {
+                    out.push_str(&calendar);
+                    Ok(())
+                }
+            }
+        }
+        CliCommand::Check { id, line, uncheck } => {
+            let update = TaskUpdate { done: Some(!uncheck), text: None };
+            store.set_task(&id, line, &update).map(|_| ())
+        }
+        CliCommand::Archive { id } => {
+            let (_, archived) = store.get(&id)?;
+            if archived {
+                return Err(format!("Note {} is already archived", id));
+            }
+            store.archive(&id)
+        }
+        CliCommand::Rm { id } => {
+            let (_, archived) = store.get(&id)?;
+            store.delete(&id, archived)
+        }
+        CliCommand::Mv { id, notebook } => {
+            store.get(&id)?;
+            store.move_note(&id, notebook.as_deref())
+        }
+        CliCommand::Notebook { command: NotebookCommand::List } => {
+            let notebooks = store.notebooks()?;
+            if json {
+                return print_json(&notebooks, out);
+            }
+            for notebook in notebooks {
+                let _ = writeln!(out, "{}\t{}", notebook.path, notebook.notes);
+            }
+            Ok(())
+        }
+        CliCommand::Notebook { command: NotebookCommand::Create { path } } => store.create_notebook(&path).map(|_| ()),
+        CliCommand::Notebook { command: NotebookCommand::Rename { from, to } } => store.rename_notebook(&from, &to),
+        CliCommand::Notebook { command: NotebookCommand::Rm { path } } => store.delete_notebook(&path),
+        CliCommand::Search { query, archived } => {
+            // Locked notes are matched on their title and tags only
+            let notes: Vec<NoteBlock> = store.notes(archived)?
+                .into_iter()
+                .map(note_lock::redact)
+                .filter(|n| matches_query(n, &query))
+                .collect();
+            print_list(&notes, json, out)
+        }
+    }
+}
+
+/// Asks on the terminal for the prompt variables of template `name` that
+/// have no value yet. Without a terminal they are left for the template to
+/// report as missing.
+fn prompt_missing_values(store: &NoteStore, name: &str, values: &mut BTreeMap<String, String>) -> Result<(), String> {
+    if !std::io::stdin().is_terminal() {
+        return Ok(());
+    }
+    let Some(template) = store.templates()?.into_iter().find(|t| t.name == name) else {
+        return Ok(());
+    };
+    for variable in template.variables {
+        if values.contains_key(&variable) {
+            continue;
+        }
+        eprint!("{}: ", variable);
+        let mut value = String::new();
+        std::io::stdin().read_line(&mut value).map_err(|e| format!("Failed to read stdin: {}", e))?;
+        values.insert(variable, value.trim_end_matches(['\r', '\n']).to_string());
+    }
+    Ok(())
+}
+
+/// Saves a new note after the active ones and returns its ID.
+fn create_note(store: &NoteStore, title: String, content: String, tags: &[String], notebook: Option<String>) -> Result<String, String> {
+    let mut note = NoteBlock {
+        id: generate_note_id(),
+        title,
+        content,
+        is_collapsed: false,
+        order: store.notes(false)?.len() as i32,
+        tags: Vec::new(),
+        created_at: None,
+        updated_at: None,
+        attachments: None,
+        locked: None,
+        remind_at: None,
+        notebook,
+    };
+    for tag in tags {
+        push_unique(&mut note.tags, normalize_tag(tag));
+    }
+    let id = note.id.clone();
+    store.save(note)?;
+    Ok(id)
+}
+
+fn ensure_unlocked(note: &NoteBlock) -> Result<(), String> {
+    if note_lock::is_locked(note) {
+        return Err(format!("Note {} is locked; unlock it in the Zenus app", note.id));
+    }
+    Ok(())
+}
+
+fn normalize_tag(tag: &str) -> String {
+    tag.trim().trim_start_matches('#').to_string()
+}
+
+fn display_title(note: &NoteBlock) -> &str {
+    if note.title.is_empty() { "Untitled" } else { &note.title }
+}
+
+/// Same matching as the search box in the app: case-insensitive substring
+/// of the title, the body or any tag.
+fn matches_query(note: &NoteBlock, query: &str) -> bool {
+    let query = query.to_lowercase();
+    note.title.to_lowercase().contains(&query)
+        || note.content.to_lowercase().contains(&query)
+        || note.tags.iter().any(|t| t.to_lowercase().contains(&query))
+}
+
+fn print_json<T: Serialize>(value: &T, out: &mut String) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(value).map_err(|e| format!("Failed to serialize output: {}", e))?;
+    let _ = writeln!(out, "{}", json);
+    Ok(())
+}
+
+fn print_note(note: &NoteBlock, json: bool, out: &mut String) -> Result<(), String> {
+    ensure_unlocked(note)?;
+    if json {
+        return print_json(note, out);
+    }
+    let _ = writeln!(out, "id: {}", note.id);
+    let _ = writeln!(out, "title: {}", display_title(note));
+    if let Some(notebook) = &note.notebook {
+        let _ = writeln!(out, "notebook: {}", notebook);
+    }
+    if !note.tags.is_empty() {
+        let _ = writeln!(out, "tags: {}", note.tags.join(", "));
+    }
+    if let Some(created_at) = &note.created_at {
+        let _ = writeln!(out, "created: {}", created_at);
+    }
+    if let Some(updated_at) = &note.updated_at {
+        let _ = writeln!(out, "updated: {}", updated_at);
+    }
+    let _ = writeln!(out, "\n{}", note.content);
+    Ok(())
+}
+
+fn print_list(notes: &[NoteBlock], json: bool, out: &mut String) -> Result<(), String> {
+    if json {
+        return print_json(&notes, out);
+    }
+    for note in notes {
+        let mut title = display_title(note).to_string();
+        if note_lock::is_locked(note) {
+            title.push_str(" (locked)");
+        }
+        let tags: Vec<String> = note.tags.iter().map(|t| format!("#{}", t)).collect();
+        let _ = writeln!(out, "{}\t{}\t{}", note.id, title, tags.join(" "));
+    }
+    Ok(())
+}
+
+/// Lets the user edit `text` in `$VISUAL` or `$EDITOR` (falling back to
+/// `vi`) and returns the result.
+fn edit_text(text: &str) -> Result<String, String> {
+    let editor = std::env::var("VISUAL")
+        .or_else(|_| std::env::var("EDITOR"))
+        .unwrap_or_else(|_| "vi".to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().ok_or("$EDITOR is empty")?;
+
+    // The note may be decrypted, so it goes into a private directory with a
+    // random name that is removed again on every way out of here
+    let mut builder = tempfile::Builder::new();
+    builder.prefix("zenus-edit-");
+    #[cfg(unix)]
+    builder.permissions(std::os::unix::fs::PermissionsExt::from_mode(0o700));
+    let dir = builder.tempdir()
+        .map_err(|e| format!("Failed to create temporary directory: {}", e))?;
+    let path = dir.path().join("note.md");
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(&path)
+        .and_then(|mut file| file.write_all(text.as_bytes()))
+        .map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+
+    let status = std::process::Command::new(program).args(parts).arg(&path).status();
+    let result = match status {
+        Ok(status) if status.success() => {
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))
+        }
+        Ok(status) => Err(format!("{} exited with {}", program, status)),
+        Err(e) => Err(format!("Failed to start {}: {}", program, e)),
+    };
+    let _ = dir.close();
+    result
+}
@@ -0,0 +1,9 @@
+use clap::Parser;
+use zenus_lib::cli::{run, CliArgs};
+
+fn main() {
+    if let Err(e) = run(CliArgs::parse()) {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,272 @@
+//! Settings shared by `zenus` and `zenus-headless`.
+//!
+//! Values are resolved with the following precedence, highest first:
+//! command line flags, `ZENUS_*` environment variables, the TOML config file
+//! and finally the built-in defaults.
+
+use crate::daily::DailyTemplate;
+use crate::git::GitSettings;
+use crate::logging::LogFormat;
+use crate::reminders::{parse_time, ReminderSettings};
+use crate::service::parse_socket_mode;
+use crate::webhooks::Webhook;
+use crate::ServerOptions;
+use serde::Deserialize;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_PORT: u16 = 8888;
+pub const DEFAULT_LOG_LEVEL: &str = "info";
+
+/// Contents of the TOML config file. Every key is optional.
+#[derive(Deserialize, Default, Debug, Clone)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub url: Option<String>,
+    pub auth: Option<String>,
+    pub path: Option<String>,
+    pub log_level: Option<String>,
+    pub log_format: Option<LogFormat>,
+    pub pidfile: Option<PathBuf>,
+    pub socket: Option<PathBuf>,
+    pub socket_mode: Option<String>,
+    /// File holding the end-to-end encryption or vault passphrase
+    pub passphrase_file: Option<PathBuf>,
+    /// Minutes of inactivity before the encrypted vault locks, 0 for never
+    pub auto_lock: Option<u64>,
+    /// Markdown file new daily notes are created from
+    pub daily_template: Option<PathBuf>,
+    /// Title of new daily notes, with the same placeholders [default: {{date}}]
+    pub daily_title: Option<String>,
+    /// Time of day for reminders of due dates without a time [default: 09:00]
+    pub reminder_time: Option<String>,
+    /// URL the server posts due reminders to (Server Mode)
+    pub reminder_webhook: Option<String>,
+    /// `[[webhooks]]` called when notes change (Server Mode)
+    pub webhooks: Vec<Webhook>,
+    /// `[git]` commits every change to the notes directory (Local and Server Mode)
+    pub git: Option<GitSettings>,
+}
+
+impl Config {
+    /// `$XDG_CONFIG_HOME/zenus/config.toml` or the platform equivalent.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("zenus").join("config.toml"))
+    }
+
+    /// Loads the config file at `path`, or the default location if `None`.
+    ///
+    /// A missing file at the default location is not an error, but an
+    /// explicitly requested one is.
+    pub fn load(path: Option<&Path>) -> Result<Config, String> {
+        let (path, required) = match path {
+            Some(p) => (p.to_path_buf(), true),
+            None => match Self::default_path() {
+                Some(p) => (p, false),
+                None => return Ok(Config::default()),
+            },
+        };
+
+        if !path.exists() {
+            if required {
+                return Err(format!("Config file not found: {}", path.display()));
+            }
+            return Ok(Config::default());
+        }
+
+        let content = fs::read_to_string(&path)
+            .map_err(|e| format!("Failed to read config file {}: {}", path.display(), e))?;
+        toml::from_str(&content)
+            .map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+    }
+
+    pub fn daily_template(&self) -> DailyTemplate {
+        DailyTemplate { title: self.daily_title.clone(), path: self.daily_template.clone() }
+    }
+
+    pub fn reminders(&self) -> Result<ReminderSettings, String> {
+        let mut settings = ReminderSettings { webhook: self.reminder_webhook.clone(), ..Default::default() };
+        if let Some(time) = &self.reminder_time {
+            settings.time = parse_time(time).map_err(|e| format!("Invalid reminder_time: {}", e))?;
+        }
+        Ok(settings)
+    }
+}
+
+/// Flags understood by both binaries. Each one can also be set through the
+/// environment variable named in its help text.
+#[derive(clap::Args, Debug, Clone)]
+pub struct SharedArgs {
+    /// Path to the TOML config file [default: ~/.config/zenus/config.toml]
+    #[arg(long, env = "ZENUS_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Port to bind/connect to [default: 8888]
+    #[arg(long, env = "ZENUS_PORT")]
+    pub port: Option<u16>,
+
+    /// Authentication token/password
+    #[arg(long, env = "ZENUS_AUTH", hide_env_values = true)]
+    pub auth: Option<String>,
+
+    /// Custom path for notes directory
+    #[arg(long, env = "ZENUS_PATH")]
+    pub path: Option<String>,
+
+    /// Log level or filter directive, overridden by RUST_LOG [default: info]
+    #[arg(long, env = "ZENUS_LOG_LEVEL")]
+    pub log_level: Option<String>,
+
+    /// Log output format [default: text]
+    #[arg(long, value_enum, env = "ZENUS_LOG_FORMAT")]
+    pub log_format: Option<LogFormat>,
+
+    /// Write the server's process id to this file (Server Mode)
+    #[arg(long, env = "ZENUS_PIDFILE")]
+    pub pidfile: Option<PathBuf>,
+
+    /// Listen on a Unix socket instead of host:port (Server Mode)
+    #[arg(long, env = "ZENUS_SOCKET")]
+    pub socket: Option<PathBuf>,
+
+    /// Octal permissions for the Unix socket, e.g. 660
+    #[arg(long, env = "ZENUS_SOCKET_MODE")]
+    pub socket_mode: Option<String>,
+}
+
+/// Fully resolved settings after merging flags, environment and config file.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub port: u16,
+    pub auth: Option<String>,
+    /// Server of Client Mode, unset in Local and Server Mode
+    pub url: Option<String>,
+    pub path: Option<String>,
+    pub log_level: String,
+    pub log_format: LogFormat,
+    pub pidfile: Option<PathBuf>,
+    pub socket: Option<PathBuf>,
+    pub socket_mode: Option<u32>,
+    pub reminders: ReminderSettings,
+    /// The config file as loaded, for binary specific keys such as `host` and `url`
+    pub file: Config,
+}
+
+/// Picks Client Mode's `url` or Local Mode's `path`. One set on the
+/// command line or in the environment replaces the other from the config
+/// file; both set at the same level is an error.
+pub fn resolve_mode(url: Option<String>, path: Option<String>, file: &Config) -> Result<(Option<String>, Option<String>), String> {
+    match (url, path) {
+        (Some(_), Some(_)) => Err("Use either --url or --path, not both".to_string()),
+        (None, None) if file.url.is_some() && file.path.is_some() => {
+            Err("The config file sets both url and path; use either one".to_string())
+        }
+        (None, None) => Ok((file.url.clone(), file.path.clone())),
+        given => Ok(given),
+    }
+}
+
+impl SharedArgs {
+    /// Resolves the settings of a binary without Client Mode, which ignores
+    /// `url` in the config file.
+    pub fn resolve(self) -> Result<Settings, String> {
+        let file = Config::load(self.config.as_deref())?;
+        let path = self.path.clone().or_else(|| file.path.clone());
+        self.merge(file, None, path)
+    }
+
+    /// Resolves the settings of a binary that also takes `--url`, see
+    /// [`resolve_mode`].
+    pub fn resolve_with_url(self, url: Option<String>) -> Result<Settings, String> {
+        let file = Config::load(self.config.as_deref())?;
+        let (url, path) = resolve_mode(url, self.path.clone(), &file)?;
+        self.merge(file, url, path)
+    }
+
+    fn merge(self, file: Config, url: Option<String>, path: Option<String>) -> Result<Settings, String> {
+        let socket_mode = self.socket_mode
+            .or_else(|| file.socket_mode.clone())
+            .map(|mode| parse_socket_mode(&mode))
+            .transpose()?;
+        let reminders = file.reminders()?;
+
+        Ok(Settings {
+            port: self.port.or(file.port).unwrap_or(DEFAULT_PORT),
+            auth: self.auth.or_else(|| file.auth.clone()),
+            url,
+            path,
+            log_level: self.log_level
+                .or_else(|| file.log_level.clone())
+                .unwrap_or_else(|| DEFAULT_LOG_LEVEL.to_string()),
+            log_format: self.log_format.or(file.log_format).unwrap_or_default(),
+            pidfile: self.pidfile.or_else(|| file.pidfile.clone()),
+            socket: self.socket.or_else(|| file.socket.clone()),
+            socket_mode,
+            reminders,
+            file,
+        })
+    }
+}
+
+impl Settings {
+    pub fn server_options(&self, host: String) -> ServerOptions {
+        ServerOptions {
+            host,
+            port: self.port,
+            auth_token: self.auth.clone(),
+            local_path: self.path.clone(),
+            pidfile: self.pidfile.clone(),
+            unix_socket: self.socket.clone(),
+            unix_socket_mode: self.socket_mode,
+            daily: self.file.daily_template(),
+            reminders: self.reminders.clone(),
+            webhooks: self.file.webhooks.clone(),
+            git: self.file.git.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(config: &Path) -> SharedArgs {
+        SharedArgs {
+            config: Some(config.to_path_buf()),
+            port: None,
+            auth: None,
+            path: None,
+            log_level: None,
+            log_format: None,
+            pidfile: None,
+            socket: None,
+            socket_mode: None,
+        }
+    }
+
+    #[test]
+    fn flags_beat_the_config_file_which_beats_the_defaults() {
+        let dir = tempfile::tempdir().unwrap();
+        let config = dir.path().join("config.toml");
+        fs::write(&config, "port = 9000\nauth = \"file\"\nurl = \"http://file\"\npath = \"/file\"\n").unwrap();
+
+        // A mode given on the command line replaces the other one from the file
+        let client = SharedArgs { port: Some(9100), ..args(&config) }.resolve_with_url(Some("http://flag".to_string())).unwrap();
+        assert_eq!((client.url.as_deref(), client.path), (Some("http://flag"), None));
+        assert_eq!(client.port, 9100);
+        assert_eq!(client.auth.as_deref(), Some("file"));
+        assert_eq!(client.log_level, DEFAULT_LOG_LEVEL);
+        let local = SharedArgs { path: Some("/flag".to_string()), ..args(&config) }.resolve_with_url(None).unwrap();
+        assert_eq!((local.url, local.path.as_deref()), (None, Some("/flag")));
+
+        // Both at the same level is ambiguous
+        assert!(args(&config).resolve_with_url(None).is_err());
+        let both = SharedArgs { path: Some("/flag".to_string()), ..args(&config) };
+        assert!(both.resolve_with_url(Some("http://flag".to_string())).is_err());
+
+        // Binaries without Client Mode ignore url in the file
+        assert_eq!(args(&config).resolve().unwrap().path.as_deref(), Some("/file"));
+    }
+}
@@ -0,0 +1,132 @@
+//! Daily notes.
+//!
+//! Each date has exactly one daily note, found by an ID derived from the
+//! date: a snowflake for midnight UTC with machine ID 3, so it sorts with
+//! the other notes and never collides with IDs from [`crate::generate_note_id`]
+//! or the frontend. The note is created on first open from a template, with
+//! the placeholders of [`crate::templates::fill_placeholders`] filled in for
+//! that date, and tagged `daily`.
+
+use crate::templates::{fill_placeholders, Placeholders};
+use crate::{load_note_local, load_notes_local, save_block_local, NoteBlock};
+use chrono::{Duration, NaiveDate};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+pub const DAILY_TAG: &str = "daily";
+pub const DEFAULT_TITLE: &str = "{{date}}";
+
+const MACHINE_ID: u64 = 3;
+
+// Snowflakes keep the timestamp in the top 42 bits, which runs out in 2109
+const MAX_MILLIS: i64 = 1 << 42;
+
+// Serialises the check-then-create of daily notes
+static DAILY_LOCK: Mutex<()> = Mutex::new(());
+
+/// How new daily notes are filled in. The template file is read each time
+/// a note is created, so edits to it apply without a restart.
+#[derive(Clone, Debug, Default)]
+pub struct DailyTemplate {
+    /// Title with placeholders [default: `{{date}}`]
+    pub title: Option<String>,
+    /// Markdown file with the body, empty if not set
+    pub path: Option<PathBuf>,
+}
+
+impl DailyTemplate {
+    /// Title and body for the daily note of `date`.
+    pub fn render(&self, date: NaiveDate) -> Result<(String, String), String> {
+        let content = match &self.path {
+            Some(path) => fs::read_to_string(path)
+                .map_err(|e| format!("Failed to read daily note template {}: {}", path.display(), e))?,
+            None => String::new(),
+        };
+        let mut vars = Placeholders { date, ..Placeholders::now() };
+        let title = fill_placeholders(self.title.as_deref().unwrap_or(DEFAULT_TITLE), &vars)?;
+        vars.title = &title;
+        let content = fill_placeholders(&content, &vars)?;
+        Ok((title, content))
+    }
+}
+
+/// The stable note ID for the daily note of `date`. Dates before 1970 or
+/// after the snowflake timestamp runs out have none.
+pub fn daily_note_id(date: NaiveDate) -> Result<String, String> {
+    let millis = date.and_hms_opt(0, 0, 0)
+        .map(|t| t.and_utc().timestamp_millis())
+        .filter(|m| (0..MAX_MILLIS).contains(m))
+        .ok_or(format!("Daily notes are not supported for {}", date))?;
+    Ok((((millis as u64) << 22) | (MACHINE_ID << 12)).to_string())
+}
+
+/// Parses `YYYY-MM-DD`, or `today`, `yesterday` and `tomorrow` in local time.
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    let today = chrono::Local::now().date_naive();
+    match value {
+        "today" => Ok(today),
+        "yesterday" => Ok(today - Duration::days(1)),
+        "tomorrow" => Ok(today + Duration::days(1)),
+        _ => NaiveDate::parse_from_str(value, "%Y-%m-%d")
+            .map_err(|_| format!("Invalid date {:?}, expected YYYY-MM-DD", value)),
+    }
+}
+
+/// A new, unsaved daily note for `date`, placed after `existing` notes.
+pub fn new_daily_note(date: NaiveDate, template: &DailyTemplate, existing: usize) -> Result<NoteBlock, String> {
+    let (title, content) = template.render(date)?;
+    Ok(NoteBlock {
+        id: daily_note_id(date)?,
+        title,
+        content,
+        is_collapsed: false,
+        order: existing as i32,
+        tags: vec![DAILY_TAG.to_string()],
+        created_at: None,
+        updated_at: None,
+        attachments: None,
+        locked: None,
+        remind_at: None,
+        notebook: None,
+    })
+}
+
+/// Returns the daily note for `date`, creating it from `template` if it
+/// does not exist yet. An archived daily note is returned as is.
+pub fn open_daily_local(date: NaiveDate, template: &DailyTemplate, custom_path: Option<&Path>) -> Result<NoteBlock, String> {
+    let id = daily_note_id(date)?;
+    let _guard = DAILY_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    if let Some(note) = load_note_local(&id, custom_path)? {
+        return Ok(note);
+    }
+
+    let note = new_daily_note(date, template, load_notes_local(None, custom_path)?.len())?;
+    save_block_local(note, custom_path)?;
+    tracing::info!(note_id = %id, %date, "created daily note");
+    load_note_local(&id, custom_path)?.ok_or(format!("Note {} not found", id))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(value: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(value, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn daily_ids_are_snowflakes_for_midnight_utc() {
+        assert_eq!(daily_note_id(date("1970-01-01")).unwrap(), (3 << 12).to_string());
+        let id: u64 = daily_note_id(date("2026-10-19")).unwrap().parse().unwrap();
+        assert_eq!(id >> 22, 1_792_368_000_000);
+    }
+
+    #[test]
+    fn dates_without_a_snowflake_are_rejected() {
+        assert!(daily_note_id(date("1969-12-31")).is_err());
+        assert!(daily_note_id(date("2109-05-14")).is_ok());
+        assert!(daily_note_id(date("2109-05-16")).is_err());
+        assert!(daily_note_id(date("2500-01-01")).is_err());
+    }
+}
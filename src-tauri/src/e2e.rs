@@ -0,0 +1,527 @@
+//! Client-side end-to-end encryption for notes stored on a server.
+//!
+//! The client derives a key from a passphrase with Argon2id and encrypts
+//! each note's title, content and tags with XChaCha20-Poly1305 before
+//! sending it, using the note ID as associated data so ciphertexts cannot be
+//! swapped between notes. The server only ever sees
+//!
+//! ```text
+//! content: "zenus-e2e:v1:<key id>:<base64 nonce + ciphertext>"
+//! ```
+//!
+//! with an empty title and no tags. Order, collapse state, timestamps and
+//! attachment file names stay in the clear so the server can still sort,
+//! archive and garbage collect.
+//!
+//! The KDF parameters and a check value for each key live in a keyring the
+//! server stores verbatim at `/e2e`, so every client can derive the same key
+//! and detect a wrong passphrase. The first key in the keyring is the
+//! current one; during [`rotate`] the previous keys stay listed until every
+//! note has been re-encrypted.
+
+use crate::remote::{ApiRequest, ApiResponse};
+use crate::NoteBlock;
+use argon2::{Algorithm, Argon2, Params, Version};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use chacha20poly1305::aead::{Aead, KeyInit, Payload};
+use chacha20poly1305::{XChaCha20Poly1305, XNonce};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+pub const KEYRING_FORMAT: &str = "zenus-e2e-keyring";
+/// The server stores the keyring verbatim in this file of the notes
+/// directory; only clients can make sense of it.
+pub const KEYRING_FILE: &str = "e2e.json";
+pub const KEYRING_VERSION: u32 = 1;
+
+const CIPHERTEXT_PREFIX: &str = "zenus-e2e:v1:";
+const NONCE_LEN: usize = 24;
+const CHECK_PLAINTEXT: &[u8] = b"zenus-e2e-check";
+const CHECK_AAD: &[u8] = b"zenus-e2e-check";
+
+// Argon2id parameters for new keys (64 MiB, 3 passes)
+const KDF_MEMORY_KIB: u32 = 64 * 1024;
+const KDF_ITERATIONS: u32 = 3;
+const KDF_PARALLELISM: u32 = 1;
+
+/// Everything needed to re-derive one key from the passphrase.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct KeyParams {
+    pub id: String,
+    pub kdf: String,
+    pub salt: String,
+    #[serde(rename = "memoryKib")]
+    pub memory_kib: u32,
+    pub iterations: u32,
+    pub parallelism: u32,
+    /// A known plaintext encrypted with the key, to detect wrong passphrases
+    pub check: String,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Keyring {
+    pub format: String,
+    pub version: u32,
+    pub keys: Vec<KeyParams>,
+}
+
+impl Keyring {
+    fn new(keys: Vec<KeyParams>) -> Self {
+        Keyring { format: KEYRING_FORMAT.to_string(), version: KEYRING_VERSION, keys }
+    }
+}
+
+/// The encrypted fields of a note.
+#[derive(Serialize, Deserialize)]
+struct Sealed {
+    title: String,
+    content: String,
+    tags: Vec<String>,
+}
+
+/// A key derived from the passphrase, ready to encrypt and decrypt notes.
+pub struct VaultKey {
+    id: String,
+    cipher: XChaCha20Poly1305,
+}
+
+impl VaultKey {
+    /// Creates a new key with a random salt and returns its keyring entry.
+    pub fn generate(passphrase: &str) -> Result<(VaultKey, KeyParams), String> {
+        Self::generate_with(passphrase, KDF_MEMORY_KIB, KDF_ITERATIONS, KDF_PARALLELISM)
+    }
+
+    pub(crate) fn generate_with(passphrase: &str, memory_kib: u32, iterations: u32, parallelism: u32) -> Result<(VaultKey, KeyParams), String> {
+        let salt: [u8; 16] = rand::random();
+        let id: String = rand::random::<[u8; 4]>().iter().map(|b| format!("{:02x}", b)).collect();
+        let cipher = derive(passphrase, &salt, memory_kib, iterations, parallelism)?;
+        let key = VaultKey { id: id.clone(), cipher };
+
+        let params = KeyParams {
+            id,
+            kdf: "argon2id".to_string(),
+            salt: BASE64.encode(salt),
+            memory_kib,
+            iterations,
+            parallelism,
+            check: key.seal(CHECK_PLAINTEXT, CHECK_AAD)?,
+        };
+        Ok((key, params))
+    }
+
+    /// Derives the key described by `params`, failing if the passphrase is wrong.
+    pub fn unlock(params: &KeyParams, passphrase: &str) -> Result<VaultKey, String> {
+        if params.kdf != "argon2id" {
+            return Err(format!("Unsupported key derivation function '{}'", params.kdf));
+        }
+        let salt = BASE64.decode(&params.salt).map_err(|e| format!("Invalid key salt: {}", e))?;
+        let cipher = derive(passphrase, &salt, params.memory_kib, params.iterations, params.parallelism)?;
+        let key = VaultKey { id: params.id.clone(), cipher };
+
+        match key.open(&params.check, CHECK_AAD) {
+            Ok(plain) if plain == CHECK_PLAINTEXT => Ok(key),
+            _ => Err(format!("Wrong passphrase for encryption key {}", params.id)),
+        }
+    }
+
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Returns a copy of `block` with title, content and tags encrypted.
+    pub fn encrypt_note(&self, block: &NoteBlock) -> Result<NoteBlock, String> {
+        if is_encrypted(block) {
+            return Ok(block.clone());
+        }
+        let sealed = serde_json::to_vec(&Sealed {
+            title: block.title.clone(),
+            content: block.content.clone(),
+            tags: block.tags.clone(),
+        })
+        .map_err(|e| format!("Failed to serialize note: {}", e))?;
+
+        // The server can't see links in the ciphertext, so list them
+        let mut encrypted = block.clone();
+        crate::attachments::record_linked(&mut encrypted);
+        encrypted.title = String::new();
+        encrypted.tags = Vec::new();
+        encrypted.content = format!("{}{}:{}", CIPHERTEXT_PREFIX, self.id, self.seal(&sealed, block.id.as_bytes())?);
+        Ok(encrypted)
+    }
+
+    /// Returns a copy of `block` with title, content and tags decrypted.
+    /// Notes that are not encrypted are returned unchanged.
+    pub fn decrypt_note(&self, block: &NoteBlock) -> Result<NoteBlock, String> {
+        let Some((key_id, data)) = split_ciphertext(&block.content) else {
+            return Ok(block.clone());
+        };
+        if key_id != self.id {
+            return Err(format!(
+                "Note {} is encrypted with key {}, but the current key is {}. Finish the key rotation with `zenus e2e rotate`.",
+                block.id, key_id, self.id
+            ));
+        }
+
+        let plain = self.open(data, block.id.as_bytes())
+            .map_err(|_| format!("Failed to decrypt note {}: the data was tampered with", block.id))?;
+        let sealed: Sealed = serde_json::from_slice(&plain)
+            .map_err(|e| format!("Failed to parse decrypted note {}: {}", block.id, e))?;
+
+        let mut decrypted = block.clone();
+        decrypted.title = sealed.title;
+        decrypted.content = sealed.content;
+        decrypted.tags = sealed.tags;
+        Ok(decrypted)
+    }
+
+    pub(crate) fn seal(&self, plain: &[u8], aad: &[u8]) -> Result<String, String> {
+        let nonce_bytes: [u8; NONCE_LEN] = rand::random();
+        let ciphertext = self.cipher
+            .encrypt(XNonce::from_slice(&nonce_bytes), Payload { msg: plain, aad })
+            .map_err(|_| "Encryption failed".to_string())?;
+        let mut out = nonce_bytes.to_vec();
+        out.extend(ciphertext);
+        Ok(BASE64.encode(out))
+    }
+
+    pub(crate) fn open(&self, data: &str, aad: &[u8]) -> Result<Vec<u8>, String> {
+        let raw = BASE64.decode(data).map_err(|e| format!("Invalid ciphertext: {}", e))?;
+        if raw.len() < NONCE_LEN {
+            return Err("Invalid ciphertext: too short".to_string());
+        }
+        let (nonce, ciphertext) = raw.split_at(NONCE_LEN);
+        self.cipher
+            .decrypt(XNonce::from_slice(nonce), Payload { msg: ciphertext, aad })
+            .map_err(|_| "Decryption failed".to_string())
+    }
+}
+
+fn derive(passphrase: &str, salt: &[u8], memory_kib: u32, iterations: u32, parallelism: u32) -> Result<XChaCha20Poly1305, String> {
+    let params = Params::new(memory_kib, iterations, parallelism, Some(32))
+        .map_err(|e| format!("Invalid key derivation parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+        .map_err(|e| format!("Key derivation failed: {}", e))?;
+    let cipher = XChaCha20Poly1305::new(&key.into());
+    key.fill(0);
+    Ok(cipher)
+}
+
+/// Splits `zenus-e2e:v1:<key id>:<data>` into key id and data.
+fn split_ciphertext(content: &str) -> Option<(&str, &str)> {
+    content.strip_prefix(CIPHERTEXT_PREFIX)?.split_once(':')
+}
+
+pub fn is_encrypted(block: &NoteBlock) -> bool {
+    split_ciphertext(&block.content).is_some()
+}
+
+/// Reads a passphrase from a file, dropping the trailing newline.
+pub fn read_passphrase(path: &Path) -> Result<String, String> {
+    let raw = std::fs::read_to_string(path)
+        .map_err(|e| format!("Failed to read passphrase file {}: {}", path.display(), e))?;
+    let passphrase = raw.trim_end_matches(['\r', '\n']).to_string();
+    if passphrase.is_empty() {
+        return Err(format!("Passphrase file {} is empty", path.display()));
+    }
+    Ok(passphrase)
+}
+
+/// Blocking client for the server's keyring and note routes, used at
+/// startup and by the `zenus e2e` commands.
+pub struct Remote {
+    client: reqwest::blocking::Client,
+    api_url: String,
+    auth_token: Option<String>,
+}
+
+impl Remote {
+    /// `url` is the server URL as given on the command line and `api_url`
+    /// the base returned by [`crate::check_server_compatibility`].
+    pub fn new(url: &str, api_url: &str, auth_token: Option<&str>) -> Result<Self, String> {
+        Ok(Remote {
+            client: crate::blocking_http_client(url)?,
+            api_url: api_url.to_string(),
+            auth_token: auth_token.map(String::from),
+        })
+    }
+
+    /// Sends `request` to the server.
+    pub(crate) fn send(&self, request: ApiRequest) -> Result<ApiResponse, String> {
+        crate::remote::send_blocking(&self.client, &self.api_url, self.auth_token.as_deref(), request)
+    }
+
+    fn request(&self, method: reqwest::Method, path: &str) -> reqwest::blocking::RequestBuilder {
+        let mut request = self.client.request(method, format!("{}{}", self.api_url, path));
+        if let Some(token) = &self.auth_token {
+            request = request.header("Authorization", token);
+        }
+        request
+    }
+
+    pub fn keyring(&self) -> Result<Option<Keyring>, String> {
+        let response = self.request(reqwest::Method::GET, "/e2e").send()
+            .map_err(|e| format!("Failed to send request: {}", e))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let keyring: Keyring = response.error_for_status()
+            .map_err(|e| format!("Server error: {}", e))?
+            .json()
+            .map_err(|e| format!("Failed to parse keyring: {}", e))?;
+        if keyring.format != KEYRING_FORMAT || keyring.version > KEYRING_VERSION || keyring.keys.is_empty() {
+            return Err("The server's encryption keyring is not supported by this version of Zenus".to_string());
+        }
+        Ok(Some(keyring))
+    }
+
+    fn put_keyring(&self, keyring: &Keyring) -> Result<(), String> {
+        self.request(reqwest::Method::PUT, "/e2e").json(keyring).send()
+            .map_err(|e| format!("Failed to send request: {}", e))?
+            .error_for_status()
+            .map_err(|e| format!("Server error: {}", e))?;
+        Ok(())
+    }
+
+    /// All active and archived notes, as stored on the server.
+    fn notes(&self) -> Result<Vec<NoteBlock>, String> {
+        let mut notes = Vec::new();
+        for subdir in [None, Some(crate::notebooks::ARCHIVE_DIR)] {
+            notes.extend(self.send(ApiRequest::notes(subdir, None)?)?.notes(None)?);
+        }
+        Ok(notes)
+    }
+
+    fn save(&self, block: &NoteBlock) -> Result<(), String> {
+        self.send(ApiRequest::save_note(block, None)?).map(|_| ())
+    }
+
+    /// Derives the current key for a client, or `None` if the server does
+    /// not use encryption.
+    ///
+    /// Fails if the server uses encryption but no passphrase was given, so a
+    /// client never shows ciphertext or mixes plaintext into the vault.
+    pub fn unlock(&self, passphrase: Option<&str>) -> Result<Option<VaultKey>, String> {
+        match (self.keyring()?, passphrase) {
+            (Some(keyring), Some(passphrase)) => VaultKey::unlock(&keyring.keys[0], passphrase).map(Some),
+            (Some(_), None) => Err(
+                "The server uses end-to-end encryption; set passphrase_file in the config or pass --passphrase-file".to_string(),
+            ),
+            (None, Some(_)) => Err(
+                "A passphrase was given but the server does not use end-to-end encryption yet; run `zenus e2e init` first".to_string(),
+            ),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Counts of notes by encryption state.
+#[derive(Serialize, Debug, Default)]
+pub struct E2eStatus {
+    pub enabled: bool,
+    #[serde(rename = "currentKey")]
+    pub current_key: Option<String>,
+    /// Notes encrypted with the current key
+    pub current: usize,
+    /// Notes encrypted with an older key
+    pub outdated: usize,
+    pub plaintext: usize,
+}
+
+impl E2eStatus {
+    pub fn summary(&self) -> String {
+        match &self.current_key {
+            None => format!("End-to-end encryption is off ({} plaintext notes)\n", self.plaintext),
+            Some(key) => format!(
+                "End-to-end encryption is on (key {})\n  {} notes encrypted with the current key\n  {} notes encrypted with an older key\n  {} plaintext notes\n",
+                key, self.current, self.outdated, self.plaintext
+            ),
+        }
+    }
+}
+
+pub fn status(remote: &Remote) -> Result<E2eStatus, String> {
+    let keyring = remote.keyring()?;
+    let current_key = keyring.map(|k| k.keys[0].id.clone());
+    let mut status = E2eStatus { enabled: current_key.is_some(), current_key, ..Default::default() };
+    for note in remote.notes()? {
+        match split_ciphertext(&note.content) {
+            None => status.plaintext += 1,
+            Some((id, _)) if Some(id) == status.current_key.as_deref() => status.current += 1,
+            Some(_) => status.outdated += 1,
+        }
+    }
+    Ok(status)
+}
+
+/// Turns on encryption and encrypts every plaintext note on the server.
+///
+/// Running it again with the same passphrase encrypts notes that were left
+/// in plaintext, e.g. because a previous run was interrupted.
+pub fn init(remote: &Remote, passphrase: &str) -> Result<E2eStatus, String> {
+    let key = match remote.keyring()? {
+        Some(keyring) => VaultKey::unlock(&keyring.keys[0], passphrase)
+            .map_err(|_| "End-to-end encryption is already set up with a different passphrase".to_string())?,
+        None => {
+            let (key, params) = VaultKey::generate(passphrase)?;
+            remote.put_keyring(&Keyring::new(vec![params]))?;
+            key
+        }
+    };
+
+    for note in remote.notes()? {
+        if !is_encrypted(&note) {
+            remote.save(&key.encrypt_note(&note)?)?;
+        }
+    }
+    status(remote)
+}
+
+/// The keys of a rotation to `new_passphrase`.
+struct Rotation {
+    new_key: VaultKey,
+    /// Keys the old passphrase unlocks, to decrypt notes not yet rotated
+    old_keys: Vec<VaultKey>,
+    /// The keyring during the rotation, with the new key first
+    keys: Vec<KeyParams>,
+    /// Whether `keys` differs from the keyring on the server
+    changed: bool,
+}
+
+/// Works out the keys for [`rotate`] without touching the server, so a
+/// wrong old passphrase fails before the keyring is changed.
+fn plan_rotation(keyring: &Keyring, old_passphrase: &str, new_passphrase: &str, generate: impl FnOnce(&str) -> Result<(VaultKey, KeyParams), String>) -> Result<Rotation, String> {
+    let unlock_old = |keys: &[KeyParams]| -> Result<Vec<VaultKey>, String> {
+        let old_keys: Vec<VaultKey> = keys.iter()
+            .filter_map(|params| VaultKey::unlock(params, old_passphrase).ok())
+            .collect();
+        if old_keys.is_empty() {
+            return Err("The old passphrase does not match any key on the server".to_string());
+        }
+        Ok(old_keys)
+    };
+
+    // Resume an interrupted rotation if the current key already is the new one
+    if keyring.keys.len() > 1 {
+        if let Ok(new_key) = VaultKey::unlock(&keyring.keys[0], new_passphrase) {
+            let old_keys = unlock_old(&keyring.keys[1..])?;
+            return Ok(Rotation { new_key, old_keys, keys: keyring.keys.clone(), changed: false });
+        }
+    }
+
+    let old_keys = unlock_old(&keyring.keys)?;
+    let (new_key, params) = generate(new_passphrase)?;
+    let mut keys = vec![params];
+    keys.extend(keyring.keys.iter().cloned());
+    Ok(Rotation { new_key, old_keys, keys, changed: true })
+}
+
+/// Re-encrypts every note with a key derived from `new_passphrase`.
+///
+/// The old passphrase is checked before anything is written. The new key is
+/// then added to the front of the keyring before any note is touched and the
+/// old keys are only dropped once the server lists every note with the new
+/// one, so an interrupted rotation can be resumed by running it again with
+/// the same passphrases.
+pub fn rotate(remote: &Remote, old_passphrase: &str, new_passphrase: &str) -> Result<E2eStatus, String> {
+    let keyring = remote.keyring()?
+        .ok_or("End-to-end encryption is not set up; run `zenus e2e init` first")?;
+
+    let Rotation { new_key, old_keys, mut keys, changed } =
+        plan_rotation(&keyring, old_passphrase, new_passphrase, VaultKey::generate)?;
+    if changed {
+        remote.put_keyring(&Keyring::new(keys.clone()))?;
+    }
+
+    for note in remote.notes()? {
+        let plain = match split_ciphertext(&note.content) {
+            None => note,
+            Some((id, _)) if id == new_key.id() => continue,
+            Some((id, _)) => {
+                let key = old_keys.iter().find(|k| k.id() == id)
+                    .ok_or(format!("Note {} is encrypted with key {}, which the old passphrase does not unlock", note.id, id))?;
+                key.decrypt_note(&note)?
+            }
+        };
+        remote.save(&new_key.encrypt_note(&plain)?)?;
+    }
+
+    // A note the loop did not see would be left with no key to open it
+    let rotated = status(remote)?;
+    if rotated.outdated > 0 || rotated.plaintext > 0 {
+        return Err(format!(
+            "{} notes are not encrypted with the new key yet; run the rotation again with the same passphrases",
+            rotated.outdated + rotated.plaintext
+        ));
+    }
+    keys.truncate(1);
+    remote.put_keyring(&Keyring::new(keys))?;
+    Ok(rotated)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Cheap KDF parameters so the tests don't spend seconds in Argon2
+    fn generate(passphrase: &str) -> Result<(VaultKey, KeyParams), String> {
+        VaultKey::generate_with(passphrase, 64, 1, 1)
+    }
+
+    fn keyring(passphrase: &str) -> Keyring {
+        Keyring::new(vec![generate(passphrase).unwrap().1])
+    }
+
+    #[test]
+    fn rotation_with_wrong_old_passphrase_keeps_the_keyring() {
+        let keyring = keyring("old");
+        let mut generated = false;
+        let result = plan_rotation(&keyring, "wrong", "new", |p| {
+            generated = true;
+            generate(p)
+        });
+        assert!(result.is_err());
+        assert!(!generated);
+    }
+
+    #[test]
+    fn rotation_puts_the_new_key_first() {
+        let keyring = keyring("old");
+        let rotation = plan_rotation(&keyring, "old", "new", generate).unwrap();
+        assert!(rotation.changed);
+        assert_eq!(rotation.keys.len(), 2);
+        assert_eq!(rotation.keys[0].id, rotation.new_key.id());
+        assert_eq!(rotation.keys[1].id, keyring.keys[0].id);
+        assert_eq!(rotation.old_keys.len(), 1);
+        assert!(VaultKey::unlock(&rotation.keys[0], "new").is_ok());
+    }
+
+    #[test]
+    fn interrupted_rotation_resumes_with_the_same_keys() {
+        let first = plan_rotation(&keyring("old"), "old", "new", generate).unwrap();
+        // The keyring as left on the server by the interrupted run
+        let interrupted = Keyring::new(first.keys.clone());
+
+        let resumed = plan_rotation(&interrupted, "old", "new", |_| -> Result<(VaultKey, KeyParams), String> { panic!("must not generate a key") }).unwrap();
+        assert!(!resumed.changed);
+        assert_eq!(resumed.new_key.id(), first.new_key.id());
+        assert_eq!(resumed.old_keys[0].id(), first.old_keys[0].id());
+        assert_eq!(resumed.keys.len(), 2);
+
+        // A wrong old passphrase still fails on resume
+        assert!(plan_rotation(&interrupted, "wrong", "new", generate).is_err());
+    }
+
+    #[test]
+    fn encrypted_notes_list_their_linked_attachments() {
+        let image = format!("{}.png", "ab".repeat(32));
+        let note = NoteBlock::test("e2e-attachments", &format!("![map](attachments/{})", image));
+        let (key, _) = generate("pw").unwrap();
+
+        let encrypted = key.encrypt_note(&note).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert_eq!(encrypted.attachments, Some(vec![image]));
+    }
+}
@@ -0,0 +1,386 @@
+//! Git storage: commit every change to the notes directory.
+//!
+//! With a `[git]` table in the config file, the notes directory is a git
+//! repository (created if needed, or part of an existing one) and every
+//! save, delete, archive, unarchive, reorder and move through the `*_local`
+//! functions is recorded with [`record`]. Changes are collected for
+//! `commit_delay` seconds and then committed together, with the actions and
+//! note IDs, never titles or notebook names, in the commit message. With a
+//! `remote`, each commit is rebased onto the remote branch and pushed.
+//!
+//! `shares.json` is never committed, since its slugs are live public links
+//! that would stay in the history after being revoked.
+//!
+//! This runs the `git` command, so it needs git installed. Notes in an
+//! encrypted vault are committed as stored, so their diffs are ciphertext.
+
+use crate::{is_note_id, resolve_notes_dir};
+use crate::share::SHARES_FILE;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::sync::Mutex;
+use std::time::Duration;
+
+pub const DEFAULT_COMMIT_DELAY_SECS: u64 = 30;
+
+/// Used when git has no identity configured.
+const FALLBACK_NAME: &str = "Zenus";
+const FALLBACK_EMAIL: &str = "zenus@localhost";
+
+/// The `[git]` table of the config file.
+#[derive(Deserialize, Clone, Debug, Default)]
+#[serde(default, deny_unknown_fields)]
+pub struct GitSettings {
+    /// Seconds changes are collected before they are committed [default: 30]
+    pub commit_delay: Option<u64>,
+    /// Remote to pull from and push to after every commit, e.g. `origin`
+    pub remote: Option<String>,
+    /// Branch on the remote [default: the current branch]
+    pub branch: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Save,
+    Delete,
+    Archive,
+    Unarchive,
+    Reorder,
+    Move,
+    /// A notebook was renamed or deleted
+    Notebook,
+}
+
+impl Action {
+    fn as_str(self) -> &'static str {
+        match self {
+            Action::Save => "save",
+            Action::Delete => "delete",
+            Action::Archive => "archive",
+            Action::Unarchive => "unarchive",
+            Action::Reorder => "reorder",
+            Action::Move => "move",
+            Action::Notebook => "notebook",
+        }
+    }
+}
+
+#[derive(Serialize, Debug)]
+pub struct Commit {
+    pub commit: String,
+    /// Author date, RFC 3339
+    pub date: String,
+    pub message: String,
+    /// Changed files, relative to the notes directory
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<String>,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CommitDiff {
+    #[serde(flatten)]
+    pub commit: Commit,
+    /// Unified diff of the commit
+    pub diff: String,
+}
+
+#[derive(Clone, Debug)]
+struct Repo {
+    dir: PathBuf,
+    /// Pass the fallback identity to `git commit`
+    fallback_identity: bool,
+}
+
+impl Repo {
+    fn run(&self, args: &[&str]) -> Result<String, String> {
+        let mut command = Command::new("git");
+        command.arg("-C").arg(&self.dir);
+        if self.fallback_identity {
+            command.args(["-c", &format!("user.name={}", FALLBACK_NAME), "-c", &format!("user.email={}", FALLBACK_EMAIL)]);
+        }
+        let output = command.args(args).output().map_err(|e| format!("Failed to run git: {}", e))?;
+        if !output.status.success() {
+            return Err(format!("git {} failed: {}", args[0], String::from_utf8_lossy(&output.stderr).trim()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+
+    fn branch(&self, settings: &GitSettings) -> Result<String, String> {
+        match &settings.branch {
+            Some(branch) => Ok(branch.clone()),
+            None => Ok(self.run(&["rev-parse", "--abbrev-ref", "HEAD"])?.trim().to_string()),
+        }
+    }
+
+    /// Rebases local commits onto the remote branch.
+    fn pull(&self, settings: &GitSettings) -> Result<(), String> {
+        let Some(remote) = &settings.remote else { return Ok(()) };
+        let branch = self.branch(settings)?;
+        if let Err(e) = self.run(&["pull", "-q", "--rebase", "--autostash", remote, &branch]) {
+            // Leave the repository usable; the next commit tries again
+            let _ = self.run(&["rebase", "--abort"]);
+            return Err(e);
+        }
+        Ok(())
+    }
+
+    fn push(&self, settings: &GitSettings) -> Result<(), String> {
+        let Some(remote) = &settings.remote else { return Ok(()) };
+        let branch = self.branch(settings)?;
+        self.run(&["push", "-q", remote, &format!("HEAD:{}", branch)]).map(|_| ())
+    }
+}
+
+struct GitVault {
+    repo: Repo,
+    settings: GitSettings,
+    pending: Vec<(Action, Option<String>)>,
+    /// A commit is scheduled for the pending changes
+    scheduled: bool,
+}
+
+static GIT: Mutex<Option<GitVault>> = Mutex::new(None);
+
+// Serialises commits, so a flush never runs while the timer's is still going
+static COMMIT_LOCK: Mutex<()> = Mutex::new(());
+
+/// Turns on git storage for the notes directory, creating a repository if
+/// it is not in one yet and pulling from the remote.
+pub fn enable(custom_path: Option<&Path>, settings: GitSettings) -> Result<(), String> {
+    let dir = resolve_notes_dir(custom_path)?;
+    std::fs::create_dir_all(&dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    let mut repo = Repo { dir, fallback_identity: false };
+
+    if repo.run(&["rev-parse", "--is-inside-work-tree"]).is_err() {
+        repo.run(&["init", "-q"])?;
+        tracing::info!(path = %repo.dir.display(), "created git repository for notes");
+    }
+    repo.fallback_identity = repo.run(&["config", "user.email"]).is_err();
+    if let Err(e) = repo.pull(&settings) {
+        tracing::warn!(error = %e, "failed to pull notes");
+    }
+
+    if repo.run(&["ls-files", "--error-unmatch", "--", SHARES_FILE]).is_ok() {
+        tracing::warn!("{} is tracked by git; run `git rm --cached {}` in the notes directory to stop committing share links", SHARES_FILE, SHARES_FILE);
+    }
+
+    *GIT.lock().unwrap_or_else(|e| e.into_inner()) = Some(GitVault { repo, settings, pending: Vec::new(), scheduled: false });
+    Ok(())
+}
+
+/// Records a change to the notes in `custom_path`, to be committed with
+/// the others after the commit delay. Does nothing unless git storage is
+/// enabled for that directory.
+pub fn record(custom_path: Option<&Path>, action: Action, id: Option<&str>) {
+    let mut git = GIT.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(vault) = git.as_mut() else { return };
+    if resolve_notes_dir(custom_path).ok().as_deref() != Some(vault.repo.dir.as_path()) {
+        return;
+    }
+
+    let change = (action, id.map(String::from));
+    if !vault.pending.contains(&change) {
+        vault.pending.push(change);
+    }
+    if !vault.scheduled {
+        vault.scheduled = true;
+        let delay = Duration::from_secs(vault.settings.commit_delay.unwrap_or(DEFAULT_COMMIT_DELAY_SECS));
+        std::thread::spawn(move || {
+            std::thread::sleep(delay);
+            if let Err(e) = flush() {
+                tracing::warn!(error = %e, "failed to commit notes");
+            }
+        });
+    }
+}
+
+/// The commit message for a batch of changes.
+fn commit_message(changes: &[(Action, Option<String>)]) -> (String, String) {
+    let subject = match changes {
+        [(action, Some(id))] => {
+            let verb = action.as_str();
+            format!("{}{} note {}", verb[..1].to_uppercase(), &verb[1..], id)
+        }
+        [(Action::Reorder, None)] => "Reorder notes".to_string(),
+        [(Action::Notebook, None)] => "Update notebooks".to_string(),
+        _ => format!("Update notes ({} changes)", changes.len()),
+    };
+    let body = changes.iter()
+        .map(|(action, id)| match id {
+            Some(id) => format!("{} {}", action.as_str(), id),
+            None => action.as_str().to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    (subject, body)
+}
+
+/// Commits the pending changes now, and pushes them if a remote is set.
+pub fn flush() -> Result<(), String> {
+    let _commit = COMMIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let (repo, settings, changes) = {
+        let mut git = GIT.lock().unwrap_or_else(|e| e.into_inner());
+        let Some(vault) = git.as_mut() else { return Ok(()) };
+        vault.scheduled = false;
+        (vault.repo.clone(), vault.settings.clone(), std::mem::take(&mut vault.pending))
+    };
+    if changes.is_empty() {
+        return Ok(());
+    }
+
+    // Only the notes directory, in case it is part of a larger repository
+    let paths = pathspec(None)?;
+    let paths: Vec<&str> = paths.iter().map(String::as_str).collect();
+    repo.run(&[&["add", "-A", "--"], paths.as_slice()].concat())?;
+    if repo.run(&[&["status", "--porcelain", "--"], paths.as_slice()].concat())?.trim().is_empty() {
+        return Ok(());
+    }
+    let (subject, body) = commit_message(&changes);
+    repo.run(&[&["commit", "-q", "-m", &subject, "-m", &body, "--"], paths.as_slice()].concat())?;
+    tracing::info!(changes = changes.len(), "committed notes");
+
+    // A remote without the branch yet cannot be pulled from but can be
+    // pushed to; after a failed rebase the push fails and the reason is
+    // the rebase
+    let pulled = repo.pull(&settings);
+    repo.push(&settings).map_err(|e| pulled.err().unwrap_or(e))
+}
+
+pub fn is_enabled() -> bool {
+    GIT.lock().unwrap_or_else(|e| e.into_inner()).is_some()
+}
+
+/// Whether `value` looks like a full or abbreviated commit hash.
+pub fn is_commit_id(value: &str) -> bool {
+    (4..=40).contains(&value.len()) && value.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn enabled_repo() -> Result<Repo, String> {
+    GIT.lock().unwrap_or_else(|e| e.into_inner())
+        .as_ref()
+        .map(|vault| vault.repo.clone())
+        .ok_or("Git storage is not enabled".to_string())
+}
+
+/// The files of note `id` in any notebook or the archive, or the whole
+/// directory but the share links.
+fn pathspec(note: Option<&str>) -> Result<Vec<String>, String> {
+    match note {
+        Some(id) if !is_note_id(id) => Err(format!("Invalid note ID {:?}", id)),
+        Some(id) => Ok(vec![format!(":(glob)**/{}.md", id)]),
+        None => Ok(vec![".".to_string(), format!(":(exclude){}", SHARES_FILE)]),
+    }
+}
+
+/// Parses the `%H%x1f%aI%x1f%s` header of `git log` and `git show`.
+fn parse_header(line: &str) -> Option<Commit> {
+    let mut parts = line.splitn(3, '\x1f');
+    Some(Commit {
+        commit: parts.next()?.to_string(),
+        date: parts.next()?.to_string(),
+        message: parts.next()?.to_string(),
+        files: Vec::new(),
+    })
+}
+
+/// The latest `limit` commits, newest first, of note `id` or all notes.
+pub fn history(note: Option<&str>, limit: usize) -> Result<Vec<Commit>, String> {
+    let paths = pathspec(note)?;
+    let repo = enabled_repo()?;
+    let limit = limit.to_string();
+    let mut args = vec!["log", "-n", &limit, "--relative", "--name-only", "--format=%x1e%H%x1f%aI%x1f%s", "--"];
+    args.extend(paths.iter().map(String::as_str));
+
+    // A new repository has no commits, which git reports as an error
+    let log = match repo.run(&args) {
+        Ok(log) => log,
+        Err(_) if repo.run(&["rev-parse", "--verify", "-q", "HEAD"]).is_err() => return Ok(vec![]),
+        Err(e) => return Err(e),
+    };
+    Ok(log.split('\x1e')
+        .filter_map(|entry| {
+            let mut lines = entry.lines();
+            let mut commit = parse_header(lines.next()?)?;
+            commit.files = lines.filter(|l| !l.is_empty()).map(String::from).collect();
+            Some(commit)
+        })
+        .collect())
+}
+
+/// A commit and its diff, limited to note `id` if given.
+pub fn show(commit: &str, note: Option<&str>) -> Result<Option<CommitDiff>, String> {
+    if !is_commit_id(commit) {
+        return Err(format!("Invalid commit {:?}", commit));
+    }
+    let paths = pathspec(note)?;
+    let repo = enabled_repo()?;
+    let spec = format!("{}^{{commit}}", commit);
+    if repo.run(&["rev-parse", "--verify", "-q", &spec]).is_err() {
+        return Ok(None);
+    }
+
+    let mut args = vec!["show", "--relative", "--format=%H%x1f%aI%x1f%s", commit, "--"];
+    args.extend(paths.iter().map(String::as_str));
+    let output = repo.run(&args)?;
+    let (header, diff) = output.split_once('\n').unwrap_or((&output, ""));
+    let commit = parse_header(header).ok_or("Failed to parse git output")?;
+    Ok(Some(CommitDiff { commit, diff: diff.trim_start_matches('\n').to_string() }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{save_block_local, NoteBlock};
+
+    fn change(action: Action, id: Option<&str>) -> (Action, Option<String>) {
+        (action, id.map(String::from))
+    }
+
+    #[test]
+    fn commit_messages_name_the_actions_and_note_ids() {
+        assert_eq!(commit_message(&[change(Action::Save, Some("1"))]), ("Save note 1".to_string(), "save 1".to_string()));
+        assert_eq!(commit_message(&[change(Action::Reorder, None)]), ("Reorder notes".to_string(), "reorder".to_string()));
+        assert_eq!(commit_message(&[change(Action::Notebook, None)]), ("Update notebooks".to_string(), "notebook".to_string()));
+
+        let batch = [change(Action::Save, Some("1")), change(Action::Archive, Some("2")), change(Action::Reorder, None)];
+        assert_eq!(commit_message(&batch), ("Update notes (3 changes)".to_string(), "save 1\narchive 2\nreorder".to_string()));
+    }
+
+    #[test]
+    fn pathspecs_only_take_plain_note_ids() {
+        for id in ["*", "1*", "a/b", "../1", ":(top)1", "a:b", ""] {
+            assert!(pathspec(Some(id)).is_err(), "{:?}", id);
+        }
+        assert_eq!(pathspec(Some("1234-ab_c")).unwrap(), [":(glob)**/1234-ab_c.md"]);
+        assert!(pathspec(None).unwrap().contains(&format!(":(exclude){}", SHARES_FILE)));
+    }
+
+    #[test]
+    fn commit_ids_are_hex_hashes() {
+        assert!(is_commit_id("abcd"));
+        assert!(is_commit_id(&"0123456789abcdef".repeat(3)[..40]));
+        for value in ["abc", "HEAD", "main~1", "abcd:x", "-abcd", &"a".repeat(41)] {
+            assert!(!is_commit_id(value), "{:?}", value);
+        }
+    }
+
+    // The only test that enables git storage, which is global
+    #[test]
+    fn flush_commits_notes_but_not_share_links() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Some(dir.path());
+        enable(path, GitSettings { commit_delay: Some(3600), ..Default::default() }).unwrap();
+
+        save_block_local(NoteBlock::test("1", "Hello"), path).unwrap();
+        std::fs::write(dir.path().join(SHARES_FILE), "{}").unwrap();
+        flush().unwrap();
+
+        let repo = enabled_repo().unwrap();
+        let tracked = repo.run(&["ls-files"]).unwrap();
+        assert!(tracked.lines().any(|file| file == "1.md"));
+        assert!(!tracked.lines().any(|file| file == SHARES_FILE));
+        assert_eq!(history(None, 10).unwrap()[0].message, "Save note 1");
+        assert!(repo.run(&["status", "--porcelain", "--", SHARES_FILE]).unwrap().starts_with("??"));
+    }
+}
@@ -1,29 +1,37 @@
 use clap::Parser;
+use zenus_lib::config::SharedArgs;
+use zenus_lib::logging::init_logging;
 use zenus_lib::*;
 
 #[derive(Parser, Debug)]
 #[command(version, about = "Zenus Server - Headless note server", long_about = None)]
 struct Args {
-    /// Host to bind the server to
-    #[arg(long, default_value = "0.0.0.0")]
-    host: String,
+    /// Host to bind the server to [default: 0.0.0.0]
+    #[arg(long, env = "ZENUS_HOST")]
+    host: Option<String>,
 
-    /// Port to bind to
-    #[arg(long, default_value_t = 8888)]
-    port: u16,
-
-    /// Authentication token/password
-    #[arg(long)]
-    auth: Option<String>,
-
-    /// Custom path for notes directory
-    #[arg(long)]
-    path: Option<String>,
+    #[command(flatten)]
+    shared: SharedArgs,
 }
 
 #[tokio::main]
 async fn main() {
     let args = Args::parse();
+    let settings = match args.shared.resolve() {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    init_logging(&settings.log_level, settings.log_format);
+
+    let host = args.host
+        .or_else(|| settings.file.host.clone())
+        .unwrap_or_else(|| "0.0.0.0".to_string());
 
-    run_server(args.host, args.port, args.auth, args.path).await;
+    if let Err(e) = run_server(settings.server_options(host)).await {
+        eprintln!("Error: {}", e);
+        std::process::exit(1);
+    }
 }
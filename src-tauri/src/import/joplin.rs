@@ -0,0 +1,227 @@
+//! Joplin JEX importer.
+//!
+//! A JEX file is a plain tar archive holding one `<id>.md` file per item
+//! (notes, notebooks, tags, resources...) plus a `resources/` directory with
+//! the attachment files. Each item is serialised as its title, a blank line,
+//! the body and finally a block of `key: value` properties.
+
+use super::{normalize_timestamp, push_unique, Attachment, AttachmentData, ImportBatch, SkippedFile};
+use crate::{generate_note_id, NoteBlock};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Component, Path};
+
+const TYPE_NOTE: &str = "1";
+const TYPE_FOLDER: &str = "2";
+const TYPE_RESOURCE: &str = "4";
+const TYPE_TAG: &str = "5";
+const TYPE_NOTE_TAG: &str = "6";
+
+#[derive(Debug, Default)]
+struct Item {
+    title: String,
+    body: String,
+    props: HashMap<String, String>,
+}
+
+impl Item {
+    fn prop(&self, key: &str) -> &str {
+        self.props.get(key).map(String::as_str).unwrap_or("")
+    }
+}
+
+/// Reads every note in the JEX archive at `path`.
+///
+/// Notebooks become tags (nested notebooks as `parent/child`), alongside the
+/// note's own Joplin tags. Resources are imported as attachments under
+/// `joplin/` and `:/<id>` links in note bodies are pointed at them.
+pub fn read_jex(path: &Path) -> Result<ImportBatch, String> {
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    let mut archive = tar::Archive::new(file);
+
+    let mut items: Vec<(String, Item)> = Vec::new();
+    let mut resource_files: HashMap<String, (String, Vec<u8>)> = HashMap::new();
+
+    for entry in archive.entries().map_err(|e| format!("Invalid JEX archive: {}", e))? {
+        let mut entry = entry.map_err(|e| format!("Invalid JEX archive: {}", e))?;
+        let entry_path = entry.path().map_err(|e| format!("Invalid JEX archive: {}", e))?.into_owned();
+        let name = entry_path.to_string_lossy().to_string();
+        // Resource names end up in file paths, so nothing may point elsewhere
+        if !entry_path.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("Invalid JEX archive: unsafe path {:?}", name));
+        }
+
+        let mut data = Vec::new();
+        entry.read_to_end(&mut data).map_err(|e| format!("Failed to read {}: {}", name, e))?;
+
+        if let Ok(rest) = entry_path.strip_prefix("resources") {
+            // Only the file name is used, whatever folders the archive has
+            let Some(file_name) = rest.file_name().map(|n| n.to_string_lossy().to_string()) else { continue };
+            let id = file_name.split('.').next().unwrap_or(&file_name).to_string();
+            resource_files.insert(id, (file_name, data));
+        } else if name.ends_with(".md") {
+            items.push((name, parse_item(&String::from_utf8_lossy(&data))));
+        }
+    }
+
+    let folders: HashMap<&str, &Item> = of_type(&items, TYPE_FOLDER).map(|(_, item)| (item.prop("id"), item)).collect();
+    let tag_titles: HashMap<&str, &str> = of_type(&items, TYPE_TAG).map(|(_, item)| (item.prop("id"), item.title.as_str())).collect();
+    let mut note_tags: HashMap<&str, Vec<&str>> = HashMap::new();
+    for (_, link) in of_type(&items, TYPE_NOTE_TAG) {
+        if let Some(title) = tag_titles.get(link.prop("tag_id")) {
+            note_tags.entry(link.prop("note_id")).or_default().push(title);
+        }
+    }
+
+    let mut batch = ImportBatch::default();
+
+    let mut resource_paths: HashMap<String, String> = HashMap::new();
+    for (_, resource) in of_type(&items, TYPE_RESOURCE) {
+        let id = resource.prop("id");
+        if let Some((file_name, data)) = resource_files.remove(id) {
+            let path = format!("joplin/{}", file_name);
+            resource_paths.insert(id.to_string(), path.clone());
+            batch.attachments.push(Attachment { path, data: AttachmentData::Bytes(data) });
+        }
+    }
+
+    for (source, item) in of_type(&items, TYPE_NOTE) {
+        if item.prop("deleted_time").parse::<i64>().unwrap_or(0) > 0 {
+            batch.skipped.push(SkippedFile { source: source.clone(), reason: "note is in the trash".to_string() });
+            continue;
+        }
+
+        let mut tags = Vec::new();
+        if let Some(notebook) = notebook_path(&folders, item.prop("parent_id")) {
+            push_unique(&mut tags, notebook);
+        }
+        for tag in note_tags.get(item.prop("id")).into_iter().flatten() {
+            push_unique(&mut tags, tag.to_string());
+        }
+
+        let mut content = item.body.clone();
+        for (id, path) in &resource_paths {
+            content = content.replace(&format!(":/{}", id), &format!("attachments/{}", path));
+        }
+
+        let title = if item.title.is_empty() { "Untitled".to_string() } else { item.title.clone() };
+        batch.notes.push((
+            format!("{} ({})", title, source),
+            NoteBlock {
+                id: generate_note_id(),
+                title,
+                content,
+                is_collapsed: false,
+                order: 0,
+                tags,
+                created_at: normalize_timestamp(item.prop("created_time")),
+                updated_at: normalize_timestamp(item.prop("updated_time")),
+                attachments: None,
+                locked: None,
+                remind_at: None,
+                notebook: None,
+            },
+        ));
+    }
+
+    // Oldest first, so imported notes keep their original order
+    batch.notes.sort_by(|a, b| a.1.created_at.cmp(&b.1.created_at));
+    Ok(batch)
+}
+
+fn of_type<'a>(items: &'a [(String, Item)], ty: &'static str) -> impl Iterator<Item = &'a (String, Item)> {
+    items.iter().filter(move |(_, item)| item.prop("type_") == ty)
+}
+
+/// Builds `parent/child` for a notebook from its chain of parents.
+fn notebook_path<'a>(folders: &HashMap<&'a str, &'a Item>, mut id: &'a str) -> Option<String> {
+    let mut parts = Vec::new();
+    while let Some(folder) = folders.get(id) {
+        // Guard against cycles in malformed exports
+        if parts.len() > folders.len() {
+            break;
+        }
+        parts.push(folder.title.trim().to_string());
+        id = folder.prop("parent_id");
+    }
+
+    if parts.is_empty() {
+        return None;
+    }
+    parts.reverse();
+    Some(parts.join("/"))
+}
+
+/// Parses one serialised Joplin item.
+fn parse_item(raw: &str) -> Item {
+    let lines: Vec<&str> = raw.lines().collect();
+
+    // Properties run from the end of the file up to the last blank line
+    let mut props = HashMap::new();
+    let mut end = lines.len();
+    while end > 0 {
+        let line = lines[end - 1];
+        if line.trim().is_empty() {
+            break;
+        }
+        match line.split_once(": ").or_else(|| line.strip_suffix(':').map(|k| (k, ""))) {
+            Some((key, value)) if !key.contains(' ') => {
+                props.insert(key.to_string(), value.to_string());
+            }
+            _ => break,
+        }
+        end -= 1;
+    }
+
+    let text = &lines[..end];
+    let title = text.first().map(|l| l.trim().to_string()).unwrap_or_default();
+    let body = if text.len() > 2 { text[2..].join("\n") } else { String::new() };
+
+    Item {
+        title,
+        body: body.trim_end().to_string(),
+        props,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Writes a tar archive with the given entries, setting the names
+    /// directly so unsafe ones get through.
+    fn write_jex(entries: &[(&str, &[u8])]) -> tempfile::NamedTempFile {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (path, data) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.as_gnu_mut().unwrap().name[..path.len()].copy_from_slice(path.as_bytes());
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append(&header, *data).unwrap();
+        }
+        let file = tempfile::Builder::new().suffix(".jex").tempfile().unwrap();
+        std::fs::write(file.path(), builder.into_inner().unwrap()).unwrap();
+        file
+    }
+
+    #[test]
+    fn rejects_parent_dir_in_resource_name() {
+        let resource = b"Picture\n\nid: x\ntype_: 4";
+        let file = write_jex(&[
+            ("x.md", resource),
+            ("resources/x/../../../../.bashrc", b"echo owned"),
+        ]);
+        assert!(read_jex(file.path()).unwrap_err().contains("unsafe path"));
+    }
+
+    #[test]
+    fn keeps_only_the_file_name_of_resources() {
+        let resource = b"Picture\n\nid: abc\ntype_: 4";
+        let file = write_jex(&[("abc.md", resource), ("resources/sub/abc.png", b"png")]);
+        let batch = read_jex(file.path()).unwrap();
+        assert_eq!(batch.attachments.len(), 1);
+        assert_eq!(batch.attachments[0].path, "joplin/abc.png");
+    }
+}
@@ -0,0 +1,286 @@
+//! Importers that turn other note apps' data into [`NoteBlock`]s.
+//!
+//! Each importer only reads its source into an [`ImportBatch`]; writing the
+//! notes goes through the regular save path so imported notes look exactly
+//! like ones created in the app.
+
+use crate::{load_notes_local, resolve_notes_dir, save_block_local, write_block_local, NoteBlock};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+pub mod joplin;
+pub mod obsidian;
+pub mod standard_notes;
+
+/// Supported import sources.
+#[derive(clap::ValueEnum, serde::Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum ImportFormat {
+    /// Obsidian vault directory
+    Obsidian,
+    /// Joplin JEX export (.jex)
+    Joplin,
+    /// Standard Notes decrypted JSON backup
+    StandardNotes,
+}
+
+impl ImportFormat {
+    /// Reads the source at `path` in this format.
+    pub fn read(self, path: &Path) -> Result<ImportBatch, String> {
+        match self {
+            ImportFormat::Obsidian => obsidian::read_vault(path),
+            ImportFormat::Joplin => joplin::read_jex(path),
+            ImportFormat::StandardNotes => standard_notes::read_backup(path),
+        }
+    }
+}
+
+/// Notes and files read from an import source, not yet written anywhere.
+#[derive(Debug, Default)]
+pub struct ImportBatch {
+    /// Notes paired with the path they were read from, relative to the source
+    pub notes: Vec<(String, NoteBlock)>,
+    pub attachments: Vec<Attachment>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+#[derive(Debug)]
+pub struct Attachment {
+    /// Where the file ends up, relative to the `attachments` directory
+    pub path: String,
+    pub data: AttachmentData,
+}
+
+#[derive(Debug)]
+pub enum AttachmentData {
+    /// A file on disk, copied when the batch is written
+    File(PathBuf),
+    /// Contents already read from an archive
+    Bytes(Vec<u8>),
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct ImportedNote {
+    pub id: String,
+    pub title: String,
+    pub source: String,
+    pub tags: Vec<String>,
+}
+
+#[derive(Serialize, Debug, Clone)]
+pub struct SkippedFile {
+    pub source: String,
+    pub reason: String,
+}
+
+/// What an import did, or would do when `dry_run` is set.
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    #[serde(rename = "dryRun")]
+    pub dry_run: bool,
+    pub notes: Vec<ImportedNote>,
+    pub attachments: Vec<String>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+impl ImportReport {
+    /// Human readable report for the command line.
+    pub fn summary(&self) -> String {
+        let mut out = String::new();
+        let verb = if self.dry_run { "Would import" } else { "Imported" };
+
+        for note in &self.notes {
+            out.push_str(&format!("  note        {} -> \"{}\"", note.source, note.title));
+            if !note.tags.is_empty() {
+                out.push_str(&format!(" [{}]", note.tags.join(", ")));
+            }
+            out.push('\n');
+        }
+        for attachment in &self.attachments {
+            out.push_str(&format!("  attachment  {}\n", attachment));
+        }
+        for skipped in &self.skipped {
+            out.push_str(&format!("  skipped     {} ({})\n", skipped.source, skipped.reason));
+        }
+        out.push_str(&format!(
+            "{} {} notes and {} attachments, skipped {} files\n",
+            verb,
+            self.notes.len(),
+            self.attachments.len(),
+            self.skipped.len()
+        ));
+        out
+    }
+}
+
+impl ImportBatch {
+    /// Builds the report for this batch without writing anything.
+    pub fn report(&self, dry_run: bool) -> ImportReport {
+        ImportReport {
+            dry_run,
+            notes: self.notes.iter()
+                .map(|(source, block)| ImportedNote {
+                    id: block.id.clone(),
+                    title: block.title.clone(),
+                    source: source.clone(),
+                    tags: block.tags.clone(),
+                })
+                .collect(),
+            attachments: self.attachments.iter().map(|a| a.path.clone()).collect(),
+            skipped: self.skipped.clone(),
+        }
+    }
+
+    /// Drops notes whose title and content match an existing note or an
+    /// earlier note in the batch, reporting them as skipped.
+    pub fn drop_duplicates(&mut self, existing: &[NoteBlock]) {
+        let mut seen: HashMap<String, String> = existing.iter()
+            .map(|block| (content_hash(block), block.id.clone()))
+            .collect();
+
+        let notes = std::mem::take(&mut self.notes);
+        for (source, block) in notes {
+            let hash = content_hash(&block);
+            if let Some(id) = seen.get(&hash) {
+                self.skipped.push(SkippedFile {
+                    source,
+                    reason: format!("duplicate of note {}", id),
+                });
+            } else {
+                seen.insert(hash, block.id.clone());
+                self.notes.push((source, block));
+            }
+        }
+    }
+
+    /// Places the imported notes after the ones already in the vault.
+    pub fn append_after(&mut self, existing: usize) {
+        for (i, (_, block)) in self.notes.iter_mut().enumerate() {
+            block.order = (existing + i) as i32;
+        }
+    }
+}
+
+/// Writes a batch into the local notes directory.
+///
+/// Attachments are copied to `attachments/` below the notes directory,
+/// keeping their path inside the source.
+pub fn write_local(mut batch: ImportBatch, custom_path: Option<&Path>, dry_run: bool) -> Result<ImportReport, String> {
+    let active = load_notes_local(None, custom_path)?;
+    let mut existing = load_notes_local(Some("archive"), custom_path)?;
+    existing.extend(active.iter().cloned());
+
+    batch.drop_duplicates(&existing);
+    batch.append_after(active.len());
+
+    let report = batch.report(dry_run);
+    if dry_run {
+        return Ok(report);
+    }
+
+    let attachments_dir = resolve_notes_dir(custom_path)?.join("attachments");
+    for attachment in &batch.attachments {
+        let rel = Path::new(&attachment.path);
+        if rel.as_os_str().is_empty() || !rel.components().all(|c| matches!(c, Component::Normal(_))) {
+            return Err(format!("Invalid attachment path {:?}", attachment.path));
+        }
+        let dest = attachments_dir.join(rel);
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create directory: {}", e))?;
+        }
+        match &attachment.data {
+            AttachmentData::File(src) => fs::copy(src, &dest).map(|_| ()),
+            AttachmentData::Bytes(bytes) => fs::write(&dest, bytes),
+        }
+        .map_err(|e| format!("Failed to copy attachment {}: {}", attachment.path, e))?;
+    }
+
+    for (_, block) in batch.notes {
+        // Keep the source's modification time when it has one
+        match block.updated_at.clone() {
+            Some(updated_at) => write_block_local(block, custom_path, updated_at)?,
+            None => save_block_local(block, custom_path)?,
+        }
+    }
+
+    Ok(report)
+}
+
+/// SHA-256 of a note's title and content, used to spot duplicate imports.
+pub fn content_hash(block: &NoteBlock) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(block.title.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(block.content.trim().as_bytes());
+    format!("{:x}", hasher.finalize())
+}
+
+/// Normalises a source timestamp to RFC 3339, dropping unparseable values.
+pub(crate) fn normalize_timestamp(value: &str) -> Option<String> {
+    chrono::DateTime::parse_from_rfc3339(value)
+        .ok()
+        .map(|t| t.with_timezone(&chrono::Utc).to_rfc3339())
+}
+
+/// Collects `#tags` written inline in markdown, skipping headings and code.
+///
+/// Like Obsidian, a tag may contain letters, digits, `_`, `-` and `/` for
+/// nesting, but must not be purely numeric so `#1` in prose is ignored.
+pub fn extract_inline_tags(content: &str) -> Vec<String> {
+    let mut tags = Vec::new();
+    let mut in_fence = false;
+
+    for line in content.lines() {
+        if line.trim_start().starts_with("```") {
+            in_fence = !in_fence;
+            continue;
+        }
+        if in_fence {
+            continue;
+        }
+
+        let mut in_code = false;
+        let mut prev = ' ';
+        for (i, c) in line.char_indices() {
+            if c == '`' {
+                in_code = !in_code;
+            } else if c == '#' && !in_code && prev.is_whitespace() {
+                let tag: String = line[i + 1..]
+                    .chars()
+                    .take_while(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '/'))
+                    .collect();
+                if !tag.is_empty() && !tag.chars().all(|c| c.is_ascii_digit()) {
+                    push_unique(&mut tags, tag.trim_end_matches('/').to_string());
+                }
+            }
+            prev = c;
+        }
+    }
+
+    tags
+}
+
+pub(crate) fn push_unique(tags: &mut Vec<String>, tag: String) {
+    if !tag.is_empty() && !tags.contains(&tag) {
+        tags.push(tag);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn write_local_rejects_attachments_outside_the_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut batch = ImportBatch::default();
+        batch.attachments.push(Attachment { path: "../outside.txt".to_string(), data: AttachmentData::Bytes(b"x".to_vec()) });
+        let result = write_local(batch, Some(dir), false);
+        assert!(result.unwrap_err().contains("Invalid attachment path"));
+        assert!(!dir.join("outside.txt").exists());
+    }
+}
@@ -0,0 +1,161 @@
+//! Obsidian vault importer.
+//!
+//! Every markdown file becomes a note titled after its file name, so
+//! `[[wiki links]]` keep resolving after the import. Tags come from the YAML
+//! front matter and from inline `#tags`; other files are treated as
+//! attachments.
+
+use super::{extract_inline_tags, push_unique, Attachment, AttachmentData, ImportBatch, SkippedFile};
+use crate::{generate_note_id, NoteBlock};
+use std::fs;
+use std::path::Path;
+
+/// Reads every note and attachment in the vault at `vault`.
+pub fn read_vault(vault: &Path) -> Result<ImportBatch, String> {
+    if !vault.is_dir() {
+        return Err(format!("Not a directory: {}", vault.display()));
+    }
+
+    let mut batch = ImportBatch::default();
+    walk(vault, vault, &mut batch)?;
+    batch.notes.sort_by(|a, b| a.0.cmp(&b.0));
+    Ok(batch)
+}
+
+fn walk(vault: &Path, dir: &Path, batch: &mut ImportBatch) -> Result<(), String> {
+    let mut entries = fs::read_dir(dir)
+        .map_err(|e| format!("Failed to read directory {}: {}", dir.display(), e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read entry: {}", e))?;
+    entries.sort_by_key(|e| e.file_name());
+
+    for entry in entries {
+        let path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+
+        // Skips .obsidian (settings, plugins), .trash and other hidden files
+        if name.starts_with('.') {
+            continue;
+        }
+
+        let rel = path.strip_prefix(vault)
+            .unwrap_or(&path)
+            .to_string_lossy()
+            .replace('\\', "/");
+
+        // file_type doesn't follow symlinks, so a link back up the vault
+        // can't make this recurse forever
+        let file_type = entry.file_type().map_err(|e| format!("Failed to read entry {}: {}", rel, e))?;
+        if file_type.is_dir() {
+            walk(vault, &path, batch)?;
+        } else if file_type.is_symlink() && path.is_dir() {
+            batch.skipped.push(SkippedFile { source: rel, reason: "Symlinked directory".to_string() });
+        } else if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            match read_note(&path) {
+                Ok(block) => batch.notes.push((rel, block)),
+                Err(reason) => batch.skipped.push(SkippedFile { source: rel, reason }),
+            }
+        } else {
+            batch.attachments.push(Attachment { path: rel, data: AttachmentData::File(path.clone()) });
+        }
+    }
+
+    Ok(())
+}
+
+fn read_note(path: &Path) -> Result<NoteBlock, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let title = path.file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("Invalid filename")?
+        .to_string();
+
+    let (front_matter, body) = split_front_matter(&raw);
+    let mut tags = match front_matter {
+        Some(yaml) => front_matter_tags(yaml)?,
+        None => Vec::new(),
+    };
+    for tag in extract_inline_tags(body) {
+        push_unique(&mut tags, tag);
+    }
+
+    Ok(NoteBlock {
+        id: generate_note_id(),
+        title,
+        content: body.trim_start_matches(['\r', '\n']).to_string(),
+        is_collapsed: false,
+        order: 0,
+        tags,
+        created_at: None,
+        updated_at: None,
+        attachments: None,
+        locked: None,
+        remind_at: None,
+        notebook: None,
+    })
+}
+
+/// Splits `---` delimited YAML front matter from the markdown body.
+fn split_front_matter(raw: &str) -> (Option<&str>, &str) {
+    let Some(rest) = raw.strip_prefix("---\n").or_else(|| raw.strip_prefix("---\r\n")) else {
+        return (None, raw);
+    };
+
+    let mut offset = 0;
+    for line in rest.split_inclusive('\n') {
+        if line.trim_end() == "---" {
+            return (Some(&rest[..offset]), &rest[offset + line.len()..]);
+        }
+        offset += line.len();
+    }
+
+    // Unterminated front matter is just content
+    (None, raw)
+}
+
+/// Reads `tags` (or `tag`) from front matter, which Obsidian accepts as a
+/// list or as a comma / space separated string.
+fn front_matter_tags(yaml: &str) -> Result<Vec<String>, String> {
+    let value: serde_yaml::Value = serde_yaml::from_str(yaml)
+        .map_err(|e| format!("Invalid front matter: {}", e))?;
+
+    let mut tags = Vec::new();
+    for key in ["tags", "tag"] {
+        match value.get(key) {
+            Some(serde_yaml::Value::Sequence(items)) => {
+                for item in items {
+                    if let Some(tag) = item.as_str() {
+                        push_unique(&mut tags, tag.trim().trim_start_matches('#').to_string());
+                    }
+                }
+            }
+            Some(serde_yaml::Value::String(list)) => {
+                for tag in list.split(|c: char| c == ',' || c.is_whitespace()) {
+                    push_unique(&mut tags, tag.trim_start_matches('#').to_string());
+                }
+            }
+            _ => {}
+        }
+    }
+    Ok(tags)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinked_directories_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let vault = dir.path();
+        fs::create_dir_all(vault.join("sub")).unwrap();
+        fs::write(vault.join("sub").join("Note.md"), "Hello").unwrap();
+        std::os::unix::fs::symlink(vault, vault.join("sub").join("loop")).unwrap();
+
+        let batch = read_vault(vault).unwrap();
+        assert_eq!(batch.notes.len(), 1);
+        assert_eq!(batch.skipped.len(), 1);
+        assert_eq!(batch.skipped[0].source, "sub/loop");
+    }
+}
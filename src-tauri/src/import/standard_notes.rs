@@ -0,0 +1,144 @@
+//! Standard Notes backup importer.
+//!
+//! Reads the decrypted JSON backup (`Standard Notes Backup and Import File`),
+//! which lists notes and tags as `items`. Tags point at their notes through
+//! `references`; nested tags point at their parent the same way.
+
+use super::{normalize_timestamp, push_unique, ImportBatch, SkippedFile};
+use crate::{generate_note_id, NoteBlock};
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize)]
+struct Backup {
+    items: Vec<Item>,
+}
+
+#[derive(Deserialize)]
+struct Item {
+    uuid: String,
+    content_type: String,
+    content: serde_json::Value,
+    #[serde(default)]
+    created_at: Option<String>,
+    #[serde(default)]
+    updated_at: Option<String>,
+}
+
+#[derive(Deserialize, Default)]
+struct Content {
+    #[serde(default)]
+    title: String,
+    #[serde(default)]
+    text: String,
+    #[serde(default)]
+    references: Vec<Reference>,
+    #[serde(default)]
+    trashed: bool,
+}
+
+#[derive(Deserialize)]
+struct Reference {
+    uuid: String,
+    #[serde(default)]
+    content_type: String,
+    #[serde(default)]
+    reference_type: Option<String>,
+}
+
+/// Reads every note in the backup file at `path`.
+pub fn read_backup(path: &Path) -> Result<ImportBatch, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let backup: Backup = serde_json::from_str(&raw)
+        .map_err(|e| format!("Not a Standard Notes backup: {}", e))?;
+
+    let mut batch = ImportBatch::default();
+    let mut notes = Vec::new();
+    let mut tags = HashMap::new();
+
+    for item in backup.items {
+        if !item.content.is_object() {
+            // Encrypted items carry their content as an opaque string
+            if item.content_type == "Note" {
+                batch.skipped.push(SkippedFile {
+                    source: item.uuid,
+                    reason: "note is encrypted, export a decrypted backup instead".to_string(),
+                });
+            }
+            continue;
+        }
+
+        let content: Content = serde_json::from_value(item.content.clone()).unwrap_or_default();
+        match item.content_type.as_str() {
+            "Note" => notes.push((item, content)),
+            "Tag" => {
+                tags.insert(item.uuid.clone(), content);
+            }
+            _ => {}
+        }
+    }
+
+    // Note uuid -> full tag paths
+    let mut note_tags: HashMap<&str, Vec<String>> = HashMap::new();
+    for (uuid, tag) in &tags {
+        let name = tag_path(&tags, uuid);
+        for reference in tag.references.iter().filter(|r| r.content_type == "Note") {
+            note_tags.entry(reference.uuid.as_str()).or_default().push(name.clone());
+        }
+    }
+
+    for (item, content) in &notes {
+        if content.trashed {
+            batch.skipped.push(SkippedFile { source: item.uuid.clone(), reason: "note is in the trash".to_string() });
+            continue;
+        }
+
+        let mut tags = Vec::new();
+        for tag in note_tags.get(item.uuid.as_str()).into_iter().flatten() {
+            push_unique(&mut tags, tag.clone());
+        }
+
+        let title = if content.title.is_empty() { "Untitled".to_string() } else { content.title.clone() };
+        batch.notes.push((
+            format!("{} ({})", title, item.uuid),
+            NoteBlock {
+                id: generate_note_id(),
+                title,
+                content: content.text.clone(),
+                is_collapsed: false,
+                order: 0,
+                tags,
+                created_at: item.created_at.as_deref().and_then(normalize_timestamp),
+                updated_at: item.updated_at.as_deref().and_then(normalize_timestamp),
+                attachments: None,
+                locked: None,
+                remind_at: None,
+                notebook: None,
+            },
+        ));
+    }
+
+    batch.notes.sort_by(|a, b| a.1.created_at.cmp(&b.1.created_at));
+    Ok(batch)
+}
+
+/// Builds `parent/child` for a tag from its `TagToParentTag` references.
+fn tag_path(tags: &HashMap<String, Content>, uuid: &str) -> String {
+    let mut parts = Vec::new();
+    let mut current = uuid;
+    while let Some(tag) = tags.get(current) {
+        // Guard against cycles in malformed backups
+        if parts.len() > tags.len() {
+            break;
+        }
+        parts.push(tag.title.trim().to_string());
+        match tag.references.iter().find(|r| r.reference_type.as_deref() == Some("TagToParentTag")) {
+            Some(parent) => current = &parent.uuid,
+            None => break,
+        }
+    }
+    parts.reverse();
+    parts.join("/")
+}
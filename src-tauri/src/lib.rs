@@ -2,15 +2,46 @@ use serde::{Deserialize, Serialize};
 use std::fs;
 #[cfg(feature = "tauri-deps")]
 use tauri::State;
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use axum::{
-    routing::{get, post, delete},
-    Router, Json, extract::{Path, State as AxumState},
+    routing::{get, post, delete, patch},
+    Router, Json, extract::{DefaultBodyLimit, Path, Query, State as AxumState},
     http::{StatusCode, HeaderMap},
     response::IntoResponse,
 };
-use std::net::SocketAddr;
+use std::sync::Arc;
 use tower_http::cors::CorsLayer;
+use tower_http::request_id::{MakeRequestUuid, PropagateRequestIdLayer, SetRequestIdLayer};
+use tower_http::trace::{DefaultOnResponse, TraceLayer};
+
+pub mod attachments;
+pub mod bundle;
+pub mod calendar;
+pub mod cli;
+pub mod config;
+pub mod daily;
+pub mod e2e;
+pub mod git;
+pub mod import;
+pub mod logging;
+pub mod metrics;
+pub mod note_lock;
+pub mod notebooks;
+pub mod publish;
+pub mod reminders;
+pub mod remote;
+pub mod render;
+pub mod service;
+pub mod share;
+pub mod store;
+pub mod tasks;
+pub mod templates;
+pub mod vault;
+pub mod webhooks;
+
+use config::SharedArgs;
+use metrics::{Metrics, NoteCounts};
+use service::PidFile;
 
 #[derive(Parser, Debug, Clone)]
 #[command(version, about, long_about = None)]
@@ -19,21 +50,189 @@ struct Args {
     #[arg(long)]
     host: Option<String>,
 
-    /// Port to bind/connect to
-    #[arg(long, default_value_t = 8888)]
-    port: u16,
-
     /// URL of the Zenus server (Client Mode)
-    #[arg(long)]
+    #[arg(long, env = "ZENUS_URL")]
     url: Option<String>,
 
-    /// Authentication token/password
-    #[arg(long)]
-    auth: Option<String>,
+    /// File holding the passphrase for end-to-end encryption (Client Mode)
+    /// or the encrypted notes vault (Local Mode)
+    #[arg(long, env = "ZENUS_PASSPHRASE_FILE")]
+    passphrase_file: Option<std::path::PathBuf>,
 
-    /// Custom path for notes directory
-    #[arg(long)]
-    path: Option<String>,
+    /// Lock the encrypted notes vault after this many idle minutes, 0 to never lock [default: 15]
+    #[arg(long, env = "ZENUS_AUTO_LOCK")]
+    auto_lock: Option<u64>,
+
+    #[command(flatten)]
+    shared: SharedArgs,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum Command {
+    /// Import notes from another app into the local notes directory
+    Import {
+        /// Format of the source
+        #[arg(value_enum)]
+        format: import::ImportFormat,
+
+        /// Vault directory or export file to import
+        source: std::path::PathBuf,
+
+        /// Only report what would be imported
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Export all notes, archived notes and attachments to a single file
+    Export {
+        /// Bundle format
+        #[arg(long, value_enum, default_value_t = bundle::BundleFormat::Zip)]
+        format: bundle::BundleFormat,
+
+        /// Output file [default: zenus-export-<timestamp>.<format>]
+        #[arg(long)]
+        out: Option<std::path::PathBuf>,
+    },
+    /// Restore notes from a bundle created by `export`
+    Restore {
+        /// Zip or JSON bundle
+        bundle: std::path::PathBuf,
+    },
+    /// Render notes with a tag to a static HTML site
+    Publish {
+        /// Output directory
+        #[arg(long)]
+        out: std::path::PathBuf,
+
+        /// Only publish notes with this tag
+        #[arg(long, default_value = "public")]
+        tag: String,
+
+        /// HTML template with {{site_title}}, {{title}} and {{content}} placeholders
+        #[arg(long)]
+        template: Option<std::path::PathBuf>,
+
+        /// Site title shown in the header and index page
+        #[arg(long, default_value = "Notes")]
+        title: String,
+    },
+    /// Encrypt or decrypt the local notes directory (needs --passphrase-file)
+    Vault {
+        #[command(subcommand)]
+        action: VaultAction,
+    },
+    /// Manage end-to-end encryption of the notes on a server (needs --url)
+    E2e {
+        #[command(subcommand)]
+        action: E2eAction,
+    },
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum VaultAction {
+    /// Show whether the notes directory is encrypted
+    Status,
+    /// Encrypt every note with the passphrase
+    Encrypt,
+    /// Decrypt every note and turn encryption off
+    Decrypt,
+}
+
+#[derive(Subcommand, Debug, Clone)]
+enum E2eAction {
+    /// Show how many notes are encrypted
+    Status,
+    /// Turn on encryption and encrypt all notes with the passphrase
+    Init,
+    /// Re-encrypt all notes with a new passphrase
+    Rotate {
+        /// File holding the new passphrase
+        #[arg(long)]
+        new_passphrase_file: std::path::PathBuf,
+    },
+}
+
+/// Runs an `e2e` subcommand against the server at `url`.
+#[cfg(feature = "tauri-deps")]
+fn run_e2e_command(action: E2eAction, url: &str, auth: Option<&str>, passphrase_file: Option<&std::path::Path>) -> Result<(), String> {
+    let api_url = check_server_compatibility(url, auth)?;
+    let remote = e2e::Remote::new(url, &api_url, auth)?;
+    let passphrase = || {
+        passphrase_file
+            .ok_or("Pass the current passphrase with --passphrase-file".to_string())
+            .and_then(e2e::read_passphrase)
+    };
+
+    let status = match action {
+        E2eAction::Status => e2e::status(&remote)?,
+        E2eAction::Init => e2e::init(&remote, &passphrase()?)?,
+        E2eAction::Rotate { new_passphrase_file } => {
+            e2e::rotate(&remote, &passphrase()?, &e2e::read_passphrase(&new_passphrase_file)?)?
+        }
+    };
+    print!("{}", status.summary());
+    Ok(())
+}
+
+/// Runs a command line subcommand against the local notes directory.
+#[cfg(feature = "tauri-deps")]
+fn run_command(command: Command, local_path: Option<&std::path::Path>, passphrase_file: Option<&std::path::Path>) -> Result<(), String> {
+    let passphrase = || {
+        passphrase_file
+            .ok_or("Pass the vault passphrase with --passphrase-file".to_string())
+            .and_then(e2e::read_passphrase)
+    };
+    if vault::is_encrypted(local_path)? && !matches!(command, Command::Vault { .. }) {
+        vault::unlock(local_path, &passphrase()?)?;
+    }
+
+    match command {
+        Command::Vault { action } => {
+            match action {
+                VaultAction::Status => {
+                    let status = vault::status(local_path)?;
+                    println!("The notes directory is {}", if status.encrypted { "encrypted" } else { "not encrypted" });
+                }
+                VaultAction::Encrypt => {
+                    let report = vault::encrypt_vault(local_path, &passphrase()?)?;
+                    println!("Encrypted {} notes", report.files);
+                }
+                VaultAction::Decrypt => {
+                    let report = vault::decrypt_vault(local_path, &passphrase()?)?;
+                    println!("Decrypted {} notes", report.files);
+                }
+            }
+            Ok(())
+        }
+        Command::E2e { .. } => Err("The e2e commands need the server's --url".to_string()),
+        Command::Import { format, source, dry_run } => {
+            let batch = format.read(&source)?;
+            let report = import::write_local(batch, local_path, dry_run)?;
+            print!("{}", report.summary());
+            Ok(())
+        }
+        Command::Export { format, out } => {
+            let data = bundle::export_local(local_path, format)?;
+            let out = out.unwrap_or_else(|| format.file_name().into());
+            fs::write(&out, data).map_err(|e| format!("Failed to write {}: {}", out.display(), e))?;
+            println!("Exported notes to {}", out.display());
+            Ok(())
+        }
+        Command::Restore { bundle: path } => {
+            let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+            let report = bundle::restore_local(&data, local_path)?;
+            print!("{}", report.summary());
+            Ok(())
+        }
+        Command::Publish { out, tag, template, title } => {
+            let options = publish::PublishOptions { out_dir: out, tag, template, site_title: title };
+            let report = publish::publish_local(local_path, &options)?;
+            print!("{}", report.summary(&options.out_dir));
+            Ok(())
+        }
+    }
 }
 
 struct AppState {
@@ -41,12 +240,80 @@ struct AppState {
     auth_token: Option<String>,
     client: reqwest::Client,
     local_path: Option<std::path::PathBuf>,
+    /// Set in Client Mode when the server uses end-to-end encryption
+    vault_key: Option<e2e::VaultKey>,
+    daily: daily::DailyTemplate,
+    reminders: reminders::ReminderSettings,
+}
+
+/// Version of the HTTP API exposed under [`API_PREFIX`].
+pub const API_VERSION: u32 = 1;
+
+/// Path prefix all note routes are mounted under.
+pub const API_PREFIX: &str = "/api/v1";
+
+/// Optional capabilities advertised by `GET /api/info`.
+pub const SERVER_FEATURES: &[&str] = &["notes", "archive", "reorder", "tags", "metrics", "export", "share", "attachments", "e2e", "append", "daily", "templates", "tasks", "reminders", "calendar", "webhooks", "history", "notebooks"];
+
+/// Settings for [`run_server`].
+#[derive(Clone, Debug, Default)]
+pub struct ServerOptions {
+    pub host: String,
+    pub port: u16,
+    pub auth_token: Option<String>,
+    pub local_path: Option<String>,
+    /// Write the process id here while the server is running
+    pub pidfile: Option<std::path::PathBuf>,
+    /// Listen on this Unix socket instead of `host:port`
+    pub unix_socket: Option<std::path::PathBuf>,
+    /// Permissions applied to `unix_socket`, e.g. `0o660`
+    pub unix_socket_mode: Option<u32>,
+    /// Template for daily notes created through `GET /daily/:date`
+    pub daily: daily::DailyTemplate,
+    /// Default reminder time and webhook for `GET /reminders`
+    pub reminders: reminders::ReminderSettings,
+    /// Called when notes are saved, deleted, archived or unarchived
+    pub webhooks: Vec<webhooks::Webhook>,
+    /// Commit every change to the notes directory with git
+    pub git: Option<git::GitSettings>,
 }
 
+/// Largest bundle accepted by `POST /import`.
+const MAX_BUNDLE_SIZE: usize = 512 * 1024 * 1024;
+
+/// Largest file accepted by `POST /notes/:id/attachments`.
+const MAX_ATTACHMENT_SIZE: usize = 100 * 1024 * 1024;
+
 #[derive(Clone)]
 pub struct ServerState {
     pub auth_token: Option<String>,
     pub local_path: Option<std::path::PathBuf>,
+    pub metrics: Arc<Metrics>,
+    pub daily: daily::DailyTemplate,
+    pub reminders: reminders::ReminderSettings,
+    pub webhooks: Arc<webhooks::Webhooks>,
+}
+
+impl ServerState {
+    /// Sends `event` for note `id` to the webhooks that want it.
+    fn notify(&self, event: webhooks::WebhookEvent, id: &str) {
+        if self.webhooks.is_empty() {
+            return;
+        }
+        match load_note_local(id, self.local_path.as_deref()) {
+            Ok(Some(note)) => self.webhooks.notify(event, &note),
+            Ok(None) => {}
+            Err(e) => tracing::warn!(error = %e, note_id = %id, "failed to load note for webhooks"),
+        }
+    }
+
+    /// The note `id` as it is before a delete, if any webhooks need it.
+    fn note_for_webhooks(&self, id: &str) -> Option<NoteBlock> {
+        if self.webhooks.is_empty() {
+            return None;
+        }
+        load_note_local(id, self.local_path.as_deref()).ok().flatten()
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
@@ -60,6 +327,202 @@ pub struct NoteBlock {
     order: i32,
     #[serde(default)]
     tags: Vec<String>,
+    #[serde(rename = "createdAt", default, skip_serializing_if = "Option::is_none")]
+    created_at: Option<String>,
+    #[serde(rename = "updatedAt", default, skip_serializing_if = "Option::is_none")]
+    updated_at: Option<String>,
+    /// Files in `attachments/` used by this note, unset if the client does
+    /// not know about attachments
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    attachments: Option<Vec<String>>,
+    /// Set on notes whose content is encrypted with their own password
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    locked: Option<e2e::KeyParams>,
+    /// Local time to be reminded of the whole note, see [`reminders`]; empty
+    /// to clear it
+    #[serde(rename = "remindAt", default, skip_serializing_if = "Option::is_none")]
+    remind_at: Option<String>,
+    /// Path of the notebook holding this note, unset at the top level; see
+    /// [`notebooks`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    notebook: Option<String>,
+}
+
+#[cfg(test)]
+impl NoteBlock {
+    /// A top-level note with only an ID and content, for tests to fill in
+    /// the rest with struct update syntax.
+    pub(crate) fn test(id: &str, content: &str) -> NoteBlock {
+        NoteBlock {
+            id: id.to_string(),
+            title: "Note".to_string(),
+            content: content.to_string(),
+            is_collapsed: false,
+            order: 0,
+            tags: Vec::new(),
+            created_at: None,
+            updated_at: None,
+            attachments: None,
+            locked: None,
+            remind_at: None,
+            notebook: None,
+        }
+    }
+}
+
+/// Whether `value` can be a note ID: letters, digits, `-` and `_`, so it
+/// cannot hold path separators or glob patterns.
+pub fn is_note_id(value: &str) -> bool {
+    !value.is_empty() && value.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Generates a snowflake-style note ID (milliseconds since the Unix epoch,
+/// machine ID and sequence number), matching the IDs the frontend creates.
+///
+/// Machine ID 2 is used so IDs never collide with the frontend's machine ID 1.
+pub fn generate_note_id() -> String {
+    static LAST: std::sync::Mutex<(i64, u64)> = std::sync::Mutex::new((0, 0));
+    const MACHINE_ID: u64 = 2;
+
+    let mut last = LAST.lock().unwrap();
+    let mut now = chrono::Utc::now().timestamp_millis();
+    if now <= last.0 {
+        // Same millisecond (or clock went backwards): bump the sequence
+        last.1 = (last.1 + 1) & 0xfff;
+        if last.1 == 0 {
+            last.0 += 1;
+        }
+        now = last.0;
+    } else {
+        *last = (now, 0);
+    }
+
+    let id = ((now as u64) << 22) | (MACHINE_ID << 12) | last.1;
+    id.to_string()
+}
+
+/// Capability discovery payload returned by `GET /api/info`.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct ServerInfo {
+    pub name: String,
+    pub version: String,
+    #[serde(rename = "apiVersion")]
+    pub api_version: u32,
+    #[serde(rename = "apiPrefix")]
+    pub api_prefix: String,
+    pub features: Vec<String>,
+    pub storage: String,
+}
+
+impl ServerInfo {
+    pub fn current() -> Self {
+        ServerInfo {
+            name: env!("CARGO_PKG_NAME").to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            api_version: API_VERSION,
+            api_prefix: API_PREFIX.to_string(),
+            features: SERVER_FEATURES.iter().map(|f| f.to_string()).collect(),
+            storage: "filesystem".to_string(),
+        }
+    }
+}
+
+/// Host used in request URLs when talking to a server over a Unix socket.
+const UNIX_SOCKET_BASE_URL: &str = "http://localhost";
+
+/// Returns the socket path for `unix:/path/to.sock` style server URLs.
+pub fn unix_socket_path(url: &str) -> Option<std::path::PathBuf> {
+    url.strip_prefix("unix://")
+        .or_else(|| url.strip_prefix("unix:"))
+        .map(std::path::PathBuf::from)
+}
+
+/// Builds the HTTP client for talking to the server at `url`, routing all
+/// requests through the socket for `unix:` URLs.
+pub fn http_client(url: &str) -> Result<reqwest::Client, String> {
+    let Some(socket) = unix_socket_path(url) else {
+        return Ok(reqwest::Client::new());
+    };
+
+    #[cfg(unix)]
+    {
+        reqwest::Client::builder()
+            .unix_socket(socket)
+            .build()
+            .map_err(|e| format!("Failed to create client: {}", e))
+    }
+    #[cfg(not(unix))]
+    {
+        Err(format!("Unix sockets are not supported on this platform: {}", socket.display()))
+    }
+}
+
+fn blocking_http_client(url: &str) -> Result<reqwest::blocking::Client, String> {
+    let Some(socket) = unix_socket_path(url) else {
+        return Ok(reqwest::blocking::Client::new());
+    };
+
+    #[cfg(unix)]
+    {
+        reqwest::blocking::Client::builder()
+            .unix_socket(socket)
+            .build()
+            .map_err(|e| format!("Failed to create client: {}", e))
+    }
+    #[cfg(not(unix))]
+    {
+        Err(format!("Unix sockets are not supported on this platform: {}", socket.display()))
+    }
+}
+
+/// Asks the server at `url` which API it speaks and returns the base URL the
+/// client should use for note requests.
+///
+/// For `unix:` URLs the returned base is an HTTP URL meant to be used with
+/// the client from [`http_client`].
+///
+/// Servers that predate `/api/info` only serve the unversioned routes, so a
+/// 404 falls back to the bare URL instead of failing. Only an API version
+/// mismatch is reported as an error.
+pub fn check_server_compatibility(url: &str, auth_token: Option<&str>) -> Result<String, String> {
+    let base = if unix_socket_path(url).is_some() {
+        UNIX_SOCKET_BASE_URL
+    } else {
+        url.trim_end_matches('/')
+    };
+    let client = blocking_http_client(url)?;
+    let mut request = client.get(format!("{}/api/info", base));
+
+    if let Some(token) = auth_token {
+        request = request.header("Authorization", token);
+    }
+
+    let response = match request.send() {
+        Ok(response) => response,
+        Err(e) => {
+            // The server may simply not be up yet; assume it matches us
+            tracing::warn!(error = %e, "could not check server version");
+            return Ok(format!("{}{}", base, API_PREFIX));
+        }
+    };
+
+    if response.status() == reqwest::StatusCode::NOT_FOUND {
+        return Ok(base.to_string());
+    }
+
+    let info = response.error_for_status()
+        .map_err(|e| format!("Server error: {}", e))?
+        .json::<ServerInfo>()
+        .map_err(|e| format!("Failed to parse server info: {}", e))?;
+
+    if info.api_version != API_VERSION {
+        return Err(format!(
+            "Server speaks API v{} (zenus {}), but this client requires API v{}",
+            info.api_version, info.version, API_VERSION
+        ));
+    }
+
+    Ok(format!("{}{}", base, info.api_prefix))
 }
 
 // Learn more about Tauri commands at https://tauri.app/develop/calling-rust/
@@ -72,288 +535,560 @@ fn greet(name: &str) -> String {
 #[cfg(feature = "tauri-deps")]
 #[tauri::command]
 async fn save_block(state: State<'_, AppState>, block: NoteBlock) -> Result<(), String> {
+    let block = if note_lock::is_locked(&block) {
+        let existing = load_note(&state, &block.id).await?;
+        note_lock::prepare_save(block, existing)?
+    } else {
+        block
+    };
+
     if let Some(api_url) = &state.api_url {
         // Client Mode: Send to server
-        let url = format!("{}/notes", api_url);
-        let mut request = state.client.post(&url).json(&block);
-        
-        if let Some(token) = &state.auth_token {
-            request = request.header("Authorization", token);
-        }
-
-        request.send().await
-            .map_err(|e| format!("Failed to send request: {}", e))?
-            .error_for_status()
-            .map_err(|e| format!("Server error: {}", e))?;
-            
-        Ok(())
+        save_block_remote(&state, api_url, &block).await
     } else {
         // Local Mode: Save to disk
         save_block_local(block, state.local_path.as_deref())
     }
 }
 
-pub fn save_block_local(block: NoteBlock, custom_path: Option<&std::path::Path>) -> Result<(), String> {
-    let notes_dir = if let Some(p) = custom_path {
-        p.to_path_buf()
+#[cfg(feature = "tauri-deps")]
+async fn save_block_remote(state: &AppState, api_url: &str, block: &NoteBlock) -> Result<(), String> {
+    let request = remote::ApiRequest::save_note(block, state.vault_key.as_ref())?;
+    send_remote(state, api_url, request).await.map(|_| ())
+}
+
+/// Returns the notes directory, falling back to the platform data dir.
+pub fn resolve_notes_dir(custom_path: Option<&std::path::Path>) -> Result<std::path::PathBuf, String> {
+    if let Some(p) = custom_path {
+        Ok(p.to_path_buf())
     } else {
         let app_dir = dirs::data_dir().ok_or("Could not get data directory")?;
-        app_dir.join("zenus")
-    };
-    let archive_dir = notes_dir.join("archive");
-    fs::create_dir_all(&notes_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
-    
-    // Check if the note exists in archive, if so, save it there //###//
-    let archive_file_path = archive_dir.join(format!("{}.md", block.id));
-    let file_path = if archive_file_path.exists() {
-        archive_file_path
-    } else {
-        notes_dir.join(format!("{}.md", block.id))
+        Ok(app_dir.join("zenus"))
+    }
+}
+
+pub fn save_block_local(block: NoteBlock, custom_path: Option<&std::path::Path>) -> Result<(), String> {
+    write_block_local(block, custom_path, chrono::Utc::now().to_rfc3339())
+}
+
+// Serialises in-place edits (appends, task toggles) so concurrent ones
+// never drop each other's changes
+pub(crate) static EDIT_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Appends `text` on a new line at the end of a body.
+pub(crate) fn append_text(content: &mut String, text: &str) {
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(text);
+}
+
+/// Whether the body of `block` can be changed in place without knowing its
+/// keys.
+pub fn check_editable(block: &NoteBlock) -> Result<(), String> {
+    if e2e::is_encrypted(block) {
+        return Err("End-to-end encrypted notes can only be changed by a client with the passphrase".to_string());
+    }
+    if note_lock::is_locked(block) {
+        return Err("Locked notes cannot be changed without their password".to_string());
+    }
+    Ok(())
+}
+
+/// Appends `text` to an active or archived note in one step.
+pub fn append_block_local(id: &str, text: &str, custom_path: Option<&std::path::Path>) -> Result<(), String> {
+    if text.is_empty() {
+        return Err("Nothing to append".to_string());
+    }
+    let _guard = EDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut block = load_note_local(id, custom_path)?.ok_or(format!("Note {} not found", id))?;
+    check_editable(&block)?;
+    append_text(&mut block.content, text);
+    save_block_local(block, custom_path)
+}
+
+/// Writes a block to disk with the given modification time.
+///
+/// `createdAt`, `attachments` and `remindAt` are taken from the block if
+/// set, then from the file being overwritten, so a client that does not know
+/// about them never resets them. An empty `remindAt` clears the reminder.
+pub(crate) fn write_block_local(mut block: NoteBlock, custom_path: Option<&std::path::Path>, updated_at: String) -> Result<(), String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+
+    // An existing note is saved where it is, in its notebook or the
+    // archive; a new one goes into the notebook it names
+    let file_path = match notebooks::find_note(&notes_dir, &block.id) {
+        Some(location) => location.path,
+        None => {
+            let notebook = notebooks::normalize_optional(block.notebook.as_deref())?;
+            notebooks::note_path(&notes_dir, notebook.as_deref(), false, &block.id)
+        }
     };
+    if let Some(dir) = file_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+    }
+
+    let existing = read_metadata(&file_path);
+    let existing_str = |key: &str| existing.as_ref()?.get(key)?.as_str().map(String::from);
+
+    let created_at = block.created_at.clone()
+        .or_else(|| existing_str("createdAt"))
+        .unwrap_or_else(|| updated_at.clone());
+    let remind_at = block.remind_at.clone()
+        .or_else(|| existing_str("remindAt"))
+        .filter(|r| !r.is_empty());
+    if block.attachments.is_none() {
+        block.attachments = existing.as_ref()
+            .and_then(|m| serde_json::from_value(m.get("attachments")?.clone()).ok());
+    }
     
+    // Attachments linked from the body are always recorded, even if the
+    // client did not list them
+    attachments::record_linked(&mut block);
+
     // Save metadata as JSON comment at the top
-    let metadata = serde_json::to_string(&serde_json::json!({
+    let mut metadata = serde_json::json!({
         "title": block.title,
         "isCollapsed": block.is_collapsed,
         "order": block.order,
         "tags": block.tags,
-        "createdAt": chrono::Utc::now().to_rfc3339(),
-        "updatedAt": chrono::Utc::now().to_rfc3339()
-    })).map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+        "createdAt": created_at,
+        "updatedAt": updated_at
+    });
+    if let Some(files) = block.attachments.as_ref().filter(|files| !files.is_empty()) {
+        metadata["attachments"] = serde_json::json!(files);
+    }
+    if let Some(remind_at) = remind_at {
+        metadata["remindAt"] = serde_json::json!(remind_at);
+    }
+
+    // Clients that do not know about locked notes send the ciphertext back
+    // without the key parameters; keep them or the note could never be opened
+    let locked = block.locked.clone().or_else(|| {
+        if !note_lock::is_sealed(&block.content) {
+            return None;
+        }
+        existing.as_ref().and_then(|m| serde_json::from_value(m.get("locked")?.clone()).ok())
+    });
+    if let Some(locked) = locked {
+        metadata["locked"] = serde_json::to_value(locked).map_err(|e| format!("Failed to serialize metadata: {}", e))?;
+    }
+    let metadata = serde_json::to_string(&metadata).map_err(|e| format!("Failed to serialize metadata: {}", e))?;
     
     let content = format!("<!-- {} -->\n\n{}", metadata, block.content);
     
-    fs::write(&file_path, content).map_err(|e| format!("Failed to write block: {}", e))?;
+    vault::write_note(&notes_dir, &file_path, &content)?;
+    git::record(custom_path, git::Action::Save, Some(&block.id));
     Ok(())
 }
 
+/// Reads the JSON metadata comment from the first line of a note file.
+fn read_metadata(file_path: &std::path::Path) -> Option<serde_json::Value> {
+    let content = vault::read_note(file_path).ok()?;
+    let first = content.lines().next()?;
+    let metadata_str = first.strip_prefix("<!-- ")?.strip_suffix(" -->")?;
+    serde_json::from_str(metadata_str).ok()
+}
+
 #[cfg(feature = "tauri-deps")]
 #[tauri::command]
-async fn load_notes(state: State<'_, AppState>, subdir: Option<String>) -> Result<Vec<NoteBlock>, String> {
-    if let Some(api_url) = &state.api_url {
+async fn load_notes(state: State<'_, AppState>, subdir: Option<String>, notebook: Option<String>) -> Result<Vec<NoteBlock>, String> {
+    let notes = if let Some(api_url) = &state.api_url {
         // Client Mode: Fetch from server
-        let url = if let Some(sub) = &subdir {
-            if sub == "archive" {
-                format!("{}/notes/archive", api_url)
-            } else {
-                format!("{}/notes", api_url)
-            }
-        } else {
-            format!("{}/notes", api_url)
-        };
-
-        let mut request = state.client.get(&url);
-        
-        if let Some(token) = &state.auth_token {
-            request = request.header("Authorization", token);
-        }
-
-        let blocks = request.send().await
-            .map_err(|e| format!("Failed to send request: {}", e))?
-            .error_for_status()
-            .map_err(|e| format!("Server error: {}", e))?
-            .json::<Vec<NoteBlock>>().await
-            .map_err(|e| format!("Failed to parse response: {}", e))?;
-            
-        Ok(blocks)
+        load_notes_remote(&state, api_url, subdir.as_deref(), notebook.as_deref()).await?
     } else {
         // Local Mode: Read from disk
-        load_notes_local(subdir.as_deref(), state.local_path.as_deref())
-    }
+        load_notebook_local(subdir.as_deref(), notebook.as_deref(), state.local_path.as_deref())?
+    };
+
+    // Locked notes are listed without their content
+    Ok(notes.into_iter().map(note_lock::redact).collect())
 }
 
-pub fn load_notes_local(subdir: Option<&str>, custom_path: Option<&std::path::Path>) -> Result<Vec<NoteBlock>, String> {
-    let mut notes_dir = if let Some(p) = custom_path {
-        p.to_path_buf()
+/// Finds one active or archived note as stored, without redacting it.
+#[cfg(feature = "tauri-deps")]
+async fn load_note(state: &AppState, id: &str) -> Result<Option<NoteBlock>, String> {
+    if let Some(api_url) = &state.api_url {
+        for subdir in [None, Some("archive")] {
+            let notes = load_notes_remote(state, api_url, subdir, None).await?;
+            if let Some(note) = notes.into_iter().find(|n| n.id == id) {
+                return Ok(Some(note));
+            }
+        }
+        Ok(None)
     } else {
-        let app_dir = dirs::data_dir().ok_or("Could not get data directory")?;
-        app_dir.join("zenus")
-    };
-    
-    if let Some(sub) = subdir {
-        notes_dir = notes_dir.join(sub);
-    }
-    
-    if !notes_dir.exists() {
-        return Ok(vec![]);
+        load_note_local(id, state.local_path.as_deref())
     }
-    
-    let mut blocks = Vec::new();
-    
-    for entry in fs::read_dir(&notes_dir).map_err(|e| format!("Failed to read directory: {}", e))? {
-        let entry = entry.map_err(|e| format!("Failed to read entry: {}", e))?;
-        let path = entry.path();
-        
-        if path.extension().and_then(|s| s.to_str()) == Some("md") {
-            let content = fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
-            
-            // Extract ID from filename (remove .md extension)
-            let id = path.file_stem()
-                .and_then(|s| s.to_str())
-                .ok_or("Invalid filename")?
-                .to_string();
-            
-            // Parse markdown content
-            let lines: Vec<&str> = content.lines().collect();
-            
-            // Check for metadata comment at the top
-            let mut is_collapsed = false;
-            let mut title = "Untitled".to_string();
-            let mut order = 0;
-            let mut tags: Vec<String> = Vec::new();
-            let mut content_start = 0;
-            
-            if lines.len() > 0 && lines[0].starts_with("<!-- ") && lines[0].ends_with(" -->") {
-                // Extract metadata from comment
-                let metadata_str = &lines[0][5..lines[0].len()-4]; // Remove <!-- and -->
-                if let Ok(metadata) = serde_json::from_str::<serde_json::Value>(metadata_str) {
-                    if let Some(collapsed) = metadata.get("isCollapsed").and_then(|v| v.as_bool()) {
-                        is_collapsed = collapsed;
-                    }
-                    if let Some(title_str) = metadata.get("title").and_then(|v| v.as_str()) {
-                        title = title_str.to_string();
-                    }
-                    if let Some(order_val) = metadata.get("order").and_then(|v| v.as_i64()) {
-                        order = order_val as i32;
-                    }
-                    if let Some(tags_arr) = metadata.get("tags").and_then(|v| v.as_array()) {
-                        tags = tags_arr.iter().filter_map(|v| v.as_str().map(String::from)).collect();
-                    }
+}
+
+/// Returns the daily note for `date` (`YYYY-MM-DD`, default today),
+/// creating it from the template on first open.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn open_daily_note(state: State<'_, AppState>, date: Option<String>) -> Result<NoteBlock, String> {
+    let date = daily::parse_date(date.as_deref().unwrap_or("today"))?;
+    let note = match (&state.api_url, &state.vault_key) {
+        (Some(api_url), None) => send_remote(&state, api_url, remote::ApiRequest::daily(date)).await?.json()?,
+        // The server cannot write encrypted notes, so create it here
+        (Some(api_url), Some(_)) => {
+            let id = daily::daily_note_id(date)?;
+            match load_note(&state, &id).await? {
+                Some(note) => note,
+                None => {
+                    let existing = load_notes_remote(&state, api_url, None, None).await?.len();
+                    let note = daily::new_daily_note(date, &state.daily, existing)?;
+                    save_block_remote(&state, api_url, &note).await?;
+                    note
                 }
-                content_start = 1; // Skip metadata line
-            }
-            
-            // Fallback: try to read title from markdown header if no metadata
-            if title == "Untitled" && lines.len() > content_start && lines[content_start].starts_with("# ") {
-                title = lines[content_start][2..].to_string();
             }
-            
-            let block_content = if lines.len() > content_start + 1 {
-                lines[content_start + 1..].join("\n")
-            } else {
-                String::new()
-            };
-            
-            blocks.push(NoteBlock {
-                id,
-                title,
-                content: block_content,
-                is_collapsed,
-                order,
-                tags,
-            });
         }
+        (None, _) => daily::open_daily_local(date, &state.daily, state.local_path.as_deref())?,
+    };
+    Ok(note_lock::redact(note))
+}
+
+/// All active notes as stored. In Client Mode they are decrypted here, so
+/// tasks and reminders are parsed here too and work with end-to-end
+/// encryption.
+#[cfg(feature = "tauri-deps")]
+async fn load_active_notes(state: &AppState) -> Result<Vec<NoteBlock>, String> {
+    match &state.api_url {
+        Some(api_url) => load_notes_remote(state, api_url, None, None).await,
+        None => load_notes_local(None, state.local_path.as_deref()),
     }
-    
-    // Sort by order, then by ID
-    blocks.sort_by(|a, b| {
-        match a.order.cmp(&b.order) {
-            std::cmp::Ordering::Equal => a.id.cmp(&b.id),
-            other => other,
-        }
-    });
-    
-    Ok(blocks)
 }
 
+/// Tasks from the checkboxes of all active notes.
 #[cfg(feature = "tauri-deps")]
 #[tauri::command]
-async fn delete_block(state: State<'_, AppState>, block_id: String, subdir: Option<String>) -> Result<(), String> {
-    if let Some(api_url) = &state.api_url {
-        // Client Mode: Delete on server
-        let url = if let Some(sub) = &subdir {
-            if sub == "archive" {
-                format!("{}/notes/{}/archive", api_url, block_id)
-            } else {
-                format!("{}/notes/{}", api_url, block_id)
+async fn list_tasks(state: State<'_, AppState>, filter: Option<tasks::TaskFilter>) -> Result<Vec<tasks::Task>, String> {
+    let notes = load_active_notes(&state).await?;
+    Ok(tasks::list_tasks(&notes, &filter.unwrap_or_default()))
+}
+
+/// Reminders due in the next `within_hours` hours (default 24).
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn upcoming_reminders(state: State<'_, AppState>, within_hours: Option<u32>) -> Result<Vec<reminders::Reminder>, String> {
+    let notes = load_active_notes(&state).await?;
+    let within = chrono::Duration::hours(within_hours.unwrap_or(24).into());
+    Ok(reminders::upcoming(&notes, &state.reminders, within))
+}
+
+/// Shows a notification for every reminder as it comes due and tells the
+/// frontend with a `reminder` event. Reminders that came due while the app
+/// was closed are not shown.
+#[cfg(feature = "tauri-deps")]
+async fn run_reminders(handle: tauri::AppHandle) {
+    use tauri::{Emitter, Manager};
+    use tauri_plugin_notification::NotificationExt;
+
+    let mut scheduler = reminders::Scheduler::new(chrono::Local::now());
+    loop {
+        tokio::time::sleep(reminders::CHECK_INTERVAL).await;
+        let state = handle.state::<AppState>();
+        // Checks are not activity that should keep the vault unlocked, and a
+        // locked vault or an unreachable server is skipped; the next check
+        // then covers this one too
+        let loaded = match &state.api_url {
+            Some(_) => load_active_notes(&state).await,
+            None if vault::is_locked(state.local_path.as_deref()).unwrap_or(false) => continue,
+            None => vault::passively(|| load_notes_local(None, state.local_path.as_deref())),
+        };
+        let notes = match loaded {
+            Ok(notes) => notes,
+            Err(e) => {
+                tracing::debug!(error = %e, "skipped reminder check");
+                continue;
             }
-        } else {
-            format!("{}/notes/{}", api_url, block_id)
         };
 
-        let mut request = state.client.delete(&url);
-        
-        if let Some(token) = &state.auth_token {
-            request = request.header("Authorization", token);
+        for reminder in scheduler.due(reminders::list_reminders(&notes, &state.reminders), chrono::Local::now()) {
+            let title = if reminder.line.is_some() { reminder.note_title.as_str() } else { "Reminder" };
+            if let Err(e) = handle.notification().builder().title(title).body(&reminder.text).show() {
+                tracing::warn!(error = %e, note_id = %reminder.note_id, "failed to show reminder");
+            }
+            let _ = handle.emit("reminder", &reminder);
         }
-
-        request.send().await
-            .map_err(|e| format!("Failed to send request: {}", e))?
-            .error_for_status()
-            .map_err(|e| format!("Server error: {}", e))?;
-            
-        Ok(())
-    } else {
-        // Local Mode: Delete from disk
-        delete_block_local(block_id, subdir.as_deref(), state.local_path.as_deref())
     }
 }
 
-pub fn delete_block_local(block_id: String, subdir: Option<&str>, custom_path: Option<&std::path::Path>) -> Result<(), String> {
-    let mut notes_dir = if let Some(p) = custom_path {
-        p.to_path_buf()
-    } else {
-        let app_dir = dirs::data_dir().ok_or("Could not get data directory")?;
-        app_dir.join("zenus")
+/// Ticks or unticks the task on `line` of a note.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn set_task(state: State<'_, AppState>, block_id: String, line: usize, update: tasks::TaskUpdate) -> Result<tasks::Task, String> {
+    let Some(api_url) = &state.api_url else {
+        return Ok(tasks::set_task_local(&block_id, line, &update, state.local_path.as_deref())?);
     };
-    
-    if let Some(sub) = subdir {
-        notes_dir = notes_dir.join(sub);
-    }
-    
-    let file_path = notes_dir.join(format!("{}.md", block_id));
-    
-    if file_path.exists() {
-        fs::remove_file(&file_path).map_err(|e| format!("Failed to delete file: {}", e))?;
+
+    if state.vault_key.is_some() {
+        // The server cannot edit encrypted notes in place
+        let mut note = load_note(&state, &block_id).await?.ok_or(format!("Note {} not found", block_id))?;
+        let task = tasks::update_task(&mut note, line, &update)?;
+        save_block_remote(&state, api_url, &note).await?;
+        return Ok(task);
     }
-    
-    Ok(())
+
+    send_remote(&state, api_url, remote::ApiRequest::set_task(&block_id, line, &update)?).await?.json()
 }
 
 #[cfg(feature = "tauri-deps")]
 #[tauri::command]
-async fn update_orders(state: State<'_, AppState>, orders: Vec<(String, i32)>) -> Result<(), String> {
+async fn list_templates(state: State<'_, AppState>) -> Result<Vec<templates::Template>, String> {
     if let Some(api_url) = &state.api_url {
-        // Client Mode: Update on server
-        let url = format!("{}/notes/reorder", api_url);
-        let mut request = state.client.post(&url).json(&orders);
-        
-        if let Some(token) = &state.auth_token {
-            request = request.header("Authorization", token);
-        }
-
-        request.send().await
-            .map_err(|e| format!("Failed to send request: {}", e))?
-            .error_for_status()
-            .map_err(|e| format!("Server error: {}", e))?;
-            
-        Ok(())
+        send_remote(&state, api_url, remote::ApiRequest::templates()).await?.json()
     } else {
-        // Local Mode: Update on disk
-        update_orders_local(orders, state.local_path.as_deref())
+        templates::list_templates(state.local_path.as_deref())
     }
 }
 
-pub fn update_orders_local(orders: Vec<(String, i32)>, custom_path: Option<&std::path::Path>) -> Result<(), String> {
-    let notes_dir = if let Some(p) = custom_path {
-        p.to_path_buf()
+/// Creates a note from a template. `request` carries the title, extra tags
+/// and the values for the template's prompt variables.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn create_note_from_template(state: State<'_, AppState>, name: String, request: templates::TemplateRequest) -> Result<NoteBlock, String> {
+    let Some(api_url) = &state.api_url else {
+        return templates::create_from_template_local(&name, &request, state.local_path.as_deref());
+    };
+
+    if state.vault_key.is_none() {
+        return send_remote(&state, api_url, remote::ApiRequest::note_from_template(&name, &request)?).await?.json();
+    }
+
+    // The server cannot write encrypted notes, so fill in the template here
+    let template: templates::Template = send_remote(&state, api_url, remote::ApiRequest::template(&name)).await?.json()?;
+    let existing = load_notes_remote(&state, api_url, None, None).await?.len();
+    let note = template.instantiate(&request, existing)?;
+    save_block_remote(&state, api_url, &note).await?;
+    Ok(note)
+}
+
+/// Encrypts a note's content with its own password.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn lock_note(state: State<'_, AppState>, block_id: String, password: String) -> Result<NoteBlock, String> {
+    let note = load_note(&state, &block_id).await?.ok_or(format!("Note {} not found", block_id))?;
+    let locked = note_lock::lock(note, &password)?;
+    save_note_as_is(&state, locked.clone()).await?;
+    Ok(note_lock::redact(locked))
+}
+
+/// Returns a locked note with its content decrypted. Edits to it are
+/// encrypted again on save until `relock_note` is called.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn unlock_note(state: State<'_, AppState>, block_id: String, password: String) -> Result<NoteBlock, String> {
+    let note = load_note(&state, &block_id).await?.ok_or(format!("Note {} not found", block_id))?;
+    note_lock::unlock(note, &password)
+}
+
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn relock_note(block_id: String) -> Result<(), String> {
+    note_lock::relock(&block_id);
+    Ok(())
+}
+
+/// Decrypts a locked note for good.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn remove_note_lock(state: State<'_, AppState>, block_id: String, password: String) -> Result<NoteBlock, String> {
+    let note = load_note(&state, &block_id).await?.ok_or(format!("Note {} not found", block_id))?;
+    let unlocked = note_lock::remove_lock(note, &password)?;
+    save_note_as_is(&state, unlocked.clone()).await?;
+    Ok(unlocked)
+}
+
+/// Saves a note without the locked-note handling of `save_block`.
+#[cfg(feature = "tauri-deps")]
+async fn save_note_as_is(state: &AppState, block: NoteBlock) -> Result<(), String> {
+    if let Some(api_url) = &state.api_url {
+        save_block_remote(state, api_url, &block).await
     } else {
-        let app_dir = dirs::data_dir().ok_or("Could not get data directory")?;
-        app_dir.join("zenus")
+        save_block_local(block, state.local_path.as_deref())
+    }
+}
+
+#[cfg(feature = "tauri-deps")]
+async fn load_notes_remote(state: &AppState, api_url: &str, subdir: Option<&str>, notebook: Option<&str>) -> Result<Vec<NoteBlock>, String> {
+    send_remote(state, api_url, remote::ApiRequest::notes(subdir, notebook)?).await?
+        .notes(state.vault_key.as_ref())
+}
+
+pub fn load_notes_local(subdir: Option<&str>, custom_path: Option<&std::path::Path>) -> Result<Vec<NoteBlock>, String> {
+    load_notebook_local(subdir, None, custom_path)
+}
+
+/// Loads the active notes, or the archived ones with `subdir` set to
+/// `archive`, sorted by order. With `notebook`, only the notes directly in
+/// that notebook are loaded, or those at the top level if it is empty;
+/// otherwise the notes of every notebook.
+pub fn load_notebook_local(subdir: Option<&str>, notebook: Option<&str>, custom_path: Option<&std::path::Path>) -> Result<Vec<NoteBlock>, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let archived = subdir == Some(notebooks::ARCHIVE_DIR);
+    let notebook = notebook.map(|n| notebooks::normalize_optional(Some(n))).transpose()?;
+    
+    let mut blocks = Vec::new();
+    
+    for (path, in_notebook) in notebooks::note_files(&notes_dir, archived) {
+        if notebook.as_ref().is_some_and(|n| *n != in_notebook) {
+            continue;
+        }
+        let mut block = read_note_file(&path)?;
+        block.notebook = in_notebook;
+        blocks.push(block);
+    }
+    
+    // Sort by order, then by ID
+    blocks.sort_by(|a, b| {
+        match a.order.cmp(&b.order) {
+            std::cmp::Ordering::Equal => a.id.cmp(&b.id),
+            other => other,
+        }
+    });
+    
+    Ok(blocks)
+}
+
+/// Loads a single note by ID, looking in the active notes first and then in
+/// the archive. Returns `None` if no such note exists.
+pub fn load_note_local(id: &str, custom_path: Option<&std::path::Path>) -> Result<Option<NoteBlock>, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let Some(location) = notebooks::find_note(&notes_dir, id) else { return Ok(None) };
+    let mut block = read_note_file(&location.path)?;
+    block.notebook = location.notebook;
+    Ok(Some(block))
+}
+
+fn read_note_file(path: &std::path::Path) -> Result<NoteBlock, String> {
+    let content = vault::read_note(path)?;
+    
+    // Extract ID from filename (remove .md extension)
+    let id = path.file_stem()
+        .and_then(|s| s.to_str())
+        .ok_or("Invalid filename")?
+        .to_string();
+    
+    // Parse markdown content
+    let lines: Vec<&str> = content.lines().collect();
+    
+    // Check for metadata comment at the top
+    let mut is_collapsed = false;
+    let mut title = "Untitled".to_string();
+    let mut order = 0;
+    let mut tags: Vec<String> = Vec::new();
+    let mut created_at = None;
+    let mut updated_at = None;
+    let mut attachments = None;
+    let mut locked = None;
+    let mut remind_at = None;
+    let mut content_start = 0;
+    
+    if !lines.is_empty() && lines[0].starts_with("<!-- ") && lines[0].ends_with(" -->") {
+        // Extract metadata from comment
+        let metadata_str = &lines[0][5..lines[0].len()-4]; // Remove <!-- and -->
+        if let Ok(metadata) = serde_json::from_str::<serde_json::Value>(metadata_str) {
+            if let Some(collapsed) = metadata.get("isCollapsed").and_then(|v| v.as_bool()) {
+                is_collapsed = collapsed;
+            }
+            if let Some(title_str) = metadata.get("title").and_then(|v| v.as_str()) {
+                title = title_str.to_string();
+            }
+            if let Some(order_val) = metadata.get("order").and_then(|v| v.as_i64()) {
+                order = order_val as i32;
+            }
+            if let Some(tags_arr) = metadata.get("tags").and_then(|v| v.as_array()) {
+                tags = tags_arr.iter().filter_map(|v| v.as_str().map(String::from)).collect();
+            }
+            created_at = metadata.get("createdAt").and_then(|v| v.as_str()).map(String::from);
+            updated_at = metadata.get("updatedAt").and_then(|v| v.as_str()).map(String::from);
+            if let Some(files) = metadata.get("attachments").and_then(|v| v.as_array()) {
+                attachments = Some(files.iter().filter_map(|v| v.as_str().map(String::from)).collect());
+            }
+            locked = metadata.get("locked").and_then(|v| serde_json::from_value(v.clone()).ok());
+            remind_at = metadata.get("remindAt").and_then(|v| v.as_str()).map(String::from);
+        }
+        content_start = 1; // Skip metadata line
+    }
+    
+    // Fallback: try to read title from markdown header if no metadata
+    if title == "Untitled" && lines.len() > content_start && lines[content_start].starts_with("# ") {
+        title = lines[content_start][2..].to_string();
+    }
+    
+    let block_content = if lines.len() > content_start + 1 {
+        lines[content_start + 1..].join("\n")
+    } else {
+        String::new()
     };
-    let archive_dir = notes_dir.join("archive");
+    
+    Ok(NoteBlock {
+        id,
+        title,
+        content: block_content,
+        is_collapsed,
+        order,
+        tags,
+        created_at,
+        updated_at,
+        attachments,
+        locked,
+        remind_at,
+        notebook: None,
+    })
+}
+
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn delete_block(state: State<'_, AppState>, block_id: String, subdir: Option<String>) -> Result<(), String> {
+    if let Some(api_url) = &state.api_url {
+        // Client Mode: Delete on server
+        let archived = subdir.as_deref() == Some(notebooks::ARCHIVE_DIR);
+        send_remote(&state, api_url, remote::ApiRequest::delete_note(&block_id, archived)).await.map(|_| ())
+    } else {
+        // Local Mode: Delete from disk
+        delete_block_local(block_id, subdir.as_deref(), state.local_path.as_deref())
+    }
+}
+
+pub fn delete_block_local(block_id: String, subdir: Option<&str>, custom_path: Option<&std::path::Path>) -> Result<(), String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let archived = subdir == Some(notebooks::ARCHIVE_DIR);
+    
+    // Only an active note, or only an archived one with `archive`
+    if let Some(location) = notebooks::find_note(&notes_dir, &block_id).filter(|l| l.archived == archived) {
+        fs::remove_file(&location.path).map_err(|e| format!("Failed to delete file: {}", e))?;
+        git::record(custom_path, git::Action::Delete, Some(&block_id));
+    }
+    
+    Ok(())
+}
+
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn update_orders(state: State<'_, AppState>, orders: Vec<(String, i32)>) -> Result<(), String> {
+    if let Some(api_url) = &state.api_url {
+        // Client Mode: Update on server
+        send_remote(&state, api_url, remote::ApiRequest::reorder(&orders)?).await.map(|_| ())
+    } else {
+        // Local Mode: Update on disk
+        update_orders_local(orders, state.local_path.as_deref())
+    }
+}
+
+pub fn update_orders_local(orders: Vec<(String, i32)>, custom_path: Option<&std::path::Path>) -> Result<(), String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    
+    // Update each note wherever it is, in a notebook or the archive; active
+    // notes come last so they win over archived ones with the same ID
+    let files: std::collections::HashMap<String, std::path::PathBuf> = [true, false].into_iter()
+        .flat_map(|archived| notebooks::note_files(&notes_dir, archived))
+        .filter_map(|(path, _)| Some((path.file_stem()?.to_str()?.to_string(), path)))
+        .collect();
     
     for (id, order) in orders {
-        // Check if the note exists in archive, if so, update it there //###//
-        let archive_file_path = archive_dir.join(format!("{}.md", id));
-        let file_path = if archive_file_path.exists() {
-            archive_file_path
-        } else {
-            notes_dir.join(format!("{}.md", id))
-        };
-        
-        if file_path.exists() {
-            let content = fs::read_to_string(&file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+        if let Some(file_path) = files.get(&id) {
+            let content = vault::read_note(file_path)?;
             let lines: Vec<&str> = content.lines().collect();
             
             if lines.len() > 0 && lines[0].starts_with("<!-- ") && lines[0].ends_with(" -->") {
@@ -370,11 +1105,12 @@ pub fn update_orders_local(orders: Vec<(String, i32)>, custom_path: Option<&std:
                     let body = if lines.len() > 1 { lines[1..].join("\n") } else { String::new() };
                     let new_content = format!("<!-- {} -->\n{}", new_metadata, body);
                     
-                    fs::write(&file_path, new_content).map_err(|e| format!("Failed to write file: {}", e))?;
+                    vault::write_note(&notes_dir, file_path, &new_content)?;
                 }
             }
         }
     }
+    git::record(custom_path, git::Action::Reorder, None);
     Ok(())
 }
 
@@ -384,19 +1120,7 @@ pub fn update_orders_local(orders: Vec<(String, i32)>, custom_path: Option<&std:
 async fn archive_block(state: State<'_, AppState>, block_id: String) -> Result<(), String> {
     if let Some(api_url) = &state.api_url {
         // Client Mode: Archive on server
-        let url = format!("{}/notes/{}/archive", api_url, block_id);
-        let mut request = state.client.post(&url);
-        
-        if let Some(token) = &state.auth_token {
-            request = request.header("Authorization", token);
-        }
-
-        request.send().await
-            .map_err(|e| format!("Failed to send request: {}", e))?
-            .error_for_status()
-            .map_err(|e| format!("Server error: {}", e))?;
-            
-        Ok(())
+        send_remote(&state, api_url, remote::ApiRequest::archive(&block_id)).await.map(|_| ())
     } else {
         // Local Mode: Move to archive folder
         archive_block_local(block_id, state.local_path.as_deref())
@@ -404,25 +1128,20 @@ async fn archive_block(state: State<'_, AppState>, block_id: String) -> Result<(
 }
 
 pub fn archive_block_local(block_id: String, custom_path: Option<&std::path::Path>) -> Result<(), String> {
-    let notes_dir = if let Some(p) = custom_path {
-        p.to_path_buf()
-    } else {
-        let app_dir = dirs::data_dir().ok_or("Could not get data directory")?;
-        app_dir.join("zenus")
-    };
-    let archive_dir = notes_dir.join("archive");
+    let notes_dir = resolve_notes_dir(custom_path)?;
     
-    fs::create_dir_all(&archive_dir).map_err(|e| format!("Failed to create archive directory: {}", e))?;
+    // Archived notes keep their notebook inside the archive
+    let location = notebooks::find_note(&notes_dir, &block_id)
+        .filter(|l| !l.archived)
+        .ok_or("File not found")?;
+    let dest_path = notebooks::note_path(&notes_dir, location.notebook.as_deref(), true, &block_id);
     
-    let src_path = notes_dir.join(format!("{}.md", block_id));
-    let dest_path = archive_dir.join(format!("{}.md", block_id));
-    
-    if src_path.exists() {
-        fs::rename(&src_path, &dest_path).map_err(|e| format!("Failed to archive file: {}", e))?;
-    } else {
-        return Err("File not found".to_string());
+    if let Some(dir) = dest_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create archive directory: {}", e))?;
     }
+    fs::rename(&location.path, &dest_path).map_err(|e| format!("Failed to archive file: {}", e))?;
     
+    git::record(custom_path, git::Action::Archive, Some(&block_id));
     Ok(())
 }
 
@@ -431,209 +1150,1412 @@ pub fn archive_block_local(block_id: String, custom_path: Option<&std::path::Pat
 async fn unarchive_block(state: State<'_, AppState>, block_id: String) -> Result<(), String> {
     if let Some(api_url) = &state.api_url {
         // Client Mode: Unarchive on server
-        let url = format!("{}/notes/{}/unarchive", api_url, block_id);
-        let mut request = state.client.post(&url);
-        
-        if let Some(token) = &state.auth_token {
-            request = request.header("Authorization", token);
-        }
-
-        request.send().await
-            .map_err(|e| format!("Failed to send request: {}", e))?
-            .error_for_status()
-            .map_err(|e| format!("Server error: {}", e))?;
-            
-        Ok(())
+        send_remote(&state, api_url, remote::ApiRequest::unarchive(&block_id)).await.map(|_| ())
     } else {
         unarchive_block_local(block_id, state.local_path.as_deref())
     }
 }
 
 pub fn unarchive_block_local(block_id: String, custom_path: Option<&std::path::Path>) -> Result<(), String> {
-    let notes_dir = if let Some(p) = custom_path {
-        p.to_path_buf()
-    } else {
-        let app_dir = dirs::data_dir().ok_or("Could not get data directory")?;
-        app_dir.join("zenus")
-    };
-    let archive_dir = notes_dir.join("archive");
+    let notes_dir = resolve_notes_dir(custom_path)?;
     
-    let src_path = archive_dir.join(format!("{}.md", block_id));
-    let dest_path = notes_dir.join(format!("{}.md", block_id));
+    // Back into the notebook it was archived from, which may be gone by now
+    let location = notebooks::find_note(&notes_dir, &block_id)
+        .filter(|l| l.archived)
+        .ok_or("Archived file not found")?;
+    let dest_path = notebooks::note_path(&notes_dir, location.notebook.as_deref(), false, &block_id);
     
-    if src_path.exists() {
-        fs::rename(&src_path, &dest_path).map_err(|e| format!("Failed to unarchive file: {}", e))?;
-    } else {
-        return Err("Archived file not found".to_string());
+    if let Some(dir) = dest_path.parent() {
+        fs::create_dir_all(dir).map_err(|e| format!("Failed to create directory: {}", e))?;
     }
+    fs::rename(&location.path, &dest_path).map_err(|e| format!("Failed to unarchive file: {}", e))?;
     
+    git::record(custom_path, git::Action::Unarchive, Some(&block_id));
     Ok(())
 }
 
-// Server implementation
-pub async fn run_server(host: String, port: u16, auth_token: Option<String>, local_path: Option<String>) {
-    println!("Starting Zenus Server on {}:{}", host, port);
-    if auth_token.is_some() {
-        println!("Authentication enabled");
-    }
-    if let Some(path) = &local_path {
-        println!("Using custom notes directory: {}", path);
-    }
+/// Sends a request to the server, adding the auth token.
+#[cfg(feature = "tauri-deps")]
+async fn send_remote(state: &AppState, api_url: &str, request: remote::ApiRequest) -> Result<remote::ApiResponse, String> {
+    remote::send(&state.client, api_url, state.auth_token.as_deref(), request).await
+}
 
-    // Middleware to check auth
-    async fn auth_middleware(
-        AxumState(state): AxumState<ServerState>,
-        headers: HeaderMap,
-        request: axum::extract::Request,
-        next: axum::middleware::Next,
-    ) -> Result<impl IntoResponse, StatusCode> {
-        if let Some(token) = &state.auth_token {
-            let auth_header = headers.get("Authorization")
-                .and_then(|h| h.to_str().ok());
-            
-            if auth_header != Some(token) {
-                return Err(StatusCode::UNAUTHORIZED);
-            }
-        }
-        Ok(next.run(request).await)
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn list_notebooks(state: State<'_, AppState>) -> Result<Vec<notebooks::Notebook>, String> {
+    if let Some(api_url) = &state.api_url {
+        send_remote(&state, api_url, remote::ApiRequest::notebooks()).await?.json()
+    } else {
+        notebooks::list_notebooks(state.local_path.as_deref())
     }
-
-    let app = Router::new()
-        .route("/notes", get(api_get_notes).post(api_save_note))
-        .route("/notes/archive", get(api_get_archived_notes))
-        .route("/notes/:id", delete(api_delete_note))
-        .route("/notes/:id/archive", post(api_archive_note).delete(api_delete_archived_note))
-        .route("/notes/:id/unarchive", post(api_unarchive_note))
-        .route("/notes/reorder", post(api_reorder_notes))
-        .layer(CorsLayer::permissive())
-        .layer(axum::middleware::from_fn_with_state(
-            ServerState { 
-                auth_token: auth_token.clone(),
-                local_path: local_path.clone().map(std::path::PathBuf::from),
-            },
-            auth_middleware
-        ))
-        .with_state(ServerState { 
-            auth_token,
-            local_path: local_path.map(std::path::PathBuf::from),
-        });
-
-    let addr: SocketAddr = format!("{}:{}", host, port).parse().expect("Invalid address");
-    let listener = tokio::net::TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
 }
 
-// API Handlers
-async fn api_get_notes(AxumState(state): AxumState<ServerState>) -> Json<Vec<NoteBlock>> {
-    match load_notes_local(None, state.local_path.as_deref()) {
-        Ok(notes) => Json(notes),
-        Err(_) => Json(vec![]),
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn create_notebook(state: State<'_, AppState>, path: String) -> Result<notebooks::Notebook, String> {
+    if let Some(api_url) = &state.api_url {
+        send_remote(&state, api_url, remote::ApiRequest::create_notebook(&path)?).await?.json()
+    } else {
+        notebooks::create_notebook_local(&path, state.local_path.as_deref())
     }
 }
 
-async fn api_save_note(
-    AxumState(state): AxumState<ServerState>,
-    Json(block): Json<NoteBlock>
-) -> StatusCode {
-    match save_block_local(block, state.local_path.as_deref()) {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn rename_notebook(state: State<'_, AppState>, from: String, to: String) -> Result<(), String> {
+    if let Some(api_url) = &state.api_url {
+        send_remote(&state, api_url, remote::ApiRequest::rename_notebook(&from, &to)?).await.map(|_| ())
+    } else {
+        notebooks::rename_notebook_local(&from, &to, state.local_path.as_deref())
     }
 }
 
-async fn api_delete_note(
-    AxumState(state): AxumState<ServerState>,
-    Path(id): Path<String>
-) -> StatusCode {
-    match delete_block_local(id, None, state.local_path.as_deref()) {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn delete_notebook(state: State<'_, AppState>, path: String) -> Result<(), String> {
+    if let Some(api_url) = &state.api_url {
+        send_remote(&state, api_url, remote::ApiRequest::delete_notebook(&path)?).await.map(|_| ())
+    } else {
+        notebooks::delete_notebook_local(&path, state.local_path.as_deref())
     }
 }
 
-async fn api_delete_archived_note(
-    AxumState(state): AxumState<ServerState>,
-    Path(id): Path<String>
-) -> StatusCode {
-    match delete_block_local(id, Some("archive"), state.local_path.as_deref()) {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+/// Moves a note into `notebook`, or to the top level without one.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn move_note(state: State<'_, AppState>, block_id: String, notebook: Option<String>) -> Result<(), String> {
+    if let Some(api_url) = &state.api_url {
+        send_remote(&state, api_url, remote::ApiRequest::move_note(&block_id, notebook.as_deref())?).await.map(|_| ())
+    } else {
+        notebooks::move_note_local(&block_id, notebook.as_deref(), state.local_path.as_deref())
     }
 }
 
-async fn api_reorder_notes(
-    AxumState(state): AxumState<ServerState>,
-    Json(orders): Json<Vec<(String, i32)>>
-) -> StatusCode {
-    match update_orders_local(orders, state.local_path.as_deref()) {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn import_notes(state: State<'_, AppState>, format: import::ImportFormat, path: String, dry_run: bool) -> Result<import::ImportReport, String> {
+    let batch = format.read(std::path::Path::new(&path))?;
+
+    if let Some(api_url) = &state.api_url {
+        // Client Mode: Save each note through the server
+        import_remote(&state, api_url, batch, dry_run).await
+    } else {
+        import::write_local(batch, state.local_path.as_deref(), dry_run)
     }
 }
 
-async fn api_get_archived_notes(AxumState(state): AxumState<ServerState>) -> Json<Vec<NoteBlock>> {
-    match load_notes_local(Some("archive"), state.local_path.as_deref()) {
-        Ok(notes) => Json(notes),
-        Err(_) => Json(vec![]),
-    }
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn import_obsidian(state: State<'_, AppState>, vault: String, dry_run: bool) -> Result<import::ImportReport, String> {
+    import_notes(state, import::ImportFormat::Obsidian, vault, dry_run).await
 }
 
-async fn api_archive_note(
-    AxumState(state): AxumState<ServerState>,
-    Path(id): Path<String>
-) -> StatusCode {
-    match archive_block_local(id, state.local_path.as_deref()) {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
-    }
+/// Exports the whole vault to `path` and returns the path written.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn export_vault(state: State<'_, AppState>, format: bundle::BundleFormat, path: String) -> Result<String, String> {
+    let data = if let Some(api_url) = &state.api_url {
+        // Client Mode: Let the server build the bundle
+        send_remote(&state, api_url, remote::ApiRequest::export(format.extension())?).await?.into_bytes()
+    } else {
+        bundle::export_local(state.local_path.as_deref(), format)?
+    };
+
+    fs::write(&path, data).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    Ok(path)
 }
 
-async fn api_unarchive_note(
-    AxumState(state): AxumState<ServerState>,
-    Path(id): Path<String>
-) -> StatusCode {
-    match unarchive_block_local(id, state.local_path.as_deref()) {
-        Ok(_) => StatusCode::OK,
-        Err(_) => StatusCode::INTERNAL_SERVER_ERROR,
+#[cfg(feature = "tauri-deps")]
+fn local_vault_only(state: &AppState) -> Result<(), String> {
+    if state.api_url.is_some() {
+        return Err("The encrypted vault is only available in Local Mode".to_string());
     }
+    Ok(())
 }
 
 #[cfg(feature = "tauri-deps")]
-#[cfg_attr(mobile, tauri::mobile_entry_point)]
-pub fn run() {
-    let args = Args::parse();
+#[tauri::command]
+async fn vault_status(state: State<'_, AppState>) -> Result<vault::VaultStatus, String> {
+    local_vault_only(&state)?;
+    vault::status(state.local_path.as_deref())
+}
 
-    // Validation: Cannot have both host (Server) and url (Client)
-    if args.host.is_some() && args.url.is_some() {
-        eprintln!("Error: Cannot run in both Server Mode (--host) and Client Mode (--url) at the same time.");
-        std::process::exit(1);
-    }
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn unlock_vault(state: State<'_, AppState>, passphrase: String) -> Result<(), String> {
+    local_vault_only(&state)?;
+    vault::unlock(state.local_path.as_deref(), &passphrase)
+}
 
-    // Validation: Cannot have both url (Client) and path (Local)
-    if args.url.is_some() && args.path.is_some() {
-        eprintln!("Error: Cannot run in both Client Mode (--url) and Local Mode (--path) at the same time.");
-        std::process::exit(1);
-    }
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn lock_vault() -> Result<(), String> {
+    vault::lock();
+    Ok(())
+}
 
-    // Server Mode
-    if let Some(host) = args.host {
-        let rt = tokio::runtime::Runtime::new().unwrap();
-        rt.block_on(run_server(host, args.port, args.auth, args.path));
-        return;
-    }
+/// Encrypts the existing notes directory with `passphrase`.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn encrypt_vault(state: State<'_, AppState>, passphrase: String) -> Result<vault::MigrationReport, String> {
+    local_vault_only(&state)?;
+    vault::encrypt_vault(state.local_path.as_deref(), &passphrase)
+}
+
+/// Decrypts the notes directory and turns encryption off.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn decrypt_vault(state: State<'_, AppState>, passphrase: String) -> Result<vault::MigrationReport, String> {
+    local_vault_only(&state)?;
+    vault::decrypt_vault(state.local_path.as_deref(), &passphrase)
+}
+
+/// Attaches the file at `path` to a note and returns the stored attachment,
+/// including a markdown link to insert into the note.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn add_attachment(state: State<'_, AppState>, block_id: String, path: String) -> Result<attachments::StoredAttachment, String> {
+    let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let name = std::path::Path::new(&path).file_name().and_then(|n| n.to_str()).map(String::from);
+
+    if let Some(api_url) = &state.api_url {
+        // Client Mode: Upload to the server
+        send_remote(&state, api_url, remote::ApiRequest::attach(&block_id, data, name.as_deref())?).await?.json()
+    } else {
+        attachments::attach(&block_id, &data, name.as_deref(), None, state.local_path.as_deref())
+    }
+}
+
+/// Removes attachments no note uses any more.
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn gc_attachments(state: State<'_, AppState>, dry_run: bool) -> Result<attachments::GcReport, String> {
+    if let Some(api_url) = &state.api_url {
+        // Client Mode: Let the server collect its own attachments
+        send_remote(&state, api_url, remote::ApiRequest::collect_garbage(dry_run)?).await?.json()
+    } else {
+        attachments::collect_garbage(state.local_path.as_deref(), dry_run)
+    }
+}
+
+#[cfg(feature = "tauri-deps")]
+#[tauri::command]
+async fn restore_vault(state: State<'_, AppState>, path: String) -> Result<bundle::RestoreReport, String> {
+    let data = fs::read(&path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+
+    if let Some(api_url) = &state.api_url {
+        // Client Mode: Upload the bundle to the server
+        send_remote(&state, api_url, remote::ApiRequest::restore(data)).await?.json()
+    } else {
+        bundle::restore_local(&data, state.local_path.as_deref())
+    }
+}
+
+/// Sends an import batch to the server note by note. The API has no way to
+/// upload files, so attachments are reported as skipped.
+#[cfg(feature = "tauri-deps")]
+async fn import_remote(state: &AppState, api_url: &str, mut batch: import::ImportBatch, dry_run: bool) -> Result<import::ImportReport, String> {
+    let active = load_notes_remote(state, api_url, None, None).await?;
+    let mut existing = load_notes_remote(state, api_url, Some("archive"), None).await?;
+    existing.extend(active.iter().cloned());
+
+    batch.drop_duplicates(&existing);
+    batch.append_after(active.len());
+
+    for attachment in batch.attachments.drain(..) {
+        batch.skipped.push(import::SkippedFile {
+            source: attachment.path,
+            reason: "attachments cannot be uploaded in Client Mode".to_string(),
+        });
+    }
+
+    let report = batch.report(dry_run);
+    if !dry_run {
+        for (_, block) in &batch.notes {
+            save_block_remote(state, api_url, block).await?;
+        }
+    }
+    Ok(report)
+}
+
+/// Verifies that the notes directory exists (creating it if needed) and accepts writes.
+pub fn check_notes_dir_writable(custom_path: Option<&std::path::Path>) -> Result<(), String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    fs::create_dir_all(&notes_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let probe = notes_dir.join(".zenus-readyz");
+    fs::write(&probe, b"ok").map_err(|e| format!("Notes directory is not writable: {}", e))?;
+    fs::remove_file(&probe).map_err(|e| format!("Failed to remove probe file: {}", e))?;
+    Ok(())
+}
+
+// Server implementation
+pub async fn run_server(options: ServerOptions) -> Result<(), String> {
+    let ServerOptions { host, port, auth_token, local_path, pidfile, unix_socket, unix_socket_mode, daily, reminders, webhooks, git } = options;
+
+    tracing::info!(%host, port, "starting Zenus server");
+    if auth_token.is_some() {
+        tracing::info!("authentication enabled");
+    }
+    if let Some(path) = &local_path {
+        tracing::info!(%path, "using custom notes directory");
+    }
+    if let Some(settings) = git {
+        git::enable(local_path.as_deref().map(std::path::Path::new), settings)?;
+        tracing::info!("committing changes with git");
+    }
+
+    // Middleware to check auth
+    async fn auth_middleware(
+        AxumState(state): AxumState<ServerState>,
+        headers: HeaderMap,
+        request: axum::extract::Request,
+        next: axum::middleware::Next,
+    ) -> Result<impl IntoResponse, StatusCode> {
+        if let Some(token) = &state.auth_token {
+            let auth_header = headers.get("Authorization")
+                .and_then(|h| h.to_str().ok());
+            
+            if auth_header != Some(token) {
+                return Err(StatusCode::UNAUTHORIZED);
+            }
+        }
+        Ok(next.run(request).await)
+    }
+
+    // Records request counts and latencies, keyed by route rather than the raw path
+    async fn metrics_middleware(
+        AxumState(state): AxumState<ServerState>,
+        request: axum::extract::Request,
+        next: axum::middleware::Next,
+    ) -> impl IntoResponse {
+        let method = request.method().to_string();
+        let route = request.extensions().get::<axum::extract::MatchedPath>()
+            .map(|p| p.as_str().to_string())
+            .unwrap_or_else(|| "unmatched".to_string());
+        let start = std::time::Instant::now();
+
+        let response = next.run(request).await;
+        state.metrics.record_request(&method, &route, response.status().as_u16(), start.elapsed());
+        response
+    }
+
+    let state = ServerState {
+        auth_token,
+        local_path: local_path.map(std::path::PathBuf::from),
+        metrics: Arc::new(Metrics::default()),
+        daily,
+        reminders,
+        webhooks: Arc::new(webhooks::Webhooks::new(webhooks)?),
+    };
+
+    if let Some(url) = state.reminders.webhook.clone() {
+        tokio::spawn(reminders::run_webhook(url, state.reminders.clone(), state.local_path.clone()));
+    }
+
+    let notes = Router::new()
+        .route("/notes", get(api_get_notes).post(api_save_note))
+        .route("/notes/archive", get(api_get_archived_notes))
+        .route("/notes/:id", delete(api_delete_note))
+        .route("/notes/:id/archive", post(api_archive_note).delete(api_delete_archived_note))
+        .route("/notes/:id/unarchive", post(api_unarchive_note))
+        .route("/notes/:id/append", post(api_append_note))
+        .route("/notes/:id/tasks/:line", post(api_update_task))
+        .route("/notes/:id/move", post(api_move_note))
+        .route("/notebooks", get(api_list_notebooks).post(api_create_notebook))
+        .route("/notebooks/*path", patch(api_rename_notebook).delete(api_delete_notebook))
+        .route("/tasks", get(api_list_tasks))
+        .route("/reminders", get(api_list_reminders))
+        .route("/notes/reorder", post(api_reorder_notes))
+        .route("/notes/:id/share", get(api_list_shares).post(api_create_share))
+        .route("/notes/:id/share/:slug", delete(api_revoke_share))
+        .route(
+            "/notes/:id/attachments",
+            post(api_upload_attachment).layer(DefaultBodyLimit::max(MAX_ATTACHMENT_SIZE)),
+        )
+        .route("/notes/:id/attachments/:file", delete(api_detach_attachment))
+        .route("/attachments/gc", post(api_gc_attachments))
+        .route("/attachments/*path", get(api_get_attachment))
+        .route("/e2e", get(api_get_keyring).put(api_put_keyring))
+        .route("/daily/:date", get(api_open_daily_note))
+        .route("/templates", get(api_list_templates))
+        .route("/templates/:name", get(api_get_template))
+        .route("/templates/:name/notes", post(api_create_from_template))
+        .route("/webhooks/deliveries", get(api_webhook_deliveries))
+        .route("/history", get(api_history))
+        .route("/history/:commit", get(api_show_commit))
+        .route("/export", get(api_export))
+        .route("/import", post(api_restore).layer(DefaultBodyLimit::max(MAX_BUNDLE_SIZE)));
+
+    // The unversioned routes are kept so older desktop clients keep working
+    let app = Router::new()
+        .nest(API_PREFIX, notes.clone())
+        .merge(notes)
+        .layer(CorsLayer::permissive())
+        .layer(axum::middleware::from_fn_with_state(state.clone(), auth_middleware))
+        // Routes below are reachable without the auth token
+        .route("/api/info", get(api_info))
+        .route("/healthz", get(api_healthz))
+        .route("/readyz", get(api_readyz))
+        .route("/metrics", get(api_metrics))
+        .route("/s/:slug", get(api_view_share))
+        // Calendar apps cannot send headers, so the token is checked there
+        .route("/tasks.ics", get(api_tasks_calendar))
+        .route(&format!("{}/tasks.ics", API_PREFIX), get(api_tasks_calendar))
+        .layer(axum::middleware::from_fn_with_state(state.clone(), metrics_middleware))
+        .layer(PropagateRequestIdLayer::x_request_id())
+        .layer(
+            TraceLayer::new_for_http()
+                .make_span_with(|request: &axum::extract::Request| {
+                    let request_id = request.headers().get("x-request-id")
+                        .and_then(|h| h.to_str().ok())
+                        .unwrap_or("-");
+                    tracing::info_span!(
+                        "request",
+                        request_id = %request_id,
+                        method = %request.method(),
+                        path = %request.uri().path(),
+                    )
+                })
+                .on_response(DefaultOnResponse::new().level(tracing::Level::INFO)),
+        )
+        .layer(SetRequestIdLayer::x_request_id(MakeRequestUuid))
+        .with_state(state);
+
+    if let Some(path) = unix_socket {
+        #[cfg(unix)]
+        {
+            let listener = service::bind_unix(&path, unix_socket_mode)?;
+            tracing::info!(path = %path.display(), "listening on unix socket");
+
+            let _pidfile = pidfile.as_deref().map(PidFile::create).transpose()?;
+            service::notify_ready();
+            service::serve_unix(listener, &path, app).await?;
+
+            commit_pending();
+            tracing::info!("server stopped");
+            return Ok(());
+        }
+        #[cfg(not(unix))]
+        {
+            let _ = unix_socket_mode;
+            return Err(format!("Unix sockets are not supported on this platform: {}", path.display()));
+        }
+    }
+
+    let listener = match service::systemd_listener()? {
+        Some(listener) => {
+            tracing::info!("using socket passed by systemd");
+            listener
+        }
+        None => service::bind_tcp(&host, port).await?,
+    };
+    if let Ok(addr) = listener.local_addr() {
+        tracing::info!(%addr, "listening");
+    }
+
+    let _pidfile = pidfile.as_deref().map(PidFile::create).transpose()?;
+    service::notify_ready();
+
+    axum::serve(listener, app)
+        .with_graceful_shutdown(service::shutdown_signal())
+        .await
+        .map_err(|e| format!("Server error: {}", e))?;
+
+    commit_pending();
+    tracing::info!("server stopped");
+    Ok(())
+}
+
+/// Commits changes still waiting for the git commit delay.
+fn commit_pending() {
+    if let Err(e) = git::flush() {
+        tracing::warn!(error = %e, "failed to commit notes");
+    }
+}
+
+// API Handlers
+async fn api_info() -> Json<ServerInfo> {
+    Json(ServerInfo::current())
+}
+
+async fn api_healthz() -> StatusCode {
+    StatusCode::OK
+}
+
+async fn api_readyz(AxumState(state): AxumState<ServerState>) -> (StatusCode, String) {
+    match check_notes_dir_writable(state.local_path.as_deref()) {
+        Ok(_) => (StatusCode::OK, "ready".to_string()),
+        Err(e) => {
+            state.metrics.record_storage_error();
+            (StatusCode::SERVICE_UNAVAILABLE, e)
+        }
+    }
+}
+
+async fn api_metrics(AxumState(state): AxumState<ServerState>) -> impl IntoResponse {
+    let counts = NoteCounts {
+        active: load_notes_local(None, state.local_path.as_deref()).map(|n| n.len()).unwrap_or(0),
+        archived: load_notes_local(Some("archive"), state.local_path.as_deref()).map(|n| n.len()).unwrap_or(0),
+    };
+    (
+        [(axum::http::header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        state.metrics.render(&counts),
+    )
+}
+
+#[derive(Deserialize)]
+struct NotebookQuery {
+    /// Only notes directly in this notebook, or at the top level if empty
+    #[serde(default)]
+    notebook: Option<String>,
+}
+
+async fn api_get_notes(
+    AxumState(state): AxumState<ServerState>,
+    Query(query): Query<NotebookQuery>
+) -> Result<Json<Vec<NoteBlock>>, (StatusCode, String)> {
+    get_notes(&state, None, query.notebook.as_deref())
+}
+
+fn get_notes(state: &ServerState, subdir: Option<&str>, notebook: Option<&str>) -> Result<Json<Vec<NoteBlock>>, (StatusCode, String)> {
+    if let Some(notebook) = notebook {
+        notebooks::normalize_optional(Some(notebook)).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    }
+    match load_notebook_local(subdir, notebook, state.local_path.as_deref()) {
+        Ok(notes) => Ok(Json(notes)),
+        Err(e) => {
+            tracing::error!(error = %e, archived = subdir.is_some(), "failed to load notes");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+async fn api_save_note(
+    AxumState(state): AxumState<ServerState>,
+    Json(block): Json<NoteBlock>
+) -> Result<StatusCode, (StatusCode, String)> {
+    notebooks::normalize_optional(block.notebook.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let note_id = block.id.clone();
+    match save_block_local(block, state.local_path.as_deref()) {
+        Ok(_) => {
+            state.notify(webhooks::WebhookEvent::Save, &note_id);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %note_id, "failed to save note");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+async fn api_delete_note(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>
+) -> StatusCode {
+    let note = state.note_for_webhooks(&id);
+    match delete_block_local(id.clone(), None, state.local_path.as_deref()) {
+        Ok(_) => {
+            if let Some(note) = &note {
+                state.webhooks.notify(webhooks::WebhookEvent::Delete, note);
+            }
+            StatusCode::OK
+        }
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to delete note");
+            state.metrics.record_storage_error();
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn api_delete_archived_note(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>
+) -> StatusCode {
+    let note = state.note_for_webhooks(&id);
+    match delete_block_local(id.clone(), Some("archive"), state.local_path.as_deref()) {
+        Ok(_) => {
+            if let Some(note) = &note {
+                state.webhooks.notify(webhooks::WebhookEvent::Delete, note);
+            }
+            StatusCode::OK
+        }
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to delete archived note");
+            state.metrics.record_storage_error();
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn api_reorder_notes(
+    AxumState(state): AxumState<ServerState>,
+    Json(orders): Json<Vec<(String, i32)>>
+) -> StatusCode {
+    match update_orders_local(orders, state.local_path.as_deref()) {
+        Ok(_) => StatusCode::OK,
+        Err(e) => {
+            tracing::error!(error = %e, "failed to reorder notes");
+            state.metrics.record_storage_error();
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ExportQuery {
+    #[serde(default)]
+    format: bundle::BundleFormat,
+}
+
+async fn api_export(
+    AxumState(state): AxumState<ServerState>,
+    Query(query): Query<ExportQuery>
+) -> Result<impl IntoResponse, StatusCode> {
+    match bundle::export_local(state.local_path.as_deref(), query.format) {
+        Ok(data) => Ok((
+            [
+                (axum::http::header::CONTENT_TYPE, query.format.content_type().to_string()),
+                (
+                    axum::http::header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", query.format.file_name()),
+                ),
+            ],
+            data,
+        )),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to export notes");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn api_restore(
+    AxumState(state): AxumState<ServerState>,
+    body: axum::body::Bytes
+) -> Result<Json<bundle::RestoreReport>, (StatusCode, String)> {
+    match bundle::restore_local(&body, state.local_path.as_deref()) {
+        Ok(report) => Ok(Json(report)),
+        Err(e) => {
+            tracing::warn!(error = %e, "failed to restore bundle");
+            Err((StatusCode::BAD_REQUEST, e))
+        }
+    }
+}
+
+async fn api_get_archived_notes(
+    AxumState(state): AxumState<ServerState>,
+    Query(query): Query<NotebookQuery>
+) -> Result<Json<Vec<NoteBlock>>, (StatusCode, String)> {
+    get_notes(&state, Some(notebooks::ARCHIVE_DIR), query.notebook.as_deref())
+}
+
+async fn api_archive_note(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>
+) -> StatusCode {
+    match archive_block_local(id.clone(), state.local_path.as_deref()) {
+        Ok(_) => {
+            state.notify(webhooks::WebhookEvent::Archive, &id);
+            StatusCode::OK
+        }
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to archive note");
+            state.metrics.record_storage_error();
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn api_unarchive_note(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>
+) -> StatusCode {
+    match unarchive_block_local(id.clone(), state.local_path.as_deref()) {
+        Ok(_) => {
+            state.notify(webhooks::WebhookEvent::Unarchive, &id);
+            StatusCode::OK
+        }
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to unarchive note");
+            state.metrics.record_storage_error();
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn api_append_note(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>,
+    text: String
+) -> Result<StatusCode, (StatusCode, String)> {
+    if text.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Nothing to append".to_string()));
+    }
+    match load_note_local(&id, state.local_path.as_deref()) {
+        Ok(Some(note)) => check_editable(&note).map_err(|e| (StatusCode::CONFLICT, e))?,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, format!("Note {} not found", id))),
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to load note");
+            state.metrics.record_storage_error();
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    }
+    match append_block_local(&id, &text, state.local_path.as_deref()) {
+        Ok(_) => {
+            state.notify(webhooks::WebhookEvent::Save, &id);
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to append to note");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+async fn api_list_tasks(
+    AxumState(state): AxumState<ServerState>,
+    Query(filter): Query<tasks::TaskFilter>
+) -> Result<Json<Vec<tasks::Task>>, StatusCode> {
+    match load_notes_local(None, state.local_path.as_deref()) {
+        Ok(notes) => Ok(Json(tasks::list_tasks(&notes, &filter))),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to load notes");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct ReminderQuery {
+    /// Hours ahead to look
+    #[serde(default = "default_reminder_window")]
+    within: u32,
+}
+
+fn default_reminder_window() -> u32 {
+    24
+}
+
+async fn api_list_reminders(
+    AxumState(state): AxumState<ServerState>,
+    Query(query): Query<ReminderQuery>
+) -> Result<Json<Vec<reminders::Reminder>>, StatusCode> {
+    match load_notes_local(None, state.local_path.as_deref()) {
+        Ok(notes) => Ok(Json(reminders::upcoming(&notes, &state.reminders, chrono::Duration::hours(query.within.into())))),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to load notes");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct CalendarQuery {
+    /// The auth token, for clients that cannot send an `Authorization` header
+    #[serde(default)]
+    token: Option<String>,
+    #[serde(default)]
+    kind: calendar::CalendarKind,
+    #[serde(default)]
+    done: Option<bool>,
+    #[serde(default)]
+    tag: Option<String>,
+}
+
+async fn api_tasks_calendar(
+    AxumState(state): AxumState<ServerState>,
+    headers: HeaderMap,
+    Query(query): Query<CalendarQuery>
+) -> Result<impl IntoResponse, StatusCode> {
+    if let Some(token) = &state.auth_token {
+        let header = headers.get("Authorization").and_then(|h| h.to_str().ok());
+        if query.token.as_ref() != Some(token) && header != Some(token) {
+            return Err(StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    let filter = tasks::TaskFilter { done: query.done, tag: query.tag };
+    match load_notes_local(None, state.local_path.as_deref()) {
+        Ok(notes) => Ok((
+            [(axum::http::header::CONTENT_TYPE, "text/calendar; charset=utf-8")],
+            calendar::tasks_calendar(&notes, &filter, query.kind, &state.reminders),
+        )),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to load notes");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn api_update_task(
+    AxumState(state): AxumState<ServerState>,
+    Path((id, line)): Path<(String, usize)>,
+    body: axum::body::Bytes
+) -> Result<Json<tasks::Task>, (StatusCode, String)> {
+    // Without a body the task is toggled
+    let update: tasks::TaskUpdate = if body.is_empty() {
+        tasks::TaskUpdate::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?
+    };
+    match tasks::set_task_local(&id, line, &update, state.local_path.as_deref()) {
+        Ok(task) => {
+            state.notify(webhooks::WebhookEvent::Save, &id);
+            Ok(Json(task))
+        }
+        Err(tasks::SetTaskError::NotFound(e)) => Err((StatusCode::NOT_FOUND, e)),
+        Err(tasks::SetTaskError::Conflict(e)) => Err((StatusCode::CONFLICT, e)),
+        Err(tasks::SetTaskError::Storage(e)) => {
+            tracing::error!(error = %e, note_id = %id, line, "failed to update task");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct MoveRequest {
+    /// Notebook to move the note into, the top level if unset or empty
+    #[serde(default)]
+    notebook: Option<String>,
+}
+
+async fn api_move_note(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>,
+    Json(request): Json<MoveRequest>
+) -> Result<StatusCode, (StatusCode, String)> {
+    notebooks::normalize_optional(request.notebook.as_deref()).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let storage_error = |e: String| {
+        tracing::error!(error = %e, note_id = %id, "failed to move note");
+        state.metrics.record_storage_error();
+        (StatusCode::INTERNAL_SERVER_ERROR, e)
+    };
+    if load_note_local(&id, state.local_path.as_deref()).map_err(storage_error)?.is_none() {
+        return Err((StatusCode::NOT_FOUND, format!("Note {} not found", id)));
+    }
+    notebooks::move_note_local(&id, request.notebook.as_deref(), state.local_path.as_deref()).map_err(storage_error)?;
+    Ok(StatusCode::OK)
+}
+
+async fn api_list_notebooks(AxumState(state): AxumState<ServerState>) -> Result<Json<Vec<notebooks::Notebook>>, StatusCode> {
+    match notebooks::list_notebooks(state.local_path.as_deref()) {
+        Ok(list) => Ok(Json(list)),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to list notebooks");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct NotebookRequest {
+    path: String,
+}
+
+/// Maps a storage error of a notebook handler to a response.
+fn notebook_error(state: &ServerState, e: String) -> (StatusCode, String) {
+    tracing::error!(error = %e, "failed to change notebook");
+    state.metrics.record_storage_error();
+    (StatusCode::INTERNAL_SERVER_ERROR, e)
+}
+
+async fn api_create_notebook(
+    AxumState(state): AxumState<ServerState>,
+    Json(request): Json<NotebookRequest>
+) -> Result<Json<notebooks::Notebook>, (StatusCode, String)> {
+    let path = notebooks::normalize(&request.path).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if notebooks::exists(&path, false, state.local_path.as_deref()).map_err(|e| notebook_error(&state, e))? {
+        return Err((StatusCode::CONFLICT, format!("Notebook {} already exists", path)));
+    }
+    notebooks::create_notebook_local(&path, state.local_path.as_deref())
+        .map(Json)
+        .map_err(|e| notebook_error(&state, e))
+}
+
+async fn api_rename_notebook(
+    AxumState(state): AxumState<ServerState>,
+    Path(path): Path<String>,
+    Json(request): Json<NotebookRequest>
+) -> Result<StatusCode, (StatusCode, String)> {
+    let from = notebooks::normalize(&path).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let to = notebooks::normalize(&request.path).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    if to.starts_with(&format!("{}/", from)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Cannot move notebook {} into itself", from)));
+    }
+    let local_path = state.local_path.as_deref();
+    if !notebooks::exists(&from, false, local_path).map_err(|e| notebook_error(&state, e))? {
+        return Err((StatusCode::NOT_FOUND, format!("Notebook {} not found", from)));
+    }
+    for archived in [false, true] {
+        if notebooks::exists(&to, archived, local_path).map_err(|e| notebook_error(&state, e))? {
+            return Err((StatusCode::CONFLICT, format!("Notebook {} already exists", to)));
+        }
+    }
+    notebooks::rename_notebook_local(&from, &to, local_path).map_err(|e| notebook_error(&state, e))?;
+    Ok(StatusCode::OK)
+}
+
+async fn api_delete_notebook(
+    AxumState(state): AxumState<ServerState>,
+    Path(path): Path<String>
+) -> Result<StatusCode, (StatusCode, String)> {
+    let path = notebooks::normalize(&path).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let local_path = state.local_path.as_deref();
+    if !notebooks::exists(&path, false, local_path).map_err(|e| notebook_error(&state, e))? {
+        return Err((StatusCode::NOT_FOUND, format!("Notebook {} not found", path)));
+    }
+    if !notebooks::is_empty(&path, local_path).map_err(|e| notebook_error(&state, e))? {
+        return Err((StatusCode::CONFLICT, format!("Notebook {} is not empty", path)));
+    }
+    notebooks::delete_notebook_local(&path, local_path).map_err(|e| notebook_error(&state, e))?;
+    Ok(StatusCode::OK)
+}
+
+#[derive(Deserialize)]
+struct UploadQuery {
+    /// Original file name, used for the extension and link text
+    name: Option<String>,
+}
+
+async fn api_upload_attachment(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>,
+    Query(query): Query<UploadQuery>,
+    headers: HeaderMap,
+    body: axum::body::Bytes
+) -> Result<Json<attachments::StoredAttachment>, (StatusCode, String)> {
+    match load_note_local(&id, state.local_path.as_deref()) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err((StatusCode::NOT_FOUND, format!("Note {} not found", id))),
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to load note");
+            state.metrics.record_storage_error();
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    }
+    if body.is_empty() {
+        return Err((StatusCode::BAD_REQUEST, "Attachment is empty".to_string()));
+    }
+
+    let content_type = headers.get(axum::http::header::CONTENT_TYPE).and_then(|h| h.to_str().ok());
+    match attachments::attach(&id, &body, query.name.as_deref(), content_type, state.local_path.as_deref()) {
+        Ok(stored) => Ok(Json(stored)),
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to store attachment");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+async fn api_detach_attachment(
+    AxumState(state): AxumState<ServerState>,
+    Path((id, file)): Path<(String, String)>
+) -> StatusCode {
+    match attachments::detach(&id, &file, state.local_path.as_deref()) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to detach attachment");
+            state.metrics.record_storage_error();
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+async fn api_get_attachment(
+    AxumState(state): AxumState<ServerState>,
+    Path(path): Path<String>
+) -> Result<impl IntoResponse, StatusCode> {
+    match attachments::read(&path, state.local_path.as_deref()) {
+        Ok(Some((data, mime))) => Ok((
+            [
+                (axum::http::header::CONTENT_TYPE, mime),
+                // Attachments may be HTML or SVG; never let them run scripts on this origin
+                (axum::http::header::CONTENT_SECURITY_POLICY, "sandbox"),
+                (axum::http::header::X_CONTENT_TYPE_OPTIONS, "nosniff"),
+            ],
+            data,
+        )),
+        Ok(None) => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!(error = %e, path = %path, "failed to read attachment");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct GcQuery {
+    #[serde(rename = "dryRun", default)]
+    dry_run: bool,
+}
+
+async fn api_gc_attachments(
+    AxumState(state): AxumState<ServerState>,
+    Query(query): Query<GcQuery>
+) -> Result<Json<attachments::GcReport>, StatusCode> {
+    match attachments::collect_garbage(state.local_path.as_deref(), query.dry_run) {
+        Ok(report) => {
+            tracing::info!(removed = report.removed.len(), bytes = report.bytes, dry_run = query.dry_run, "collected unused attachments");
+            Ok(Json(report))
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to collect unused attachments");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn api_list_templates(AxumState(state): AxumState<ServerState>) -> Result<Json<Vec<templates::Template>>, (StatusCode, String)> {
+    templates::list_templates(state.local_path.as_deref()).map(Json).map_err(|e| {
+        tracing::error!(error = %e, "failed to list templates");
+        state.metrics.record_storage_error();
+        (StatusCode::INTERNAL_SERVER_ERROR, e)
+    })
+}
+
+async fn api_get_template(
+    AxumState(state): AxumState<ServerState>,
+    Path(name): Path<String>
+) -> Result<Json<templates::Template>, (StatusCode, String)> {
+    match templates::load_template(&name, state.local_path.as_deref()) {
+        Ok(Some(template)) => Ok(Json(template)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("Template {} not found", name))),
+        Err(e) => Err((StatusCode::BAD_REQUEST, e)),
+    }
+}
+
+async fn api_create_from_template(
+    AxumState(state): AxumState<ServerState>,
+    Path(name): Path<String>,
+    body: axum::body::Bytes
+) -> Result<Json<NoteBlock>, (StatusCode, String)> {
+    // The body is optional for templates without prompt variables
+    let request: templates::TemplateRequest = if body.is_empty() {
+        templates::TemplateRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?
+    };
+    let template = match templates::load_template(&name, state.local_path.as_deref()) {
+        Ok(Some(template)) => template,
+        Ok(None) => return Err((StatusCode::NOT_FOUND, format!("Template {} not found", name))),
+        Err(e) => return Err((StatusCode::BAD_REQUEST, e)),
+    };
+
+    let storage_error = |e: String| {
+        tracing::error!(error = %e, template = %name, "failed to create note from template");
+        state.metrics.record_storage_error();
+        (StatusCode::INTERNAL_SERVER_ERROR, e)
+    };
+    let notes_dir = resolve_notes_dir(state.local_path.as_deref()).map_err(storage_error)?;
+    if notes_dir.join(e2e::KEYRING_FILE).exists() {
+        return Err((StatusCode::CONFLICT, "The server uses end-to-end encryption; create the note from a client".to_string()));
+    }
+
+    let existing = load_notes_local(None, state.local_path.as_deref()).map_err(storage_error)?.len();
+    let note = template.instantiate(&request, existing).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let id = note.id.clone();
+    save_block_local(note, state.local_path.as_deref()).map_err(storage_error)?;
+    match load_note_local(&id, state.local_path.as_deref()).map_err(storage_error)? {
+        Some(note) => {
+            state.webhooks.notify(webhooks::WebhookEvent::Save, &note);
+            Ok(Json(note))
+        }
+        None => Err(storage_error(format!("Note {} not found", id))),
+    }
+}
+
+async fn api_webhook_deliveries(AxumState(state): AxumState<ServerState>) -> Json<Vec<webhooks::Delivery>> {
+    Json(state.webhooks.deliveries())
+}
+
+#[derive(Deserialize)]
+struct HistoryQuery {
+    /// Only commits that changed this note
+    #[serde(default)]
+    note: Option<String>,
+    #[serde(default = "default_history_limit")]
+    limit: usize,
+}
+
+fn default_history_limit() -> usize {
+    50
+}
+
+fn git_not_enabled() -> (StatusCode, String) {
+    (StatusCode::NOT_FOUND, "Git storage is not enabled on this server".to_string())
+}
+
+async fn api_history(
+    AxumState(state): AxumState<ServerState>,
+    Query(query): Query<HistoryQuery>
+) -> Result<Json<Vec<git::Commit>>, (StatusCode, String)> {
+    if !git::is_enabled() {
+        return Err(git_not_enabled());
+    }
+    if let Some(note) = query.note.as_deref().filter(|note| !is_note_id(note)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid note ID {:?}", note)));
+    }
+    match git::history(query.note.as_deref(), query.limit) {
+        Ok(commits) => Ok(Json(commits)),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to read history");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+async fn api_show_commit(
+    AxumState(state): AxumState<ServerState>,
+    Path(commit): Path<String>,
+    Query(query): Query<HistoryQuery>
+) -> Result<Json<git::CommitDiff>, (StatusCode, String)> {
+    if !git::is_enabled() {
+        return Err(git_not_enabled());
+    }
+    if !git::is_commit_id(&commit) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid commit {:?}", commit)));
+    }
+    if let Some(note) = query.note.as_deref().filter(|note| !is_note_id(note)) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid note ID {:?}", note)));
+    }
+    match git::show(&commit, query.note.as_deref()) {
+        Ok(Some(diff)) => Ok(Json(diff)),
+        Ok(None) => Err((StatusCode::NOT_FOUND, format!("Commit {} not found", commit))),
+        Err(e) => {
+            tracing::error!(error = %e, %commit, "failed to read commit");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+async fn api_open_daily_note(
+    AxumState(state): AxumState<ServerState>,
+    Path(date): Path<String>
+) -> Result<Json<NoteBlock>, (StatusCode, String)> {
+    let date = daily::parse_date(&date).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+    let id = daily::daily_note_id(date).map_err(|e| (StatusCode::BAD_REQUEST, e))?;
+
+    // With end-to-end encryption the server must not write plaintext notes;
+    // clients create the note themselves
+    let storage_error = |e: String| {
+        tracing::error!(error = %e, note_id = %id, "failed to open daily note");
+        state.metrics.record_storage_error();
+        (StatusCode::INTERNAL_SERVER_ERROR, e)
+    };
+    let notes_dir = resolve_notes_dir(state.local_path.as_deref()).map_err(storage_error)?;
+    if notes_dir.join(e2e::KEYRING_FILE).exists() {
+        return match load_note_local(&id, state.local_path.as_deref()).map_err(storage_error)? {
+            Some(note) => Ok(Json(note)),
+            None => Err((StatusCode::CONFLICT, "The server uses end-to-end encryption; create the daily note from a client".to_string())),
+        };
+    }
+
+    daily::open_daily_local(date, &state.daily, state.local_path.as_deref())
+        .map(Json)
+        .map_err(storage_error)
+}
+
+async fn api_get_keyring(AxumState(state): AxumState<ServerState>) -> Result<impl IntoResponse, StatusCode> {
+    let path = resolve_notes_dir(state.local_path.as_deref())
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .join(e2e::KEYRING_FILE);
+    match fs::read(&path) {
+        Ok(data) => Ok(([(axum::http::header::CONTENT_TYPE, "application/json")], data)),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Err(StatusCode::NOT_FOUND),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to read keyring");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn api_put_keyring(
+    AxumState(state): AxumState<ServerState>,
+    body: axum::body::Bytes
+) -> Result<StatusCode, (StatusCode, String)> {
+    if let Err(e) = serde_json::from_slice::<serde_json::Value>(&body) {
+        return Err((StatusCode::BAD_REQUEST, format!("Invalid keyring: {}", e)));
+    }
+    let result = resolve_notes_dir(state.local_path.as_deref()).and_then(|dir| {
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+        fs::write(dir.join(e2e::KEYRING_FILE), &body).map_err(|e| format!("Failed to write keyring: {}", e))
+    });
+    match result {
+        Ok(_) => {
+            tracing::info!("updated end-to-end encryption keyring");
+            Ok(StatusCode::OK)
+        }
+        Err(e) => {
+            tracing::error!(error = %e, "failed to write keyring");
+            state.metrics.record_storage_error();
+            Err((StatusCode::INTERNAL_SERVER_ERROR, e))
+        }
+    }
+}
+
+async fn api_create_share(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>,
+    body: axum::body::Bytes
+) -> Result<Json<share::Share>, (StatusCode, String)> {
+    // The body is optional, a share without one never expires
+    let request: share::ShareRequest = if body.is_empty() {
+        share::ShareRequest::default()
+    } else {
+        serde_json::from_slice(&body).map_err(|e| (StatusCode::BAD_REQUEST, format!("Invalid request: {}", e)))?
+    };
+    match load_note_local(&id, state.local_path.as_deref()) {
+        Ok(Some(_)) => {}
+        Ok(None) => return Err((StatusCode::NOT_FOUND, format!("Note {} not found", id))),
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to load note");
+            state.metrics.record_storage_error();
+            return Err((StatusCode::INTERNAL_SERVER_ERROR, e));
+        }
+    }
+    match share::create_share(&id, &request, state.local_path.as_deref()) {
+        Ok(share) => {
+            tracing::info!(note_id = %id, expires_at = ?share.expires_at, "created share link");
+            Ok(Json(share))
+        }
+        Err(e) => Err((StatusCode::BAD_REQUEST, e)),
+    }
+}
+
+async fn api_list_shares(
+    AxumState(state): AxumState<ServerState>,
+    Path(id): Path<String>
+) -> Result<Json<Vec<share::Share>>, StatusCode> {
+    match share::list_shares(&id, state.local_path.as_deref()) {
+        Ok(shares) => Ok(Json(shares)),
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to load shares");
+            state.metrics.record_storage_error();
+            Err(StatusCode::INTERNAL_SERVER_ERROR)
+        }
+    }
+}
+
+async fn api_revoke_share(
+    AxumState(state): AxumState<ServerState>,
+    Path((id, slug)): Path<(String, String)>
+) -> StatusCode {
+    match share::revoke_share(&id, &slug, state.local_path.as_deref()) {
+        Ok(true) => StatusCode::OK,
+        Ok(false) => StatusCode::NOT_FOUND,
+        Err(e) => {
+            tracing::error!(error = %e, note_id = %id, "failed to revoke share");
+            state.metrics.record_storage_error();
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+/// Public, unauthenticated view of a shared note.
+async fn api_view_share(
+    AxumState(state): AxumState<ServerState>,
+    Path(slug): Path<String>
+) -> impl IntoResponse {
+    use axum::http::header;
+
+    let (status, body) = match share::resolve_share(&slug, state.local_path.as_deref()) {
+        Ok(share::Resolved::Found(note)) => (StatusCode::OK, share::render_page(&note)),
+        Ok(share::Resolved::Expired) => (StatusCode::GONE, "This link has expired".to_string()),
+        Ok(share::Resolved::NotFound) => (StatusCode::NOT_FOUND, "Not found".to_string()),
+        Err(e) => {
+            tracing::error!(error = %e, "failed to resolve share");
+            state.metrics.record_storage_error();
+            (StatusCode::INTERNAL_SERVER_ERROR, "Internal server error".to_string())
+        }
+    };
+    let content_type = if status == StatusCode::OK { "text/html; charset=utf-8" } else { "text/plain; charset=utf-8" };
+    (
+        status,
+        [
+            (header::CONTENT_TYPE, content_type),
+            // Notes may contain raw HTML, so nothing in the page may run or load scripts
+            (header::CONTENT_SECURITY_POLICY, "default-src 'none'; style-src 'unsafe-inline'; img-src *"),
+            (header::REFERRER_POLICY, "no-referrer"),
+            (header::HeaderName::from_static("x-robots-tag"), "noindex"),
+            (header::CACHE_CONTROL, "no-store"),
+        ],
+        body,
+    )
+}
+
+#[cfg(feature = "tauri-deps")]
+#[cfg_attr(mobile, tauri::mobile_entry_point)]
+pub fn run() {
+    let args = Args::parse();
+    let settings = match args.shared.resolve_with_url(args.url) {
+        Ok(settings) => settings,
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+    logging::init_logging(&settings.log_level, settings.log_format);
+
+    // Server Mode is only entered with an explicit --host, never from the config file
+    let url = settings.url.clone();
+
+    let passphrase_file = args.passphrase_file.or_else(|| settings.file.passphrase_file.clone());
+
+    if let Some(Command::E2e { action }) = args.command {
+        let Some(url) = url else {
+            eprintln!("Error: The e2e commands need the server's --url.");
+            std::process::exit(1);
+        };
+        if let Err(e) = run_e2e_command(action, &url, settings.auth.as_deref(), passphrase_file.as_deref()) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    if let Some(command) = args.command {
+        if url.is_some() {
+            eprintln!("Error: Subcommands work on a local notes directory and cannot be combined with --url.");
+            std::process::exit(1);
+        }
+        let local_path = settings.path.map(std::path::PathBuf::from);
+        if let Err(e) = run_command(command, local_path.as_deref(), passphrase_file.as_deref()) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    // Validation: Cannot have both host (Server) and url (Client)
+    if args.host.is_some() && url.is_some() {
+        eprintln!("Error: Cannot run in both Server Mode (--host) and Client Mode (--url) at the same time.");
+        std::process::exit(1);
+    }
+
+    // Server Mode
+    if let Some(host) = args.host {
+        let rt = tokio::runtime::Runtime::new().unwrap();
+        if let Err(e) = rt.block_on(run_server(settings.server_options(host))) {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
+    let client = match url.as_deref().map(http_client).transpose() {
+        Ok(client) => client.unwrap_or_default(),
+        Err(e) => {
+            eprintln!("Error: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    // Client Mode: make sure we speak the same API version as the server
+    let api_url = match &url {
+        Some(url) => match check_server_compatibility(url, settings.auth.as_deref()) {
+            Ok(base) => Some(base),
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        },
+        None => None,
+    };
+
+    // Client Mode: derive the encryption key if the server uses one
+    let vault_key = match (&url, &api_url) {
+        (Some(url), Some(api_url)) => {
+            let passphrase = match passphrase_file.as_deref().map(e2e::read_passphrase).transpose() {
+                Ok(passphrase) => passphrase,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            };
+            match e2e::Remote::new(url, api_url, settings.auth.as_deref()).and_then(|r| r.unlock(passphrase.as_deref())) {
+                Ok(key) => key,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    std::process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+
+    // Local Mode: unlock the encrypted vault up front if a passphrase was given,
+    // otherwise the frontend asks for it through unlock_vault
+    if api_url.is_none() {
+        vault::set_auto_lock(args.auto_lock.or(settings.file.auto_lock).unwrap_or(vault::DEFAULT_AUTO_LOCK_MINUTES));
+        let local_path = settings.path.as_deref().map(std::path::Path::new);
+        if let (Some(file), Ok(true)) = (&passphrase_file, vault::is_encrypted(local_path)) {
+            if let Err(e) = e2e::read_passphrase(file).and_then(|p| vault::unlock(local_path, &p)) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+        if let Some(git) = settings.file.git.clone() {
+            if let Err(e) = git::enable(local_path, git) {
+                eprintln!("Error: {}", e);
+                std::process::exit(1);
+            }
+        }
+    }
 
     // Client/Local Mode
     let app_state = AppState {
-        api_url: args.url,
-        auth_token: args.auth,
-        client: reqwest::Client::new(),
-        local_path: args.path.map(std::path::PathBuf::from),
+        api_url,
+        auth_token: settings.auth,
+        client,
+        local_path: settings.path.map(std::path::PathBuf::from),
+        vault_key,
+        daily: settings.file.daily_template(),
+        reminders: settings.reminders,
     };
 
     tauri::Builder::default()
         .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_notification::init())
         .manage(app_state)
-        .invoke_handler(tauri::generate_handler![greet, save_block, load_notes, delete_block, update_orders, archive_block, unarchive_block])
-        .run(tauri::generate_context!())
-        .expect("error while running tauri application");
+        .setup(|app| {
+            use tauri::Emitter;
+
+            // Lock the vault once it has been idle long enough and tell the frontend
+            let handle = app.handle().clone();
+            std::thread::spawn(move || loop {
+                std::thread::sleep(std::time::Duration::from_secs(30));
+                if vault::lock_if_idle() {
+                    let _ = handle.emit("vault-locked", ());
+                }
+            });
+
+            tauri::async_runtime::spawn(run_reminders(app.handle().clone()));
+            Ok(())
+        })
+        .invoke_handler(tauri::generate_handler![greet, save_block, load_notes, delete_block, update_orders, archive_block, unarchive_block, import_notes, import_obsidian, export_vault, restore_vault, add_attachment, gc_attachments, vault_status, unlock_vault, lock_vault, encrypt_vault, decrypt_vault, lock_note, unlock_note, relock_note, remove_note_lock, open_daily_note, list_templates, create_note_from_template, list_tasks, set_task, upcoming_reminders, list_notebooks, create_notebook, rename_notebook, delete_notebook, move_note])
+        .build(tauri::generate_context!())
+        .expect("error while building tauri application")
+        .run(|_, event| {
+            if let tauri::RunEvent::Exit = event {
+                commit_pending();
+            }
+        });
 }
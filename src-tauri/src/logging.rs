@@ -0,0 +1,35 @@
+//! Structured logging setup shared by the GUI and headless binaries.
+
+use clap::ValueEnum;
+use serde::Deserialize;
+use tracing_subscriber::EnvFilter;
+
+#[derive(ValueEnum, Deserialize, Clone, Copy, Debug, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable, one line per event
+    #[default]
+    Text,
+    /// One JSON object per event, for log shippers
+    Json,
+}
+
+/// Installs the global tracing subscriber.
+///
+/// `RUST_LOG` takes precedence over `level` so individual modules can still be
+/// turned up without touching the command line.
+pub fn init_logging(level: &str, format: LogFormat) {
+    let filter = EnvFilter::try_from_default_env()
+        .or_else(|_| EnvFilter::try_new(level))
+        .unwrap_or_else(|_| EnvFilter::new("info"));
+
+    let builder = tracing_subscriber::fmt().with_env_filter(filter);
+    let result = match format {
+        LogFormat::Text => builder.try_init(),
+        LogFormat::Json => builder.json().flatten_event(true).with_span_list(false).try_init(),
+    };
+
+    if let Err(e) = result {
+        eprintln!("Warning: failed to initialise logging: {}", e);
+    }
+}
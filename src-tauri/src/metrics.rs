@@ -0,0 +1,120 @@
+//! In-process counters for the server, rendered in the Prometheus text format.
+
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Upper bounds (in seconds) of the request latency histogram buckets.
+const LATENCY_BUCKETS: &[f64] = &[0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0];
+
+#[derive(Default)]
+struct Histogram {
+    buckets: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, seconds: f64) {
+        if self.buckets.is_empty() {
+            self.buckets = vec![0; LATENCY_BUCKETS.len()];
+        }
+        for (i, bound) in LATENCY_BUCKETS.iter().enumerate() {
+            if seconds <= *bound {
+                self.buckets[i] += 1;
+            }
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+}
+
+#[derive(Default)]
+pub struct Metrics {
+    /// Request counts keyed by (method, route, status)
+    requests: Mutex<BTreeMap<(String, String, u16), u64>>,
+    /// Request latencies keyed by (method, route)
+    latencies: Mutex<BTreeMap<(String, String), Histogram>>,
+    storage_errors: AtomicU64,
+}
+
+/// Note counts sampled when the metrics are scraped.
+pub struct NoteCounts {
+    pub active: usize,
+    pub archived: usize,
+}
+
+impl Metrics {
+    pub fn record_request(&self, method: &str, route: &str, status: u16, elapsed: Duration) {
+        let mut requests = self.requests.lock().unwrap();
+        *requests
+            .entry((method.to_string(), route.to_string(), status))
+            .or_insert(0) += 1;
+        drop(requests);
+
+        let mut latencies = self.latencies.lock().unwrap();
+        latencies
+            .entry((method.to_string(), route.to_string()))
+            .or_default()
+            .observe(elapsed.as_secs_f64());
+    }
+
+    pub fn record_storage_error(&self) {
+        self.storage_errors.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn render(&self, notes: &NoteCounts) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP zenus_http_requests_total Total HTTP requests handled.\n");
+        out.push_str("# TYPE zenus_http_requests_total counter\n");
+        for ((method, route, status), count) in self.requests.lock().unwrap().iter() {
+            let _ = writeln!(
+                out,
+                "zenus_http_requests_total{{method=\"{}\",route=\"{}\",status=\"{}\"}} {}",
+                method, escape(route), status, count
+            );
+        }
+
+        out.push_str("# HELP zenus_http_request_duration_seconds HTTP request latency.\n");
+        out.push_str("# TYPE zenus_http_request_duration_seconds histogram\n");
+        for ((method, route), hist) in self.latencies.lock().unwrap().iter() {
+            let labels = format!("method=\"{}\",route=\"{}\"", method, escape(route));
+            for (bound, count) in LATENCY_BUCKETS.iter().zip(&hist.buckets) {
+                let _ = writeln!(
+                    out,
+                    "zenus_http_request_duration_seconds_bucket{{{},le=\"{}\"}} {}",
+                    labels, bound, count
+                );
+            }
+            let _ = writeln!(
+                out,
+                "zenus_http_request_duration_seconds_bucket{{{},le=\"+Inf\"}} {}",
+                labels, hist.count
+            );
+            let _ = writeln!(out, "zenus_http_request_duration_seconds_sum{{{}}} {}", labels, hist.sum);
+            let _ = writeln!(out, "zenus_http_request_duration_seconds_count{{{}}} {}", labels, hist.count);
+        }
+
+        out.push_str("# HELP zenus_notes Number of notes on disk.\n");
+        out.push_str("# TYPE zenus_notes gauge\n");
+        let _ = writeln!(out, "zenus_notes{{state=\"active\"}} {}", notes.active);
+        let _ = writeln!(out, "zenus_notes{{state=\"archived\"}} {}", notes.archived);
+
+        out.push_str("# HELP zenus_storage_errors_total Failed reads or writes of the notes directory.\n");
+        out.push_str("# TYPE zenus_storage_errors_total counter\n");
+        let _ = writeln!(
+            out,
+            "zenus_storage_errors_total {}",
+            self.storage_errors.load(Ordering::Relaxed)
+        );
+
+        out
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,219 @@
+//! Private notes protected by their own password.
+//!
+//! A locked note keeps its title and tags readable but stores its content
+//! as `zenus-locked:v1:<base64 nonce + ciphertext>`, sealed with a key
+//! derived from the note's password. The key parameters and check value are
+//! kept in the note's `locked` metadata field. Storage, export and the HTTP
+//! API pass the ciphertext through untouched; only the desktop app decrypts
+//! it, so this works the same in Local and Client Mode.
+//!
+//! Notes handed to the frontend in lists have their content blanked (see
+//! [`redact`]). [`unlock`] decrypts one note on demand and remembers its key
+//! so edits can be encrypted again on save, until the note is relocked or
+//! the vault locks.
+
+use crate::e2e::{KeyParams, VaultKey};
+use crate::NoteBlock;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+
+const LOCKED_PREFIX: &str = "zenus-locked:v1:";
+
+// Keys of the notes unlocked in this session, by note ID
+static NOTE_KEYS: Mutex<BTreeMap<String, VaultKey>> = Mutex::new(BTreeMap::new());
+
+fn note_keys() -> std::sync::MutexGuard<'static, BTreeMap<String, VaultKey>> {
+    NOTE_KEYS.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+pub fn is_locked(block: &NoteBlock) -> bool {
+    block.locked.is_some()
+}
+
+/// Whether `content` is the sealed content of a locked note.
+pub fn is_sealed(content: &str) -> bool {
+    content.starts_with(LOCKED_PREFIX)
+}
+
+/// Returns `block` with the content of a locked note removed, for lists
+/// and search.
+pub fn redact(mut block: NoteBlock) -> NoteBlock {
+    if is_locked(&block) {
+        block.content = String::new();
+    }
+    block
+}
+
+fn seal(key: &VaultKey, block: &NoteBlock) -> Result<String, String> {
+    Ok(format!("{}{}", LOCKED_PREFIX, key.seal(block.content.as_bytes(), block.id.as_bytes())?))
+}
+
+fn open(key: &VaultKey, block: &NoteBlock) -> Result<String, String> {
+    let data = block.content.strip_prefix(LOCKED_PREFIX)
+        .ok_or(format!("Note {} has no encrypted content", block.id))?;
+    let plain = key.open(data, block.id.as_bytes())
+        .map_err(|_| format!("Failed to decrypt note {}: the data was tampered with", block.id))?;
+    String::from_utf8(plain).map_err(|e| format!("Failed to decrypt note {}: {}", block.id, e))
+}
+
+fn derive(params: &KeyParams, password: &str) -> Result<VaultKey, String> {
+    VaultKey::unlock(params, password).map_err(|_| "Wrong password for this note".to_string())
+}
+
+/// Encrypts the content of `block` with `password`.
+pub fn lock(block: NoteBlock, password: &str) -> Result<NoteBlock, String> {
+    if is_locked(&block) {
+        return Err("Note is already locked".to_string());
+    }
+    if password.is_empty() {
+        return Err("Password must not be empty".to_string());
+    }
+    let (key, params) = VaultKey::generate(password)?;
+    lock_with(block, &key, params)
+}
+
+fn lock_with(mut block: NoteBlock, key: &VaultKey, params: KeyParams) -> Result<NoteBlock, String> {
+    crate::attachments::record_linked(&mut block);
+    block.content = seal(key, &block)?;
+    block.locked = Some(params);
+    note_keys().remove(&block.id);
+    Ok(block)
+}
+
+/// Decrypts the content of a locked note and remembers its key for saving.
+/// The returned note is still marked as locked.
+pub fn unlock(mut block: NoteBlock, password: &str) -> Result<NoteBlock, String> {
+    let params = block.locked.as_ref().ok_or("Note is not locked")?;
+    let key = derive(params, password)?;
+    block.content = open(&key, &block)?;
+    note_keys().insert(block.id.clone(), key);
+    Ok(block)
+}
+
+/// Decrypts a locked note for good.
+pub fn remove_lock(block: NoteBlock, password: &str) -> Result<NoteBlock, String> {
+    let mut block = unlock(block, password)?;
+    block.locked = None;
+    note_keys().remove(&block.id);
+    Ok(block)
+}
+
+/// Forgets the key of an unlocked note.
+pub fn relock(id: &str) {
+    note_keys().remove(id);
+}
+
+/// Forgets the keys of all unlocked notes.
+pub fn relock_all() {
+    note_keys().clear();
+}
+
+/// Prepares a locked note coming from the frontend for saving.
+///
+/// If the note was unlocked with [`unlock`], its content is what the user
+/// edited, even when blank, and is encrypted with the remembered key.
+/// Otherwise the frontend only saw the redacted note, so the stored
+/// ciphertext from `existing` is kept and only the other fields change.
+pub fn prepare_save(mut block: NoteBlock, existing: Option<NoteBlock>) -> Result<NoteBlock, String> {
+    if !is_locked(&block) || is_sealed(&block.content) {
+        return Ok(block);
+    }
+
+    if let Some(key) = note_keys().get(&block.id) {
+        crate::attachments::record_linked(&mut block);
+        block.content = seal(key, &block)?;
+        return Ok(block);
+    }
+    if !block.content.is_empty() {
+        return Err("Note is locked; unlock it before editing".to_string());
+    }
+
+    let existing = existing
+        .filter(|n| is_sealed(&n.content))
+        .ok_or(format!("Note {} has no encrypted content to keep", block.id))?;
+    block.content = existing.content;
+    block.locked = existing.locked;
+    Ok(block)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::time::{Duration, SystemTime};
+
+    // Cheap KDF parameters so the tests don't spend seconds in Argon2
+    fn locked(id: &str, content: &str, password: &str) -> NoteBlock {
+        let (key, params) = VaultKey::generate_with(password, 64, 1, 1).unwrap();
+        lock_with(NoteBlock::test(id, content), &key, params).unwrap()
+    }
+
+    #[test]
+    fn content_round_trips_through_the_lock() {
+        let stored = locked("lock-round-trip", "Bank PIN 1234", "pw");
+        assert!(is_sealed(&stored.content) && !stored.content.contains("1234"));
+        assert!(unlock(stored.clone(), "wrong").is_err());
+
+        // The note ID is associated data, so content can't be moved to another note
+        let mut moved = stored.clone();
+        moved.id = "lock-other".to_string();
+        assert!(unlock(moved, "pw").is_err());
+
+        let opened = remove_lock(stored, "pw").unwrap();
+        assert_eq!(opened.content, "Bank PIN 1234");
+        assert!(!is_locked(&opened));
+    }
+
+    #[test]
+    fn blank_save_of_a_note_not_unlocked_keeps_the_ciphertext() {
+        let stored = locked("lock-keep", "secret", "pw");
+        let mut edited = redact(stored.clone());
+        edited.title = "Renamed".to_string();
+
+        let saved = prepare_save(edited, Some(stored.clone())).unwrap();
+        assert_eq!(saved.content, stored.content);
+        assert_eq!(saved.title, "Renamed");
+    }
+
+    #[test]
+    fn unlocked_note_can_be_emptied() {
+        let stored = locked("lock-empty", "secret", "pw");
+        let mut edited = unlock(stored.clone(), "pw").unwrap();
+        edited.content = String::new();
+
+        let saved = prepare_save(edited, Some(stored.clone())).unwrap();
+        relock("lock-empty");
+        assert!(is_sealed(&saved.content));
+        assert_ne!(saved.content, stored.content);
+        assert_eq!(unlock(saved, "pw").unwrap().content, "");
+        relock("lock-empty");
+    }
+
+    #[test]
+    fn locking_keeps_linked_attachments_from_garbage_collection() {
+        let dir = tempfile::tempdir().unwrap();
+        let attachments = dir.path().join(crate::attachments::ATTACHMENTS_DIR);
+        fs::create_dir_all(&attachments).unwrap();
+        let (image, orphan) = (format!("{}.png", "ab".repeat(32)), format!("{}.png", "cd".repeat(32)));
+        for file in [&image, &orphan] {
+            let f = fs::File::create(attachments.join(file)).unwrap();
+            // Older than the grace period, so only the links keep it
+            f.set_modified(SystemTime::now() - Duration::from_secs(24 * 60 * 60)).unwrap();
+        }
+
+        let stored = locked("lock-attachments", &format!("![scan](attachments/{})", image), "pw");
+        crate::save_block_local(stored, Some(dir.path())).unwrap();
+
+        let report = crate::attachments::collect_garbage(Some(dir.path()), false).unwrap();
+        assert_eq!(report.removed, vec![orphan]);
+        assert!(attachments.join(&image).exists());
+    }
+
+    #[test]
+    fn edits_to_a_note_not_unlocked_are_refused() {
+        let stored = locked("lock-refuse", "secret", "pw");
+        let mut edited = redact(stored.clone());
+        edited.content = "plaintext".to_string();
+        assert!(prepare_save(edited, Some(stored)).is_err());
+    }
+}
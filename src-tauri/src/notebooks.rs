@@ -0,0 +1,322 @@
+//! Notebooks: nested folders of notes.
+//!
+//! A notebook is a directory in the notes directory, and its path is the
+//! path of that directory, like `work/projects`. Notes outside any notebook
+//! stay at the top level. The location of the file is the only record of a
+//! note's notebook: it is filled into [`NoteBlock::notebook`] when the note
+//! is read, and only used by a save to place a new note. Archived notes keep
+//! their notebook under `archive/`, so unarchiving puts them back.
+//!
+//! The top-level `archive`, `attachments` and `templates` directories, and
+//! any directory starting with a dot, are not notebooks.
+
+use crate::attachments::ATTACHMENTS_DIR;
+use crate::templates::TEMPLATES_DIR;
+use crate::{git, resolve_notes_dir, EDIT_LOCK};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+pub const ARCHIVE_DIR: &str = "archive";
+
+/// Top-level directories that hold something other than notes.
+const RESERVED: &[&str] = &[ARCHIVE_DIR, ATTACHMENTS_DIR, TEMPLATES_DIR];
+
+/// Characters not allowed in notebook names on some file systems.
+const INVALID_CHARS: &[char] = &['\\', ':', '*', '?', '"', '<', '>', '|'];
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Notebook {
+    /// Path like `work/projects`
+    pub path: String,
+    /// Active notes directly in this notebook
+    pub notes: usize,
+}
+
+/// Where a note file is.
+#[derive(Debug)]
+pub struct NoteLocation {
+    pub path: PathBuf,
+    pub notebook: Option<String>,
+    pub archived: bool,
+}
+
+/// Checks a notebook path and returns it without surrounding slashes or
+/// spaces around its names.
+pub fn normalize(path: &str) -> Result<String, String> {
+    let names: Vec<&str> = path.trim().trim_matches('/').split('/').map(str::trim).collect();
+    if names.iter().any(|name| name.is_empty()) {
+        return Err(format!("Invalid notebook {:?}: names must not be empty", path));
+    }
+    for name in &names {
+        if name.starts_with('.') || name.contains(INVALID_CHARS) || name.chars().any(char::is_control) {
+            return Err(format!("Invalid notebook {:?}: {:?} is not a valid name", path, name));
+        }
+    }
+    if RESERVED.contains(&names[0]) {
+        return Err(format!("Invalid notebook {:?}: {:?} is reserved", path, names[0]));
+    }
+    Ok(names.join("/"))
+}
+
+/// Like [`normalize`], but an empty path is the top level.
+pub fn normalize_optional(path: Option<&str>) -> Result<Option<String>, String> {
+    match path.map(str::trim) {
+        None | Some("") | Some("/") => Ok(None),
+        Some(path) => normalize(path).map(Some),
+    }
+}
+
+/// The directory of `notebook` among the active or archived notes.
+fn notebook_dir(notes_dir: &Path, notebook: Option<&str>, archived: bool) -> PathBuf {
+    let mut dir = if archived { notes_dir.join(ARCHIVE_DIR) } else { notes_dir.to_path_buf() };
+    if let Some(notebook) = notebook {
+        dir.extend(notebook.split('/'));
+    }
+    dir
+}
+
+/// The file of note `id` in `notebook`.
+pub fn note_path(notes_dir: &Path, notebook: Option<&str>, archived: bool, id: &str) -> PathBuf {
+    notebook_dir(notes_dir, notebook, archived).join(format!("{}.md", id))
+}
+
+/// Whether a directory entry is a notebook. Reserved names only count at
+/// the top level of the active notes.
+fn is_notebook(entry: &fs::DirEntry, top_level: bool) -> bool {
+    let name = entry.file_name();
+    let name = name.to_string_lossy();
+    entry.file_type().is_ok_and(|t| t.is_dir())
+        && !name.starts_with('.')
+        && !(top_level && RESERVED.contains(&name.as_ref()))
+}
+
+fn join_notebook(parent: Option<&str>, name: &str) -> String {
+    match parent {
+        Some(parent) => format!("{}/{}", parent, name),
+        None => name.to_string(),
+    }
+}
+
+/// Calls `visit` for every notebook directory below `dir`, parents first.
+fn walk_notebooks(dir: &Path, parent: Option<&str>, top_level: bool, visit: &mut dyn FnMut(&Path, &str)) {
+    let Ok(entries) = fs::read_dir(dir) else { return };
+    for entry in entries.flatten() {
+        if !is_notebook(&entry, top_level) {
+            continue;
+        }
+        let notebook = join_notebook(parent, &entry.file_name().to_string_lossy());
+        visit(&entry.path(), &notebook);
+        walk_notebooks(&entry.path(), Some(&notebook), false, visit);
+    }
+}
+
+fn md_files(dir: &Path) -> Vec<PathBuf> {
+    let Ok(entries) = fs::read_dir(dir) else { return Vec::new() };
+    entries.flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md"))
+        .collect()
+}
+
+/// Note files of the active or archived notes with their notebooks.
+pub fn note_files(notes_dir: &Path, archived: bool) -> Vec<(PathBuf, Option<String>)> {
+    let root = notebook_dir(notes_dir, None, archived);
+    let mut files: Vec<(PathBuf, Option<String>)> = md_files(&root).into_iter().map(|path| (path, None)).collect();
+    walk_notebooks(&root, None, !archived, &mut |dir, notebook| {
+        files.extend(md_files(dir).into_iter().map(|path| (path, Some(notebook.to_string()))));
+    });
+    files
+}
+
+/// Finds the file of note `id`, active ones first.
+pub fn find_note(notes_dir: &Path, id: &str) -> Option<NoteLocation> {
+    let file_name = format!("{}.md", id);
+    for archived in [false, true] {
+        let top = note_path(notes_dir, None, archived, id);
+        if top.is_file() {
+            return Some(NoteLocation { path: top, notebook: None, archived });
+        }
+        let root = notebook_dir(notes_dir, None, archived);
+        let mut found = None;
+        walk_notebooks(&root, None, !archived, &mut |dir, notebook| {
+            let path = dir.join(&file_name);
+            if found.is_none() && path.is_file() {
+                found = Some((path, notebook.to_string()));
+            }
+        });
+        if let Some((path, notebook)) = found {
+            return Some(NoteLocation { path, notebook: Some(notebook), archived });
+        }
+    }
+    None
+}
+
+/// Whether the notebook `path` exists among the active or archived notes.
+pub fn exists(path: &str, archived: bool, custom_path: Option<&Path>) -> Result<bool, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    Ok(notebook_dir(&notes_dir, Some(path), archived).is_dir())
+}
+
+/// All active notebooks, sorted by path. Empty ones are included.
+pub fn list_notebooks(custom_path: Option<&Path>) -> Result<Vec<Notebook>, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let mut notebooks = Vec::new();
+    walk_notebooks(&notes_dir, None, true, &mut |dir, notebook| {
+        notebooks.push(Notebook { path: notebook.to_string(), notes: md_files(dir).len() });
+    });
+    notebooks.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(notebooks)
+}
+
+/// Creates the notebook `path` and any parents it needs.
+pub fn create_notebook_local(path: &str, custom_path: Option<&Path>) -> Result<Notebook, String> {
+    let path = normalize(path)?;
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let dir = notebook_dir(&notes_dir, Some(&path), false);
+    if dir.is_dir() {
+        return Err(format!("Notebook {} already exists", path));
+    }
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create notebook: {}", e))?;
+    Ok(Notebook { path, notes: 0 })
+}
+
+/// Renames the notebook `from` to `to`, which may move it into another
+/// notebook. Its archived notes move along.
+pub fn rename_notebook_local(from: &str, to: &str, custom_path: Option<&Path>) -> Result<(), String> {
+    let (from, to) = (normalize(from)?, normalize(to)?);
+    if to.starts_with(&format!("{}/", from)) {
+        return Err(format!("Cannot move notebook {} into itself", from));
+    }
+    let _guard = EDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let notes_dir = resolve_notes_dir(custom_path)?;
+
+    let source = notebook_dir(&notes_dir, Some(&from), false);
+    if !source.is_dir() {
+        return Err(format!("Notebook {} not found", from));
+    }
+    let mut moves = vec![(source, notebook_dir(&notes_dir, Some(&to), false))];
+    let archived = notebook_dir(&notes_dir, Some(&from), true);
+    if archived.is_dir() {
+        moves.push((archived, notebook_dir(&notes_dir, Some(&to), true)));
+    }
+    if moves.iter().any(|(_, dest)| dest.exists()) {
+        return Err(format!("Notebook {} already exists", to));
+    }
+
+    for (source, dest) in moves {
+        if let Some(parent) = dest.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create notebook: {}", e))?;
+        }
+        fs::rename(&source, &dest).map_err(|e| format!("Failed to rename notebook: {}", e))?;
+    }
+    git::record(custom_path, git::Action::Notebook, None);
+    Ok(())
+}
+
+/// Whether `dir` has any files, at any depth.
+fn has_files(dir: &Path) -> bool {
+    let Ok(entries) = fs::read_dir(dir) else { return false };
+    entries.flatten().any(|entry| match entry.file_type() {
+        Ok(t) if t.is_dir() => has_files(&entry.path()),
+        _ => true,
+    })
+}
+
+/// Whether the active notebook `path` holds no files, not even in the
+/// notebooks inside it.
+pub fn is_empty(path: &str, custom_path: Option<&Path>) -> Result<bool, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    Ok(!has_files(&notebook_dir(&notes_dir, Some(path), false)))
+}
+
+/// Deletes the notebook `path`, which must not hold any notes, not even in
+/// the notebooks inside it. Its archived notes are kept.
+pub fn delete_notebook_local(path: &str, custom_path: Option<&Path>) -> Result<(), String> {
+    let path = normalize(path)?;
+    let _guard = EDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let dir = notebook_dir(&notes_dir, Some(&path), false);
+    if !dir.is_dir() {
+        return Err(format!("Notebook {} not found", path));
+    }
+    if has_files(&dir) {
+        return Err(format!("Notebook {} is not empty", path));
+    }
+    fs::remove_dir_all(&dir).map_err(|e| format!("Failed to delete notebook: {}", e))?;
+    git::record(custom_path, git::Action::Notebook, None);
+    Ok(())
+}
+
+/// Moves an active or archived note into `notebook`, or to the top level
+/// with `None`, creating the notebook if needed.
+pub fn move_note_local(id: &str, notebook: Option<&str>, custom_path: Option<&Path>) -> Result<(), String> {
+    let notebook = normalize_optional(notebook)?;
+    let _guard = EDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let location = find_note(&notes_dir, id).ok_or(format!("Note {} not found", id))?;
+    if location.notebook == notebook {
+        return Ok(());
+    }
+
+    let dest = note_path(&notes_dir, notebook.as_deref(), location.archived, id);
+    if let Some(parent) = dest.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create notebook: {}", e))?;
+    }
+    fs::rename(&location.path, &dest).map_err(|e| format!("Failed to move note: {}", e))?;
+    git::record(custom_path, git::Action::Move, Some(id));
+    Ok(())
+}
+
+/// `/notebooks/<path>` of the HTTP API, with the names percent-encoded.
+pub fn api_path(path: &str) -> String {
+    let mut url = String::from("/notebooks");
+    for name in path.split('/') {
+        url.push('/');
+        for byte in name.bytes() {
+            if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+                url.push(byte as char);
+            } else {
+                url.push_str(&format!("%{:02X}", byte));
+            }
+        }
+    }
+    url
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{save_block_local, NoteBlock};
+
+    #[test]
+    fn normalize_rejects_paths_outside_the_notebooks() {
+        for path in ["..", "work/..", ".hidden", "work/.git", "a//b", "", "/", "archive/x", "attachments", "templates/x", "a\\b", "a:b"] {
+            assert!(normalize(path).is_err(), "{:?}", path);
+        }
+        // Reserved names are only reserved at the top level
+        assert_eq!(normalize("work/archive").unwrap(), "work/archive");
+    }
+
+    #[test]
+    fn normalize_trims_slashes_and_spaces() {
+        assert_eq!(normalize("/work/projects/").unwrap(), "work/projects");
+        assert_eq!(normalize("  work / projects  ").unwrap(), "work/projects");
+        assert_eq!(normalize_optional(Some(" / ")).unwrap(), None);
+    }
+
+    #[test]
+    fn notebooks_cannot_move_into_themselves() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Some(dir.path());
+        create_notebook_local("work", path).unwrap();
+        save_block_local(NoteBlock { notebook: Some("work".to_string()), ..NoteBlock::test("1", "Plan") }, path).unwrap();
+
+        assert!(rename_notebook_local("work", "work/old", path).unwrap_err().contains("into itself"));
+        assert!(rename_notebook_local("work", " /work/ ", path).unwrap_err().contains("already exists"));
+        assert!(find_note(dir.path(), "1").unwrap().path.starts_with(dir.path().join("work")));
+
+        rename_notebook_local("work", "archived/work", path).unwrap();
+        assert_eq!(find_note(dir.path(), "1").unwrap().notebook.as_deref(), Some("archived/work"));
+    }
+}
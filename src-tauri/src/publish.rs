@@ -0,0 +1,218 @@
+//! Static site generation from notes carrying a given tag.
+//!
+//! All pages are written flat into the output directory (`index.html`,
+//! `<note>.html` and `tag-<tag>.html`) so every link between them is a plain
+//! relative file name. Tags whose names make the same file name, or none,
+//! get a number.
+
+use crate::render::{escape_html, render_markdown, slugify};
+use crate::templates::replace_placeholders;
+use crate::{load_notes_local, NoteBlock};
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Page template used unless `--template` is given. `{{site_title}}`,
+/// `{{title}}` and `{{content}}` are replaced in every page.
+pub const DEFAULT_TEMPLATE: &str = r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<title>{{title}} - {{site_title}}</title>
+<style>
+body { max-width: 46rem; margin: 2rem auto; padding: 0 1rem; font-family: system-ui, sans-serif; line-height: 1.6; color: #222; }
+header a { color: inherit; text-decoration: none; font-weight: 600; }
+a { color: #2563eb; }
+pre { background: #f4f4f5; padding: 0.75rem; overflow-x: auto; }
+.tags a { display: inline-block; margin-right: 0.5rem; font-size: 0.9rem; }
+.wikilink-missing { color: #888; }
+li:has(> input[type=checkbox]) { list-style: none; }
+</style>
+</head>
+<body>
+<header><a href="index.html">{{site_title}}</a></header>
+<main>
+{{content}}
+</main>
+</body>
+</html>
+"#;
+
+#[derive(Debug, Clone)]
+pub struct PublishOptions {
+    pub out_dir: PathBuf,
+    /// Only notes with this tag are published
+    pub tag: String,
+    pub template: Option<PathBuf>,
+    pub site_title: String,
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct PublishReport {
+    pub notes: usize,
+    pub tags: usize,
+}
+
+impl PublishReport {
+    pub fn summary(&self, out_dir: &Path) -> String {
+        format!(
+            "Published {} notes and {} tag pages to {}\n",
+            self.notes,
+            self.tags,
+            out_dir.display()
+        )
+    }
+}
+
+/// Renders every active note tagged `options.tag` into a static site.
+pub fn publish_local(custom_path: Option<&Path>, options: &PublishOptions) -> Result<PublishReport, String> {
+    let template = match &options.template {
+        Some(path) => fs::read_to_string(path)
+            .map_err(|e| format!("Failed to read template {}: {}", path.display(), e))?,
+        None => DEFAULT_TEMPLATE.to_string(),
+    };
+
+    let notes: Vec<NoteBlock> = load_notes_local(None, custom_path)?
+        .into_iter()
+        // Locked notes are never published, even with the tag
+        .filter(|n| n.tags.iter().any(|t| t == &options.tag) && !crate::note_lock::is_locked(n))
+        .collect();
+
+    // Unique page name per note, looked up by title for wiki links
+    let mut pages: Vec<String> = Vec::new();
+    let mut by_title: HashMap<String, String> = HashMap::new();
+    for note in &notes {
+        let mut slug = slugify(&note.title);
+        if slug.is_empty() || slug == "index" || slug.starts_with("tag-") || pages.contains(&format!("{}.html", slug)) {
+            slug = if slug.is_empty() { note.id.clone() } else { format!("{}-{}", slug, note.id) };
+        }
+        let page = format!("{}.html", slug);
+        by_title.entry(note.title.to_lowercase()).or_insert_with(|| page.clone());
+        pages.push(page);
+    }
+
+    // Tags other than the one used for selection get their own page
+    let mut tags: BTreeMap<String, Vec<usize>> = BTreeMap::new();
+    for (i, note) in notes.iter().enumerate() {
+        for tag in note.tags.iter().filter(|t| *t != &options.tag) {
+            tags.entry(tag.clone()).or_default().push(i);
+        }
+    }
+    let tag_pages = tag_pages(tags.keys());
+    let tag_page = |tag: &str| tag_pages[tag].as_str();
+
+    fs::create_dir_all(&options.out_dir)
+        .map_err(|e| format!("Failed to create {}: {}", options.out_dir.display(), e))?;
+    let write = |name: &str, title: &str, content: &str| -> Result<(), String> {
+        // One pass, so placeholders in titles and notes are not filled in
+        let html = replace_placeholders(&template, |name| {
+            Ok(match name {
+                "site_title" => Some(escape_html(&options.site_title)),
+                "title" => Some(escape_html(title)),
+                "content" => Some(content.to_string()),
+                _ => None,
+            })
+        })?;
+        let path = options.out_dir.join(name);
+        fs::write(&path, html).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    };
+
+    for (note, page) in notes.iter().zip(&pages) {
+        let mut content = format!("<h1>{}</h1>\n", escape_html(&note.title));
+        let note_tags: Vec<&String> = note.tags.iter().filter(|t| *t != &options.tag).collect();
+        if !note_tags.is_empty() {
+            content.push_str("<p class=\"tags\">");
+            for tag in note_tags {
+                content.push_str(&format!("<a href=\"{}\">#{}</a>", tag_page(tag), escape_html(tag)));
+            }
+            content.push_str("</p>\n");
+        }
+        content.push_str(&render_markdown(&note.content, |title| by_title.get(&title.to_lowercase()).cloned()));
+        write(page, &note.title, &content)?;
+    }
+
+    let note_list = |indices: &mut dyn Iterator<Item = usize>| {
+        let mut list = String::from("<ul>\n");
+        for i in indices {
+            list.push_str(&format!("<li><a href=\"{}\">{}</a></li>\n", pages[i], escape_html(&notes[i].title)));
+        }
+        list.push_str("</ul>\n");
+        list
+    };
+
+    for (tag, indices) in &tags {
+        let content = format!("<h1>#{}</h1>\n{}", escape_html(tag), note_list(&mut indices.iter().copied()));
+        write(tag_page(tag), &format!("#{}", tag), &content)?;
+    }
+
+    let mut index = format!("<h1>{}</h1>\n{}", escape_html(&options.site_title), note_list(&mut (0..notes.len())));
+    if !tags.is_empty() {
+        index.push_str("<h2>Tags</h2>\n<p class=\"tags\">");
+        for tag in tags.keys() {
+            index.push_str(&format!("<a href=\"{}\">#{}</a>", tag_page(tag), escape_html(tag)));
+        }
+        index.push_str("</p>\n");
+    }
+    write("index.html", &options.site_title, &index)?;
+
+    Ok(PublishReport {
+        notes: notes.len(),
+        tags: tags.len(),
+    })
+}
+
+/// Page names for tags, `tag-<slug>.html`. Tags without a slug are
+/// numbered, and a slug already taken by another tag gets a number.
+fn tag_pages<'a>(tags: impl Iterator<Item = &'a String>) -> HashMap<String, String> {
+    let mut pages: HashMap<String, String> = HashMap::new();
+    for tag in tags {
+        let slug = slugify(tag);
+        let mut page = format!("tag-{}.html", slug);
+        let mut n = 0;
+        while (slug.is_empty() && n == 0) || pages.values().any(|p| *p == page) {
+            n += 1;
+            page = if slug.is_empty() { format!("tag-{}.html", n) } else { format!("tag-{}-{}.html", slug, n + 1) };
+        }
+        pages.insert(tag.clone(), page);
+    }
+    pages
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_pages_are_unique() {
+        let tags: Vec<String> = ["C++", "c", "!!", "??", "Work"].iter().map(|t| t.to_string()).collect();
+        let pages = tag_pages(tags.iter());
+        let mut names: Vec<&String> = pages.values().collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), tags.len());
+        assert_eq!(pages["Work"], "tag-work.html");
+        assert!(!names.contains(&&"tag-.html".to_string()));
+    }
+
+    #[test]
+    fn placeholders_in_titles_are_not_filled_in() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let note = NoteBlock { title: "{{content}}".to_string(), tags: vec!["pub".to_string()], ..NoteBlock::test("1", "Body") };
+        crate::save_block_local(note, Some(&dir.join("notes"))).unwrap();
+
+        let options = PublishOptions {
+            out_dir: dir.join("site"),
+            tag: "pub".to_string(),
+            template: None,
+            site_title: "{{title}}".to_string(),
+        };
+        assert_eq!(publish_local(Some(&dir.join("notes")), &options).unwrap().notes, 1);
+        let index = fs::read_to_string(dir.join("site").join("index.html")).unwrap();
+        assert!(index.contains("<title>{{title}} - {{title}}</title>"));
+        assert!(index.contains("<h1>{{title}}</h1>"));
+        assert!(index.contains(">{{content}}</a>"));
+    }
+}
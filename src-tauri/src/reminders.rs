@@ -0,0 +1,311 @@
+//! Reminders for tasks and notes.
+//!
+//! A reminder is set by
+//!
+//! - `@remind(...)` on an open task,
+//! - otherwise `@due(...)` on an open task,
+//! - `remindAt` in the metadata of a note, for the whole note.
+//!
+//! Times are `YYYY-MM-DD HH:MM` in the local time of the machine checking
+//! them; a date alone means [`ReminderSettings::time`] on that day. A
+//! [`Scheduler`] hands out each reminder once its time has passed, which the
+//! desktop app shows as a notification and `zenus-headless` posts to a
+//! webhook.
+
+use crate::tasks::parse_tasks;
+use crate::{load_notes_local, NoteBlock};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// How often reminders are checked.
+pub const CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Time of day for reminders on a date without a time.
+pub const DEFAULT_TIME: &str = "09:00";
+
+#[derive(Clone, Debug)]
+pub struct ReminderSettings {
+    /// Time of day for dates without a time
+    pub time: NaiveTime,
+    /// URL `zenus-headless` posts each reminder to when it is due
+    pub webhook: Option<String>,
+}
+
+impl Default for ReminderSettings {
+    fn default() -> Self {
+        ReminderSettings { time: parse_time(DEFAULT_TIME).unwrap_or_default(), webhook: None }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Reminder {
+    #[serde(rename = "noteId")]
+    pub note_id: String,
+    #[serde(rename = "noteTitle")]
+    pub note_title: String,
+    /// Line of the task, unset for a reminder of the whole note
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub line: Option<usize>,
+    /// Text of the task, or the title of the note
+    pub text: String,
+    /// When the reminder is due
+    pub at: DateTime<Local>,
+    /// Due date of the task, if it has one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+}
+
+/// Parses `HH:MM`.
+pub fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(value.trim(), "%H:%M").map_err(|_| format!("Invalid time {:?}, expected HH:MM", value))
+}
+
+/// Parses `YYYY-MM-DD`, `YYYY-MM-DD HH:MM` or an RFC 3339 timestamp, which
+/// is converted to local time.
+pub fn parse_when(value: &str) -> Option<(NaiveDate, Option<NaiveTime>)> {
+    let value = value.trim();
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Some((date, None));
+    }
+    for pattern in ["%Y-%m-%d %H:%M", "%Y-%m-%dT%H:%M", "%Y-%m-%dT%H:%M:%S"] {
+        if let Ok(t) = NaiveDateTime::parse_from_str(value, pattern) {
+            return Some((t.date(), Some(t.time())));
+        }
+    }
+    let t = DateTime::parse_from_rfc3339(value).ok()?.with_timezone(&Local).naive_local();
+    Some((t.date(), Some(t.time())))
+}
+
+/// The inverse of [`parse_when`], in the form tasks are written.
+pub fn format_when(date: NaiveDate, time: Option<NaiveTime>) -> String {
+    match time {
+        Some(time) => format!("{} {}", date.format("%Y-%m-%d"), time.format("%H:%M")),
+        None => date.format("%Y-%m-%d").to_string(),
+    }
+}
+
+/// The local time of `value`.
+pub(crate) fn local_time(value: &str, settings: &ReminderSettings) -> Option<DateTime<Local>> {
+    let (date, time) = parse_when(value)?;
+    in_zone(date.and_time(time.unwrap_or(settings.time)), &Local)
+}
+
+/// `t` in the time zone `tz`. Times skipped by a daylight saving change
+/// move to the next hour.
+fn in_zone<Tz: TimeZone>(t: NaiveDateTime, tz: &Tz) -> Option<DateTime<Tz>> {
+    t.and_local_timezone(tz.clone()).earliest()
+        .or_else(|| (t + chrono::Duration::hours(1)).and_local_timezone(tz.clone()).earliest())
+}
+
+/// All reminders in `notes`, sorted by time. Done tasks have none.
+pub fn list_reminders(notes: &[NoteBlock], settings: &ReminderSettings) -> Vec<Reminder> {
+    let mut reminders = Vec::new();
+    for note in notes {
+        let title = if note.title.is_empty() { "Untitled" } else { &note.title };
+        if let Some(at) = note.remind_at.as_deref().and_then(|v| local_time(v, settings)) {
+            reminders.push(Reminder {
+                note_id: note.id.clone(),
+                note_title: title.to_string(),
+                line: None,
+                text: title.to_string(),
+                at,
+                due: None,
+            });
+        }
+        for task in parse_tasks(note).into_iter().filter(|t| !t.done) {
+            let Some(when) = task.remind.as_deref().or(task.due.as_deref()) else { continue };
+            let Some(at) = local_time(when, settings) else { continue };
+            reminders.push(Reminder {
+                note_id: note.id.clone(),
+                note_title: title.to_string(),
+                line: Some(task.line),
+                text: task.text,
+                at,
+                due: task.due,
+            });
+        }
+    }
+    reminders.sort_by_key(|r| r.at);
+    reminders
+}
+
+/// The reminders in `notes` due between now and `within` from now.
+pub fn upcoming(notes: &[NoteBlock], settings: &ReminderSettings, within: chrono::Duration) -> Vec<Reminder> {
+    let now = Local::now();
+    list_reminders(notes, settings).into_iter().filter(|r| r.at >= now && r.at <= now + within).collect()
+}
+
+/// Hands out the reminders whose time has passed since the last check.
+pub struct Scheduler {
+    last_check: DateTime<Local>,
+}
+
+impl Scheduler {
+    /// Reminders that are already due at `start` are never handed out.
+    pub fn new(start: DateTime<Local>) -> Scheduler {
+        Scheduler { last_check: start }
+    }
+
+    /// The `reminders` due after the last check and up to `now`.
+    pub fn due(&mut self, reminders: Vec<Reminder>, now: DateTime<Local>) -> Vec<Reminder> {
+        let last_check = std::mem::replace(&mut self.last_check, now);
+        reminders.into_iter().filter(|r| r.at > last_check && r.at <= now).collect()
+    }
+}
+
+/// Posts every reminder of the notes in `local_path` to `url` as JSON once
+/// it is due. Runs until the server stops.
+pub async fn run_webhook(url: String, settings: ReminderSettings, local_path: Option<PathBuf>) {
+    let client = match crate::http_client(&url) {
+        Ok(client) => client,
+        Err(e) => {
+            tracing::error!(error = %e, %url, "reminder webhook disabled");
+            return;
+        }
+    };
+    tracing::info!(%url, "sending reminders to webhook");
+
+    let mut scheduler = Scheduler::new(Local::now());
+    loop {
+        tokio::time::sleep(CHECK_INTERVAL).await;
+        // Wait for a locked vault to be unlocked; the next check then covers
+        // this one too
+        if crate::vault::is_locked(local_path.as_deref()).unwrap_or(false) {
+            continue;
+        }
+        // Reading and decrypting every note blocks, so keep it off the
+        // async workers. The checks are not activity that should keep the
+        // vault unlocked
+        let path = local_path.clone();
+        let loaded = tokio::task::spawn_blocking(move || crate::vault::passively(|| load_notes_local(None, path.as_deref()))).await
+            .unwrap_or_else(|e| Err(format!("Task failed: {}", e)));
+        let notes = match loaded {
+            Ok(notes) => notes,
+            Err(e) => {
+                tracing::warn!(error = %e, "failed to load notes for reminders");
+                continue;
+            }
+        };
+
+        for reminder in scheduler.due(list_reminders(&notes, &settings), Local::now()) {
+            let result = client.post(&url).json(&reminder).send().await
+                .and_then(|response| response.error_for_status());
+            match result {
+                Ok(_) => tracing::info!(note_id = %reminder.note_id, "sent reminder"),
+                Err(e) => tracing::warn!(error = %e, note_id = %reminder.note_id, "failed to send reminder"),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{load_note_local, save_block_local};
+    use chrono::{FixedOffset, LocalResult};
+
+    fn note(remind_at: Option<&str>) -> NoteBlock {
+        NoteBlock { remind_at: remind_at.map(String::from), ..NoteBlock::test("1", "Packing list") }
+    }
+
+    #[test]
+    fn saves_without_a_reminder_keep_it() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Some(dir.path());
+        save_block_local(note(Some("2026-10-20 09:00")), path).unwrap();
+
+        // A client that predates reminders
+        save_block_local(note(None), path).unwrap();
+        assert_eq!(load_note_local("1", path).unwrap().unwrap().remind_at.as_deref(), Some("2026-10-20 09:00"));
+
+        save_block_local(note(Some("")), path).unwrap();
+        assert_eq!(load_note_local("1", path).unwrap().unwrap().remind_at, None);
+    }
+
+    fn reminder(at: DateTime<Local>) -> Reminder {
+        Reminder { note_id: "1".to_string(), note_title: "Trip".to_string(), line: None, text: "Trip".to_string(), at, due: None }
+    }
+
+    #[test]
+    fn scheduler_hands_out_each_reminder_once() {
+        let start = Local::now();
+        let minutes = |m: i64| start + chrono::Duration::minutes(m);
+        let reminders = || [-5, 0, 1, 2, 3].map(|m| reminder(minutes(m))).to_vec();
+        let mut scheduler = Scheduler::new(start);
+
+        // Due before or at the start: never; up to and including now: yes
+        let first: Vec<_> = scheduler.due(reminders(), minutes(2)).into_iter().map(|r| r.at).collect();
+        assert_eq!(first, [minutes(1), minutes(2)]);
+        let second: Vec<_> = scheduler.due(reminders(), minutes(3)).into_iter().map(|r| r.at).collect();
+        assert_eq!(second, [minutes(3)]);
+        assert!(scheduler.due(reminders(), minutes(3)).is_empty());
+    }
+
+    #[test]
+    fn parses_dates_times_and_timestamps() {
+        let date = NaiveDate::from_ymd_opt(2026, 10, 20).unwrap();
+        let time = NaiveTime::from_hms_opt(9, 30, 0);
+        assert_eq!(parse_when("2026-10-20"), Some((date, None)));
+        assert_eq!(parse_when(" 2026-10-20 09:30 "), Some((date, time)));
+        assert_eq!(parse_when("2026-10-20T09:30"), Some((date, time)));
+
+        let utc = DateTime::parse_from_rfc3339("2026-10-20T09:30:00Z").unwrap().with_timezone(&Local).naive_local();
+        assert_eq!(parse_when("2026-10-20T09:30:00Z"), Some((utc.date(), Some(utc.time()))));
+        assert_eq!(parse_when("tomorrow"), None);
+    }
+
+    /// UTC+1 until 2026-03-29 01:00 UTC and UTC+2 after, so that night
+    /// skips the local times from 02:00 to 03:00.
+    #[derive(Clone, Copy)]
+    struct SpringForward;
+
+    impl SpringForward {
+        fn offset(hours: i32) -> FixedOffset {
+            FixedOffset::east_opt(hours * 3600).unwrap()
+        }
+
+        fn change() -> NaiveDateTime {
+            NaiveDate::from_ymd_opt(2026, 3, 29).unwrap().and_hms_opt(1, 0, 0).unwrap()
+        }
+    }
+
+    impl TimeZone for SpringForward {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            SpringForward
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            let summer = Self::offset(2);
+            match (*local - Self::offset(1) < Self::change(), *local - summer >= Self::change()) {
+                (true, _) => LocalResult::Single(Self::offset(1)),
+                (false, true) => LocalResult::Single(summer),
+                (false, false) => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            self.offset_from_utc_datetime(&utc.and_time(NaiveTime::MIN))
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Self::offset(if *utc < Self::change() { 1 } else { 2 })
+        }
+    }
+
+    #[test]
+    fn times_skipped_by_daylight_saving_move_to_the_next_hour() {
+        let day = NaiveDate::from_ymd_opt(2026, 3, 29).unwrap();
+        let at = |h, m| day.and_hms_opt(h, m, 0).unwrap();
+        assert_eq!(in_zone(at(2, 30), &SpringForward).unwrap().naive_local(), at(3, 30));
+        assert_eq!(in_zone(at(1, 30), &SpringForward).unwrap().naive_local(), at(1, 30));
+        assert_eq!(in_zone(at(3, 0), &SpringForward).unwrap().naive_local(), at(3, 0));
+    }
+}
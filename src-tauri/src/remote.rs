@@ -0,0 +1,266 @@
+//! The requests Zenus clients send to a server.
+//!
+//! The desktop app in Client Mode talks to the server with an async HTTP
+//! client and the command line tools with a blocking one. Both describe a
+//! route with an [`ApiRequest`] and read the reply into an [`ApiResponse`],
+//! so paths, bodies and error messages are only written down here.
+
+use crate::e2e::VaultKey;
+use crate::notebooks::{api_path, normalize, ARCHIVE_DIR};
+use crate::tasks::{TaskFilter, TaskUpdate};
+use crate::templates::TemplateRequest;
+use crate::NoteBlock;
+use chrono::NaiveDate;
+use reqwest::header::CONTENT_TYPE;
+use reqwest::{Method, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// A request to a route below the API base URL.
+pub struct ApiRequest {
+    method: Method,
+    /// Path with the query string, if any
+    path: String,
+    content_type: Option<&'static str>,
+    body: Vec<u8>,
+}
+
+impl ApiRequest {
+    fn new(method: Method, path: impl Into<String>) -> ApiRequest {
+        ApiRequest { method, path: path.into(), content_type: None, body: Vec::new() }
+    }
+
+    fn query<T: Serialize + ?Sized>(mut self, query: &T) -> Result<ApiRequest, String> {
+        let query = serde_urlencoded::to_string(query).map_err(|e| format!("Failed to encode query: {}", e))?;
+        if !query.is_empty() {
+            self.path = format!("{}?{}", self.path, query);
+        }
+        Ok(self)
+    }
+
+    fn json<T: Serialize + ?Sized>(mut self, body: &T) -> Result<ApiRequest, String> {
+        self.body = serde_json::to_vec(body).map_err(|e| format!("Failed to serialize request: {}", e))?;
+        self.content_type = Some("application/json");
+        Ok(self)
+    }
+
+    fn text(mut self, body: &str) -> ApiRequest {
+        self.body = body.as_bytes().to_vec();
+        self.content_type = Some("text/plain; charset=utf-8");
+        self
+    }
+
+    /// Active notes, or archived ones with `subdir` set to `archive`. With
+    /// `notebook`, only the notes directly in that notebook.
+    pub fn notes(subdir: Option<&str>, notebook: Option<&str>) -> Result<ApiRequest, String> {
+        let path = if subdir == Some(ARCHIVE_DIR) { "/notes/archive" } else { "/notes" };
+        let request = ApiRequest::new(Method::GET, path);
+        match notebook {
+            Some(notebook) => request.query(&[("notebook", notebook)]),
+            None => Ok(request),
+        }
+    }
+
+    /// Saves a note, encrypting it first with the end-to-end key if there
+    /// is one.
+    pub fn save_note(block: &NoteBlock, key: Option<&VaultKey>) -> Result<ApiRequest, String> {
+        match key {
+            Some(key) => ApiRequest::new(Method::POST, "/notes").json(&key.encrypt_note(block)?),
+            None => ApiRequest::new(Method::POST, "/notes").json(block),
+        }
+    }
+
+    pub fn delete_note(id: &str, archived: bool) -> ApiRequest {
+        let path = if archived { format!("/notes/{}/archive", id) } else { format!("/notes/{}", id) };
+        ApiRequest::new(Method::DELETE, path)
+    }
+
+    pub fn reorder(orders: &[(String, i32)]) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::POST, "/notes/reorder").json(orders)
+    }
+
+    pub fn archive(id: &str) -> ApiRequest {
+        ApiRequest::new(Method::POST, format!("/notes/{}/archive", id))
+    }
+
+    pub fn unarchive(id: &str) -> ApiRequest {
+        ApiRequest::new(Method::POST, format!("/notes/{}/unarchive", id))
+    }
+
+    pub fn append(id: &str, text: &str) -> ApiRequest {
+        ApiRequest::new(Method::POST, format!("/notes/{}/append", id)).text(text)
+    }
+
+    pub fn move_note(id: &str, notebook: Option<&str>) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::POST, format!("/notes/{}/move", id)).json(&serde_json::json!({ "notebook": notebook }))
+    }
+
+    pub fn daily(date: NaiveDate) -> ApiRequest {
+        ApiRequest::new(Method::GET, format!("/daily/{}", date))
+    }
+
+    pub fn tasks(filter: &TaskFilter) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::GET, "/tasks").query(filter)
+    }
+
+    pub fn set_task(id: &str, line: usize, update: &TaskUpdate) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::POST, format!("/notes/{}/tasks/{}", id, line)).json(update)
+    }
+
+    pub fn templates() -> ApiRequest {
+        ApiRequest::new(Method::GET, "/templates")
+    }
+
+    pub fn template(name: &str) -> ApiRequest {
+        ApiRequest::new(Method::GET, format!("/templates/{}", name))
+    }
+
+    pub fn note_from_template(name: &str, request: &TemplateRequest) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::POST, format!("/templates/{}/notes", name)).json(request)
+    }
+
+    pub fn notebooks() -> ApiRequest {
+        ApiRequest::new(Method::GET, "/notebooks")
+    }
+
+    pub fn create_notebook(path: &str) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::POST, "/notebooks").json(&serde_json::json!({ "path": path }))
+    }
+
+    pub fn rename_notebook(from: &str, to: &str) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::PATCH, api_path(&normalize(from)?)).json(&serde_json::json!({ "path": to }))
+    }
+
+    pub fn delete_notebook(path: &str) -> Result<ApiRequest, String> {
+        Ok(ApiRequest::new(Method::DELETE, api_path(&normalize(path)?)))
+    }
+
+    /// Uploads `data` as an attachment of note `id`.
+    pub fn attach(id: &str, data: Vec<u8>, name: Option<&str>) -> Result<ApiRequest, String> {
+        let mut request = ApiRequest::new(Method::POST, format!("/notes/{}/attachments", id));
+        if let Some(name) = name {
+            request = request.query(&[("name", name)])?;
+        }
+        request.body = data;
+        Ok(request)
+    }
+
+    pub fn collect_garbage(dry_run: bool) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::POST, "/attachments/gc").query(&[("dryRun", dry_run)])
+    }
+
+    pub fn export(format: &str) -> Result<ApiRequest, String> {
+        ApiRequest::new(Method::GET, "/export").query(&[("format", format)])
+    }
+
+    /// Restores the vault bundle `data`.
+    pub fn restore(data: Vec<u8>) -> ApiRequest {
+        let mut request = ApiRequest::new(Method::POST, "/import");
+        request.body = data;
+        request
+    }
+}
+
+/// The body of a successful reply.
+pub struct ApiResponse {
+    body: Vec<u8>,
+}
+
+impl ApiResponse {
+    /// Client errors carry the server's message, which is worth showing;
+    /// anything else unsuccessful is reported by its status.
+    fn read(status: StatusCode, body: Vec<u8>) -> Result<ApiResponse, String> {
+        if status.is_success() {
+            return Ok(ApiResponse { body });
+        }
+        let message = String::from_utf8_lossy(&body).trim().to_string();
+        if status.is_client_error() && !message.is_empty() {
+            return Err(message);
+        }
+        Err(format!("Server error: {}", status))
+    }
+
+    pub fn json<T: DeserializeOwned>(&self) -> Result<T, String> {
+        serde_json::from_slice(&self.body).map_err(|e| format!("Failed to parse response: {}", e))
+    }
+
+    /// Notes, decrypted with the end-to-end key if there is one.
+    pub fn notes(&self, key: Option<&VaultKey>) -> Result<Vec<NoteBlock>, String> {
+        let notes: Vec<NoteBlock> = self.json()?;
+        match key {
+            Some(key) => notes.iter().map(|note| key.decrypt_note(note)).collect(),
+            None => Ok(notes),
+        }
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.body
+    }
+}
+
+/// Sends `request` to the server at `api_url` with the async client.
+pub async fn send(
+    client: &reqwest::Client,
+    api_url: &str,
+    auth_token: Option<&str>,
+    request: ApiRequest,
+) -> Result<ApiResponse, String> {
+    let mut builder = client.request(request.method, format!("{}{}", api_url, request.path));
+    if let Some(token) = auth_token {
+        builder = builder.header("Authorization", token);
+    }
+    if let Some(content_type) = request.content_type {
+        builder = builder.header(CONTENT_TYPE, content_type);
+    }
+
+    let response = builder.body(request.body).send().await
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+    let status = response.status();
+    let body = response.bytes().await.map_err(|e| format!("Failed to read response: {}", e))?;
+    ApiResponse::read(status, body.to_vec())
+}
+
+/// Sends `request` to the server at `api_url` with the blocking client.
+pub fn send_blocking(
+    client: &reqwest::blocking::Client,
+    api_url: &str,
+    auth_token: Option<&str>,
+    request: ApiRequest,
+) -> Result<ApiResponse, String> {
+    let mut builder = client.request(request.method, format!("{}{}", api_url, request.path));
+    if let Some(token) = auth_token {
+        builder = builder.header("Authorization", token);
+    }
+    if let Some(content_type) = request.content_type {
+        builder = builder.header(CONTENT_TYPE, content_type);
+    }
+
+    let response = builder.body(request.body).send()
+        .map_err(|e| format!("Failed to send request: {}", e))?;
+    let status = response.status();
+    let body = response.bytes().map_err(|e| format!("Failed to read response: {}", e))?;
+    ApiResponse::read(status, body.to_vec())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn queries_are_encoded_into_the_path() {
+        assert_eq!(ApiRequest::notes(Some(ARCHIVE_DIR), None).unwrap().path, "/notes/archive");
+        assert_eq!(ApiRequest::notes(None, Some("Work/Q&A")).unwrap().path, "/notes?notebook=Work%2FQ%26A");
+        let filter = TaskFilter { done: Some(false), tag: None };
+        assert_eq!(ApiRequest::tasks(&filter).unwrap().path, "/tasks?done=false");
+    }
+
+    #[test]
+    fn client_errors_carry_the_server_message() {
+        let conflict = ApiResponse::read(StatusCode::CONFLICT, b"Line 3 is not a task\n".to_vec());
+        assert_eq!(conflict.err().unwrap(), "Line 3 is not a task");
+        let empty = ApiResponse::read(StatusCode::NOT_FOUND, Vec::new());
+        assert_eq!(empty.err().unwrap(), "Server error: 404 Not Found");
+        let failed = ApiResponse::read(StatusCode::INTERNAL_SERVER_ERROR, b"disk full".to_vec());
+        assert_eq!(failed.err().unwrap(), "Server error: 500 Internal Server Error");
+    }
+}
@@ -0,0 +1,86 @@
+//! Markdown to HTML rendering for notes.
+
+use pulldown_cmark::{html, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+
+/// Renders note markdown to HTML.
+///
+/// `[[Title]]` and `[[Title|label]]` links are passed to `resolve`, which
+/// returns the href for a note title or `None` if the note is not available
+/// (e.g. not published), in which case only the label is shown. Task list
+/// items render as disabled checkboxes.
+pub fn render_markdown(content: &str, resolve: impl Fn(&str) -> Option<String>) -> String {
+    let options = Options::ENABLE_TABLES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_WIKILINKS;
+
+    // For each open link, whether it was a wiki link that could not be resolved
+    let mut unresolved: Vec<bool> = Vec::new();
+
+    let events = Parser::new_ext(content, options).map(|event| match event {
+        Event::Start(Tag::Link { link_type: LinkType::WikiLink { has_pothole }, dest_url, title, id }) => {
+            // `[[Title#Heading]]` links to the note itself
+            let target = dest_url.split('#').next().unwrap_or("").trim();
+            match resolve(target) {
+                Some(href) => {
+                    unresolved.push(false);
+                    Event::Start(Tag::Link {
+                        link_type: LinkType::WikiLink { has_pothole },
+                        dest_url: CowStr::from(href),
+                        title,
+                        id,
+                    })
+                }
+                None => {
+                    unresolved.push(true);
+                    Event::Html(CowStr::from("<span class=\"wikilink-missing\">"))
+                }
+            }
+        }
+        Event::Start(tag @ Tag::Link { .. }) => {
+            unresolved.push(false);
+            Event::Start(tag)
+        }
+        Event::End(TagEnd::Link) => {
+            if unresolved.pop().unwrap_or(false) {
+                Event::Html(CowStr::from("</span>"))
+            } else {
+                Event::End(TagEnd::Link)
+            }
+        }
+        other => other,
+    });
+
+    let mut out = String::new();
+    html::push_html(&mut out, events);
+    out
+}
+
+/// Escapes text for use in HTML element content and attribute values.
+pub fn escape_html(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Lowercase, dash separated version of `text` for file names and anchors.
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    for c in text.trim().chars().flat_map(char::to_lowercase) {
+        if c.is_alphanumeric() {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    slug.trim_matches('-').to_string()
+}
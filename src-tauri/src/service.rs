@@ -0,0 +1,202 @@
+//! Process lifecycle helpers for running the server as a system service:
+//! signal handling, systemd readiness / socket activation and pidfiles.
+
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+/// Binds a TCP listener, turning the common failures into readable messages.
+pub async fn bind_tcp(host: &str, port: u16) -> Result<tokio::net::TcpListener, String> {
+    tokio::net::TcpListener::bind((host, port)).await.map_err(|e| match e.kind() {
+        ErrorKind::AddrInUse => format!("Port {} on {} is already in use by another process", port, host),
+        ErrorKind::PermissionDenied => format!("Permission denied binding to {}:{} (ports below 1024 need elevated privileges)", host, port),
+        ErrorKind::AddrNotAvailable => format!("Address {} is not available on this machine", host),
+        _ => format!("Failed to bind to {}:{}: {}", host, port, e),
+    })
+}
+
+/// Binds a Unix domain socket, replacing a stale socket file left behind by a
+/// previous run, and applies `mode` (e.g. `0o660`) to it if given.
+///
+/// The socket is bound in a private directory next to `path` and only moved
+/// into place once it has its mode, so nobody can connect to it in between.
+#[cfg(unix)]
+pub fn bind_unix(path: &Path, mode: Option<u32>) -> Result<tokio::net::UnixListener, String> {
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    if let Ok(meta) = fs::symlink_metadata(path) {
+        if !meta.file_type().is_socket() {
+            return Err(format!("{} exists and is not a socket", path.display()));
+        }
+        fs::remove_file(path).map_err(|e| format!("Failed to remove stale socket {}: {}", path.display(), e))?;
+    }
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    fs::create_dir_all(parent).map_err(|e| format!("Failed to create socket directory: {}", e))?;
+
+    let private = tempfile::Builder::new()
+        .prefix(".zenus-socket-")
+        .permissions(fs::Permissions::from_mode(0o700))
+        .tempdir_in(parent)
+        .map_err(|e| format!("Failed to create socket directory: {}", e))?;
+    let staged = private.path().join("socket");
+
+    let listener = tokio::net::UnixListener::bind(&staged)
+        .map_err(|e| format!("Failed to bind to {}: {}", path.display(), e))?;
+
+    if let Some(mode) = mode {
+        fs::set_permissions(&staged, fs::Permissions::from_mode(mode))
+            .map_err(|e| format!("Failed to set permissions on {}: {}", path.display(), e))?;
+    }
+    fs::rename(&staged, path).map_err(|e| format!("Failed to bind to {}: {}", path.display(), e))?;
+    Ok(listener)
+}
+
+/// Serves `app` on a Unix socket until [`shutdown_signal`] resolves, then
+/// waits for open connections to finish and removes the socket file.
+///
+/// `axum::serve` only accepts TCP listeners, so connections are driven with
+/// hyper directly.
+#[cfg(unix)]
+pub async fn serve_unix(listener: tokio::net::UnixListener, path: &Path, app: axum::Router) -> Result<(), String> {
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use hyper_util::server::graceful::GracefulShutdown;
+    use hyper_util::service::TowerToHyperService;
+
+    let graceful = GracefulShutdown::new();
+    let shutdown = shutdown_signal();
+    tokio::pin!(shutdown);
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => {
+                let stream = match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        tracing::warn!(error = %e, "failed to accept connection");
+                        continue;
+                    }
+                };
+                let service = TowerToHyperService::new(app.clone());
+                let builder = Builder::new(TokioExecutor::new());
+                let conn = builder.serve_connection_with_upgrades(TokioIo::new(stream), service);
+                let conn = graceful.watch(conn.into_owned());
+                tokio::spawn(async move {
+                    if let Err(e) = conn.await {
+                        tracing::debug!(error = %e, "connection closed with error");
+                    }
+                });
+            }
+            _ = &mut shutdown => break,
+        }
+    }
+
+    drop(listener);
+    graceful.shutdown().await;
+    let _ = fs::remove_file(path);
+    Ok(())
+}
+
+/// Parses an octal permission string such as `660` or `0o660`.
+pub fn parse_socket_mode(mode: &str) -> Result<u32, String> {
+    let digits = mode.trim_start_matches("0o");
+    u32::from_str_radix(digits, 8)
+        .ok()
+        .filter(|m| *m <= 0o7777)
+        .ok_or_else(|| format!("Invalid socket mode '{}', expected octal like 660", mode))
+}
+
+/// Returns the listening socket handed over by systemd socket activation, if any.
+#[cfg(unix)]
+pub fn systemd_listener() -> Result<Option<tokio::net::TcpListener>, String> {
+    use std::os::unix::io::FromRawFd;
+
+    let mut fds = sd_notify::listen_fds()
+        .map_err(|e| format!("Invalid socket activation environment: {}", e))?;
+
+    let Some(fd) = fds.next() else {
+        return Ok(None);
+    };
+
+    // SAFETY: systemd passes ownership of the descriptors starting at SD_LISTEN_FDS_START
+    let std_listener = unsafe { std::net::TcpListener::from_raw_fd(fd) };
+    std_listener.set_nonblocking(true)
+        .map_err(|e| format!("Failed to configure activated socket: {}", e))?;
+    tokio::net::TcpListener::from_std(std_listener)
+        .map(Some)
+        .map_err(|e| format!("Failed to use activated socket: {}", e))
+}
+
+#[cfg(not(unix))]
+pub fn systemd_listener() -> Result<Option<tokio::net::TcpListener>, String> {
+    Ok(None)
+}
+
+/// Tells systemd (when running under `Type=notify`) that the server accepts connections.
+pub fn notify_ready() {
+    #[cfg(unix)]
+    if let Err(e) = sd_notify::notify(false, &[sd_notify::NotifyState::Ready]) {
+        tracing::warn!(error = %e, "failed to notify systemd of readiness");
+    }
+}
+
+fn notify_stopping() {
+    #[cfg(unix)]
+    let _ = sd_notify::notify(false, &[sd_notify::NotifyState::Stopping]);
+}
+
+/// Resolves once SIGINT or SIGTERM is received.
+pub async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            tracing::error!(error = %e, "failed to listen for Ctrl+C");
+            std::future::pending::<()>().await;
+        }
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                tracing::error!(error = %e, "failed to listen for SIGTERM");
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => {},
+        _ = terminate => {},
+    }
+
+    tracing::info!("shutdown requested, finishing in-flight requests");
+    notify_stopping();
+}
+
+/// Writes the current process id to a file and removes it again on drop.
+pub struct PidFile {
+    path: PathBuf,
+}
+
+impl PidFile {
+    pub fn create(path: &Path) -> Result<PidFile, String> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create pidfile directory: {}", e))?;
+        }
+        fs::write(path, format!("{}\n", std::process::id()))
+            .map_err(|e| format!("Failed to write pidfile {}: {}", path.display(), e))?;
+        Ok(PidFile { path: path.to_path_buf() })
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
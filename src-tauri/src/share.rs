@@ -0,0 +1,212 @@
+//! Public read-only share links.
+//!
+//! Shares are kept in `shares.json` in the notes directory. Each one maps an
+//! unguessable slug to a note; the server renders that note at `/s/<slug>`
+//! without the auth token until the share expires or is revoked.
+
+use crate::render::{escape_html, render_markdown};
+use crate::{load_note_local, resolve_notes_dir};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub const SHARES_FILE: &str = "shares.json";
+
+// Serialises read-modify-write cycles on the shares file
+static SHARES_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Share {
+    pub slug: String,
+    #[serde(rename = "noteId")]
+    pub note_id: String,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+    #[serde(rename = "expiresAt", default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<String>,
+}
+
+impl Share {
+    pub fn is_expired(&self) -> bool {
+        self.expires_at
+            .as_deref()
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|t| t <= chrono::Utc::now())
+    }
+}
+
+/// Body of `POST /notes/:id/share`. Both fields are optional; without either
+/// the share never expires.
+#[derive(Deserialize, Debug, Default)]
+pub struct ShareRequest {
+    /// Lifetime in seconds
+    #[serde(rename = "expiresIn", default)]
+    pub expires_in: Option<i64>,
+    /// Absolute expiry as RFC 3339
+    #[serde(rename = "expiresAt", default)]
+    pub expires_at: Option<String>,
+}
+
+/// Result of looking up a slug.
+pub enum Resolved {
+    Found(Box<crate::NoteBlock>),
+    Expired,
+    NotFound,
+}
+
+fn read_shares(notes_dir: &Path) -> Result<Vec<Share>, String> {
+    let path = notes_dir.join(SHARES_FILE);
+    if !path.exists() {
+        return Ok(vec![]);
+    }
+    let raw = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", SHARES_FILE, e))?;
+    serde_json::from_str(&raw).map_err(|e| format!("Invalid {}: {}", SHARES_FILE, e))
+}
+
+fn write_shares(notes_dir: &Path, shares: &[Share]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(shares).map_err(|e| format!("Failed to serialize shares: {}", e))?;
+    fs::write(notes_dir.join(SHARES_FILE), json).map_err(|e| format!("Failed to write {}: {}", SHARES_FILE, e))
+}
+
+/// 128 random bits, hex encoded.
+fn generate_slug() -> String {
+    rand::random::<[u8; 16]>().iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Creates a new share for an existing note. Expired shares are dropped
+/// from the file on the way.
+pub fn create_share(id: &str, request: &ShareRequest, custom_path: Option<&Path>) -> Result<Share, String> {
+    match load_note_local(id, custom_path)? {
+        None => return Err(format!("Note {} not found", id)),
+        Some(note) if crate::e2e::is_encrypted(&note) => {
+            return Err("End-to-end encrypted notes cannot be shared".to_string())
+        }
+        Some(note) if crate::note_lock::is_locked(&note) => {
+            return Err("Locked notes cannot be shared".to_string())
+        }
+        Some(_) => {}
+    }
+
+    let now = chrono::Utc::now();
+    let expires_at = match (request.expires_in, &request.expires_at) {
+        (Some(_), Some(_)) => return Err("Use either expiresIn or expiresAt, not both".to_string()),
+        (Some(secs), None) if secs <= 0 => return Err("expiresIn must be positive".to_string()),
+        (Some(secs), None) => Some((now + chrono::Duration::seconds(secs)).to_rfc3339()),
+        (None, Some(at)) => {
+            let at = chrono::DateTime::parse_from_rfc3339(at)
+                .map_err(|e| format!("Invalid expiresAt: {}", e))?;
+            if at <= now {
+                return Err("expiresAt is in the past".to_string());
+            }
+            Some(at.to_rfc3339())
+        }
+        (None, None) => None,
+    };
+
+    let share = Share {
+        slug: generate_slug(),
+        note_id: id.to_string(),
+        created_at: now.to_rfc3339(),
+        expires_at,
+    };
+
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let _guard = SHARES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut shares = read_shares(&notes_dir)?;
+    shares.retain(|s| !s.is_expired());
+    shares.push(share.clone());
+    write_shares(&notes_dir, &shares)?;
+    Ok(share)
+}
+
+/// Shares of a note that are still live.
+pub fn list_shares(id: &str, custom_path: Option<&Path>) -> Result<Vec<Share>, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let _guard = SHARES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    Ok(read_shares(&notes_dir)?
+        .into_iter()
+        .filter(|s| s.note_id == id && !s.is_expired())
+        .collect())
+}
+
+/// Removes a share. Returns whether it existed.
+pub fn revoke_share(id: &str, slug: &str, custom_path: Option<&Path>) -> Result<bool, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let _guard = SHARES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut shares = read_shares(&notes_dir)?;
+    let before = shares.len();
+    shares.retain(|s| !(s.note_id == id && s.slug == slug));
+    if shares.len() == before {
+        return Ok(false);
+    }
+    write_shares(&notes_dir, &shares)?;
+    Ok(true)
+}
+
+/// Looks up the note behind a slug. A share whose note has been deleted,
+/// locked or end-to-end encrypted since counts as not found.
+pub fn resolve_share(slug: &str, custom_path: Option<&Path>) -> Result<Resolved, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let share = {
+        let _guard = SHARES_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+        read_shares(&notes_dir)?.into_iter().find(|s| s.slug == slug)
+    };
+    let Some(share) = share else { return Ok(Resolved::NotFound) };
+    if share.is_expired() {
+        return Ok(Resolved::Expired);
+    }
+    Ok(match load_note_local(&share.note_id, custom_path)? {
+        Some(note) if crate::e2e::is_encrypted(&note) || crate::note_lock::is_locked(&note) => Resolved::NotFound,
+        Some(note) => Resolved::Found(Box::new(note)),
+        None => Resolved::NotFound,
+    })
+}
+
+/// Standalone page for a shared note. Wiki links are shown as plain text
+/// since the linked notes are not shared.
+pub fn render_page(note: &crate::NoteBlock) -> String {
+    let title = escape_html(&note.title);
+    format!(
+        r#"<!DOCTYPE html>
+<html lang="en">
+<head>
+<meta charset="utf-8">
+<meta name="viewport" content="width=device-width, initial-scale=1">
+<meta name="robots" content="noindex">
+<title>{title}</title>
+<style>
+body {{ max-width: 46rem; margin: 2rem auto; padding: 0 1rem; font-family: system-ui, sans-serif; line-height: 1.6; color: #222; }}
+a {{ color: #2563eb; }}
+pre {{ background: #f4f4f5; padding: 0.75rem; overflow-x: auto; }}
+li:has(> input[type=checkbox]) {{ list-style: none; }}
+</style>
+</head>
+<body>
+<h1>{title}</h1>
+{content}
+</body>
+</html>
+"#,
+        title = title,
+        content = render_markdown(&note.content, |_| None),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{save_block_local, NoteBlock};
+
+    #[test]
+    fn links_stop_working_once_the_note_is_encrypted() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = Some(dir.path());
+        save_block_local(NoteBlock::test("1", "Public"), path).unwrap();
+        let share = create_share("1", &ShareRequest::default(), path).unwrap();
+        assert!(matches!(resolve_share(&share.slug, path).unwrap(), Resolved::Found(_)));
+
+        save_block_local(NoteBlock::test("1", "zenus-e2e:v1:key:ciphertext"), path).unwrap();
+        assert!(matches!(resolve_share(&share.slug, path).unwrap(), Resolved::NotFound));
+    }
+}
@@ -0,0 +1,223 @@
+//! Blocking access to the notes for command line tools.
+//!
+//! A [`NoteStore`] is either the local notes directory, going through the
+//! same `*_local` functions as the desktop app in Local Mode, or a Zenus
+//! server, going through the same HTTP routes and end-to-end encryption as
+//! the desktop app in Client Mode. Notes come back as stored, so locked
+//! notes keep their sealed content and can be saved back unchanged.
+
+use crate::daily::{daily_note_id, new_daily_note, open_daily_local, DailyTemplate};
+use crate::e2e::{self, Remote, VaultKey};
+use crate::notebooks::{
+    create_notebook_local, delete_notebook_local, find_note, list_notebooks, move_note_local, rename_notebook_local,
+    Notebook, ARCHIVE_DIR,
+};
+use crate::remote::ApiRequest;
+use crate::tasks::{list_tasks, set_task_local, update_task, Task, TaskFilter, TaskUpdate};
+use crate::templates::{create_from_template_local, list_templates, Template, TemplateRequest};
+use crate::{
+    append_block_local, append_text, archive_block_local, check_editable, check_server_compatibility, delete_block_local,
+    load_note_local, load_notes_local, save_block_local, vault, NoteBlock,
+};
+use chrono::NaiveDate;
+use std::path::{Path, PathBuf};
+
+pub enum NoteStore {
+    Local(Option<PathBuf>),
+    Remote { remote: Remote, key: Option<VaultKey> },
+}
+
+impl NoteStore {
+    /// Opens the server at `url`, or the local notes directory if `url` is
+    /// `None`. The passphrase unlocks end-to-end encryption on a server or
+    /// an encrypted local vault; it is only read when needed.
+    pub fn open(
+        url: Option<&str>,
+        auth_token: Option<&str>,
+        local_path: Option<&Path>,
+        passphrase_file: Option<&Path>,
+    ) -> Result<NoteStore, String> {
+        let passphrase = passphrase_file.map(e2e::read_passphrase).transpose()?;
+
+        let Some(url) = url else {
+            if vault::is_encrypted(local_path)? {
+                let passphrase = passphrase.ok_or("The notes directory is encrypted; pass its passphrase with --passphrase-file")?;
+                vault::unlock(local_path, &passphrase)?;
+            }
+            return Ok(NoteStore::Local(local_path.map(Path::to_path_buf)));
+        };
+
+        let api_url = check_server_compatibility(url, auth_token)?;
+        let remote = Remote::new(url, &api_url, auth_token)?;
+        let key = remote.unlock(passphrase.as_deref())?;
+        Ok(NoteStore::Remote { remote, key })
+    }
+
+    /// Active notes, or archived ones with `archived`, sorted by order.
+    pub fn notes(&self, archived: bool) -> Result<Vec<NoteBlock>, String> {
+        let subdir = archived.then_some(ARCHIVE_DIR);
+        match self {
+            NoteStore::Local(path) => load_notes_local(subdir, path.as_deref()),
+            NoteStore::Remote { remote, key } => remote.send(ApiRequest::notes(subdir, None)?)?.notes(key.as_ref()),
+        }
+    }
+
+    /// Finds an active or archived note. The flag tells whether it is
+    /// archived.
+    pub fn find(&self, id: &str) -> Result<Option<(NoteBlock, bool)>, String> {
+        if let NoteStore::Local(path) = self {
+            let Some(note) = load_note_local(id, path.as_deref())? else { return Ok(None) };
+            let archived = find_note(&crate::resolve_notes_dir(path.as_deref())?, id).is_some_and(|l| l.archived);
+            return Ok(Some((note, archived)));
+        }
+
+        for archived in [false, true] {
+            if let Some(note) = self.notes(archived)?.into_iter().find(|n| n.id == id) {
+                return Ok(Some((note, archived)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Like [`NoteStore::find`], but a missing note is an error.
+    pub fn get(&self, id: &str) -> Result<(NoteBlock, bool), String> {
+        self.find(id)?.ok_or(format!("Note {} not found", id))
+    }
+
+    pub fn save(&self, block: NoteBlock) -> Result<(), String> {
+        match self {
+            NoteStore::Local(path) => save_block_local(block, path.as_deref()),
+            NoteStore::Remote { remote, key } => remote.send(ApiRequest::save_note(&block, key.as_ref())?).map(|_| ()),
+        }
+    }
+
+    pub fn delete(&self, id: &str, archived: bool) -> Result<(), String> {
+        match self {
+            NoteStore::Local(path) => delete_block_local(id.to_string(), archived.then_some(ARCHIVE_DIR), path.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::delete_note(id, archived)).map(|_| ()),
+        }
+    }
+
+    /// Appends `text` to a note. The local directory and the server append
+    /// in one step; with end-to-end encryption the note has to be decrypted
+    /// and saved whole instead.
+    pub fn append(&self, id: &str, text: &str) -> Result<(), String> {
+        match self {
+            NoteStore::Local(path) => append_block_local(id, text, path.as_deref()),
+            NoteStore::Remote { remote, key: None } => remote.send(ApiRequest::append(id, text)).map(|_| ()),
+            NoteStore::Remote { key: Some(_), .. } => {
+                if text.is_empty() {
+                    return Err("Nothing to append".to_string());
+                }
+                let (mut note, _) = self.get(id)?;
+                check_editable(&note)?;
+                append_text(&mut note.content, text);
+                self.save(note)
+            }
+        }
+    }
+
+    /// Returns the daily note for `date`, creating it if needed. A server
+    /// uses its own template, except with end-to-end encryption where the
+    /// note has to be created here from `template`.
+    pub fn daily(&self, date: NaiveDate, template: &DailyTemplate) -> Result<NoteBlock, String> {
+        match self {
+            NoteStore::Local(path) => open_daily_local(date, template, path.as_deref()),
+            NoteStore::Remote { remote, key: None } => remote.send(ApiRequest::daily(date))?.json(),
+            NoteStore::Remote { key: Some(_), .. } => {
+                if let Some((note, _)) = self.find(&daily_note_id(date)?)? {
+                    return Ok(note);
+                }
+                let note = new_daily_note(date, template, self.notes(false)?.len())?;
+                self.save(note.clone())?;
+                Ok(note)
+            }
+        }
+    }
+
+    pub fn tasks(&self, filter: &TaskFilter) -> Result<Vec<Task>, String> {
+        match self {
+            NoteStore::Local(path) => Ok(list_tasks(&load_notes_local(None, path.as_deref())?, filter)),
+            NoteStore::Remote { remote, key: None } => remote.send(ApiRequest::tasks(filter)?)?.json(),
+            NoteStore::Remote { key: Some(_), .. } => Ok(list_tasks(&self.notes(false)?, filter)),
+        }
+    }
+
+    pub fn set_task(&self, id: &str, line: usize, update: &TaskUpdate) -> Result<Task, String> {
+        match self {
+            NoteStore::Local(path) => Ok(set_task_local(id, line, update, path.as_deref())?),
+            NoteStore::Remote { remote, key: None } => remote.send(ApiRequest::set_task(id, line, update)?)?.json(),
+            NoteStore::Remote { key: Some(_), .. } => {
+                let (mut note, _) = self.get(id)?;
+                let task = update_task(&mut note, line, update)?;
+                self.save(note)?;
+                Ok(task)
+            }
+        }
+    }
+
+    pub fn templates(&self) -> Result<Vec<Template>, String> {
+        match self {
+            NoteStore::Local(path) => list_templates(path.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::templates())?.json(),
+        }
+    }
+
+    /// Creates a note from the template `name`. With end-to-end encryption
+    /// the template is filled in here rather than on the server.
+    pub fn from_template(&self, name: &str, request: &TemplateRequest) -> Result<NoteBlock, String> {
+        match self {
+            NoteStore::Local(path) => create_from_template_local(name, request, path.as_deref()),
+            NoteStore::Remote { remote, key: None } => remote.send(ApiRequest::note_from_template(name, request)?)?.json(),
+            NoteStore::Remote { remote, key: Some(_) } => {
+                let template: Template = remote.send(ApiRequest::template(name))?.json()?;
+                let note = template.instantiate(request, self.notes(false)?.len())?;
+                self.save(note.clone())?;
+                Ok(note)
+            }
+        }
+    }
+
+    pub fn archive(&self, id: &str) -> Result<(), String> {
+        match self {
+            NoteStore::Local(path) => archive_block_local(id.to_string(), path.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::archive(id)).map(|_| ()),
+        }
+    }
+
+    pub fn notebooks(&self) -> Result<Vec<Notebook>, String> {
+        match self {
+            NoteStore::Local(path) => list_notebooks(path.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::notebooks())?.json(),
+        }
+    }
+
+    pub fn create_notebook(&self, path: &str) -> Result<Notebook, String> {
+        match self {
+            NoteStore::Local(local) => create_notebook_local(path, local.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::create_notebook(path)?)?.json(),
+        }
+    }
+
+    pub fn rename_notebook(&self, from: &str, to: &str) -> Result<(), String> {
+        match self {
+            NoteStore::Local(path) => rename_notebook_local(from, to, path.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::rename_notebook(from, to)?).map(|_| ()),
+        }
+    }
+
+    pub fn delete_notebook(&self, path: &str) -> Result<(), String> {
+        match self {
+            NoteStore::Local(local) => delete_notebook_local(path, local.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::delete_notebook(path)?).map(|_| ()),
+        }
+    }
+
+    /// Moves a note into `notebook`, or to the top level with `None`.
+    pub fn move_note(&self, id: &str, notebook: Option<&str>) -> Result<(), String> {
+        match self {
+            NoteStore::Local(path) => move_note_local(id, notebook, path.as_deref()),
+            NoteStore::Remote { remote, .. } => remote.send(ApiRequest::move_note(id, notebook)?).map(|_| ()),
+        }
+    }
+}
@@ -0,0 +1,269 @@
+//! Tasks parsed from the markdown checkboxes in note bodies.
+//!
+//! A task is a list item starting with `[ ]` or `[x]`, like
+//!
+//! ```text
+//! - [ ] Send the invoice @due(2026-11-01)
+//! ```
+//!
+//! A due date may carry a time, `@due(2026-11-01 14:00)`, and
+//! `@remind(...)` asks for a reminder at another time (see
+//! [`crate::reminders`]).
+//!
+//! Checkboxes inside fenced code blocks are ignored. Tasks are addressed by
+//! their 1-based line number in the note body, which is what
+//! [`set_task_local`] uses to tick a box without rewriting the whole note.
+
+use crate::reminders::{format_when, parse_when};
+use crate::{check_editable, e2e, load_note_local, note_lock, save_block_local, NoteBlock, EDIT_LOCK};
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+const DUE_PREFIX: &str = "@due(";
+const REMIND_PREFIX: &str = "@remind(";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Task {
+    #[serde(rename = "noteId")]
+    pub note_id: String,
+    #[serde(rename = "noteTitle")]
+    pub note_title: String,
+    /// 1-based line in the note body
+    pub line: usize,
+    /// Text after the checkbox, without the due date and reminder
+    pub text: String,
+    pub done: bool,
+    /// `YYYY-MM-DD` or `YYYY-MM-DD HH:MM` from `@due(...)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub due: Option<String>,
+    /// `YYYY-MM-DD` or `YYYY-MM-DD HH:MM` from `@remind(...)`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remind: Option<String>,
+    /// Tags of the note
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Query of `GET /tasks`. Unset fields match every task.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TaskFilter {
+    #[serde(default)]
+    pub done: Option<bool>,
+    /// Only tasks in notes with this tag
+    #[serde(default)]
+    pub tag: Option<String>,
+}
+
+impl TaskFilter {
+    pub fn matches(&self, task: &Task) -> bool {
+        self.done.is_none_or(|done| task.done == done)
+            && self.tag.as_deref().filter(|tag| !tag.is_empty()).is_none_or(|tag| task.tags.iter().any(|t| t == tag))
+    }
+}
+
+/// Body of `POST /notes/:id/tasks/:line`.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TaskUpdate {
+    /// New state; toggles the task if unset
+    #[serde(default)]
+    pub done: Option<bool>,
+    /// Expected task text, to catch edits that moved the task to another line
+    #[serde(default)]
+    pub text: Option<String>,
+}
+
+/// The checkbox state and the text after it, if `line` is a task.
+fn parse_line(line: &str) -> Option<(bool, &str)> {
+    let item = line.trim_start();
+    let rest = match item.strip_prefix(['-', '*', '+']) {
+        Some(rest) => rest,
+        None => {
+            let digits = item.len() - item.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+            if digits == 0 {
+                return None;
+            }
+            item[digits..].strip_prefix(['.', ')'])?
+        }
+    };
+    let rest = rest.strip_prefix(' ')?.trim_start();
+    let done = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    let text = &rest[3..];
+    if !text.is_empty() && !text.starts_with(' ') {
+        return None;
+    }
+    Some((done, text.trim()))
+}
+
+/// Splits a date attribute such as `@due(YYYY-MM-DD)` off a task's text.
+/// Attributes with an invalid date are left in the text.
+fn split_date(text: &str, prefix: &str) -> (String, Option<String>) {
+    let Some(start) = text.find(prefix) else { return (text.to_string(), None) };
+    let Some(len) = text[start + prefix.len()..].find(')') else { return (text.to_string(), None) };
+    let value = &text[start + prefix.len()..start + prefix.len() + len];
+    let Some((date, time)) = parse_when(value) else {
+        return (text.to_string(), None);
+    };
+    let rest = format!("{}{}", &text[..start], &text[start + prefix.len() + len + 1..]);
+    (rest.split_whitespace().collect::<Vec<_>>().join(" "), Some(format_when(date, time)))
+}
+
+/// Line numbers (1-based) and lines of `content` outside fenced code blocks.
+fn lines_outside_code(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut fence: Option<&str> = None;
+    content.lines().enumerate().filter_map(move |(i, line)| {
+        let trimmed = line.trim_start();
+        let marker = ["```", "~~~"].into_iter().find(|m| trimmed.starts_with(m));
+        match (fence, marker) {
+            (None, Some(m)) => fence = Some(m),
+            (Some(open), Some(m)) if open == m => fence = None,
+            (None, None) => return Some((i + 1, line)),
+            _ => {}
+        }
+        None
+    })
+}
+
+/// The tasks in a note. Locked and end-to-end encrypted notes have none.
+pub fn parse_tasks(note: &NoteBlock) -> Vec<Task> {
+    if note_lock::is_locked(note) || e2e::is_encrypted(note) {
+        return vec![];
+    }
+    lines_outside_code(&note.content)
+        .filter_map(|(line, text)| {
+            let (done, text) = parse_line(text)?;
+            let (text, due) = split_date(text, DUE_PREFIX);
+            let (text, remind) = split_date(&text, REMIND_PREFIX);
+            Some(Task {
+                note_id: note.id.clone(),
+                note_title: note.title.clone(),
+                line,
+                text,
+                done,
+                due,
+                remind,
+                tags: note.tags.clone(),
+            })
+        })
+        .collect()
+}
+
+/// The tasks of all `notes` that match `filter`, in note order.
+pub fn list_tasks(notes: &[NoteBlock], filter: &TaskFilter) -> Vec<Task> {
+    notes.iter().flat_map(parse_tasks).filter(|t| filter.matches(t)).collect()
+}
+
+/// Sets the checkbox of the task on `line` in `note` and returns the task.
+pub fn update_task(note: &mut NoteBlock, line: usize, update: &TaskUpdate) -> Result<Task, String> {
+    check_editable(note)?;
+    let task = parse_tasks(note).into_iter().find(|t| t.line == line)
+        .ok_or(format!("Line {} of note {} is not a task", line, note.id))?;
+    if let Some(expected) = &update.text {
+        if expected.trim() != task.text {
+            return Err(format!("The task on line {} is now {:?}", line, task.text));
+        }
+    }
+
+    let done = update.done.unwrap_or(!task.done);
+    if done != task.done {
+        let mut lines: Vec<String> = note.content.split('\n').map(String::from).collect();
+        let text = &mut lines[line - 1];
+        // The bullet before the checkbox never contains a bracket
+        let start = text.find('[').ok_or(format!("Line {} of note {} is not a task", line, note.id))?;
+        text.replace_range(start..start + 3, if done { "[x]" } else { "[ ]" });
+        note.content = lines.join("\n");
+    }
+    Ok(Task { done, ..task })
+}
+
+/// Why [`set_task_local`] failed.
+#[derive(Debug)]
+pub enum SetTaskError {
+    NotFound(String),
+    /// The line is not a task or not the expected one, or the note cannot
+    /// be edited
+    Conflict(String),
+    /// Reading or writing the note failed
+    Storage(String),
+}
+
+impl From<SetTaskError> for String {
+    fn from(e: SetTaskError) -> String {
+        match e {
+            SetTaskError::NotFound(e) | SetTaskError::Conflict(e) | SetTaskError::Storage(e) => e,
+        }
+    }
+}
+
+/// Sets a task in an active or archived note in one step.
+pub fn set_task_local(id: &str, line: usize, update: &TaskUpdate, custom_path: Option<&Path>) -> Result<Task, SetTaskError> {
+    let _guard = EDIT_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut note = load_note_local(id, custom_path)
+        .map_err(SetTaskError::Storage)?
+        .ok_or_else(|| SetTaskError::NotFound(format!("Note {} not found", id)))?;
+    let task = update_task(&mut note, line, update).map_err(SetTaskError::Conflict)?;
+    save_block_local(note, custom_path).map_err(SetTaskError::Storage)?;
+    Ok(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_bullets_and_numbered_items() {
+        assert_eq!(parse_line("- [ ] Write"), Some((false, "Write")));
+        assert_eq!(parse_line("  * [x] Nested"), Some((true, "Nested")));
+        assert_eq!(parse_line("+ [X] Upper case"), Some((true, "Upper case")));
+        assert_eq!(parse_line("12. [ ] Numbered"), Some((false, "Numbered")));
+        assert_eq!(parse_line("3) [x] Parenthesis"), Some((true, "Parenthesis")));
+        assert_eq!(parse_line("- [ ]"), Some((false, "")));
+    }
+
+    #[test]
+    fn ignores_lines_that_are_not_tasks() {
+        for line in ["[ ] No bullet", "-[ ] No space", "- [y] Other mark", "- [ ]Glued", "1 [ ] No dot", "- [link](url)", "Plain text"] {
+            assert_eq!(parse_line(line), None, "{:?}", line);
+        }
+    }
+
+    #[test]
+    fn ignores_tasks_in_code_fences() {
+        let tasks = parse_tasks(&NoteBlock::test("1", "- [ ] One\n```\n- [ ] Code\n~~~\n- [ ] Still code\n```\n~~~\n- [ ] Tilde code\n~~~\n- [x] Two"));
+        let found: Vec<(usize, &str)> = tasks.iter().map(|t| (t.line, t.text.as_str())).collect();
+        assert_eq!(found, [(1, "One"), (10, "Two")]);
+    }
+
+    #[test]
+    fn splits_due_and_remind_off_the_text() {
+        let tasks = parse_tasks(&NoteBlock::test(
+            "1",
+            "- [ ] Invoice @due(2026-11-01) for ACME @remind(2026-10-31T08:00)\n- [ ] Call @due(tomorrow)\n- [ ] Lunch @due(2026-11-02 12:30)",
+        ));
+        assert_eq!(tasks[0].text, "Invoice for ACME");
+        assert_eq!(tasks[0].due.as_deref(), Some("2026-11-01"));
+        assert_eq!(tasks[0].remind.as_deref(), Some("2026-10-31 08:00"));
+        // An invalid date stays in the text
+        assert_eq!(tasks[1].text, "Call @due(tomorrow)");
+        assert_eq!(tasks[1].due, None);
+        assert_eq!(tasks[2].text, "Lunch");
+        assert_eq!(tasks[2].due.as_deref(), Some("2026-11-02 12:30"));
+        assert_eq!(tasks[2].remind, None);
+    }
+
+    #[test]
+    fn set_task_local_tells_missing_notes_from_conflicts() {
+        let dir = tempfile::tempdir().unwrap();
+        save_block_local(NoteBlock::test("1", "- [ ] Write\n- [ ] Send"), Some(dir.path())).unwrap();
+        let done = TaskUpdate { done: Some(true), text: Some("Send".to_string()) };
+
+        assert!(matches!(set_task_local("2", 2, &done, Some(dir.path())), Err(SetTaskError::NotFound(_))));
+        assert!(matches!(set_task_local("1", 1, &done, Some(dir.path())), Err(SetTaskError::Conflict(_))));
+        assert!(set_task_local("1", 2, &done, Some(dir.path())).unwrap().done);
+        let saved = load_note_local("1", Some(dir.path())).unwrap().unwrap();
+        assert_eq!(saved.content, "- [ ] Write\n- [x] Send");
+    }
+}
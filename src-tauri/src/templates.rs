@@ -0,0 +1,255 @@
+//! Note templates kept in the `templates` directory of the vault.
+//!
+//! A template is a markdown file `templates/<name>.md`. It may start with
+//! the same metadata comment as a note file, of which `title` and `tags` are
+//! used for the new note; everything else is the body. Title and body can
+//! contain placeholders (see [`fill_placeholders`]), including
+//! `{{prompt:<name>}}` variables whose values the caller asks the user for.
+
+use crate::{load_notes_local, resolve_notes_dir, save_block_local, vault, NoteBlock};
+use chrono::{Datelike, Duration, NaiveDate, NaiveTime};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+pub const TEMPLATES_DIR: &str = "templates";
+
+const PROMPT_PREFIX: &str = "prompt:";
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Template {
+    pub name: String,
+    /// Title of new notes, with placeholders
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub title: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    pub content: String,
+    /// Names of the `{{prompt:<name>}}` variables, in order of appearance
+    #[serde(default)]
+    pub variables: Vec<String>,
+}
+
+/// Body of `POST /templates/:name/notes`. Every field is optional.
+#[derive(Serialize, Deserialize, Debug, Default, Clone)]
+pub struct TemplateRequest {
+    /// Title of the new note, also available as `{{title}}`
+    #[serde(default)]
+    pub title: Option<String>,
+    /// Tags added to the template's tags
+    #[serde(default)]
+    pub tags: Vec<String>,
+    /// Values of the prompt variables
+    #[serde(default)]
+    pub values: BTreeMap<String, String>,
+}
+
+/// Values for [`fill_placeholders`].
+pub struct Placeholders<'a> {
+    pub date: NaiveDate,
+    pub time: NaiveTime,
+    pub title: &'a str,
+    pub values: &'a BTreeMap<String, String>,
+}
+
+impl Placeholders<'_> {
+    /// The current local date and time, with no title or prompt values.
+    pub fn now() -> Placeholders<'static> {
+        static NO_VALUES: BTreeMap<String, String> = BTreeMap::new();
+        let now = chrono::Local::now().naive_local();
+        Placeholders { date: now.date(), time: now.time(), title: "", values: &NO_VALUES }
+    }
+}
+
+/// Formats `date` with a strftime pattern, or `None` if the pattern is
+/// invalid.
+fn format_date(date: NaiveDate, pattern: &str) -> Option<String> {
+    let items: Vec<_> = chrono::format::StrftimeItems::new(pattern).collect();
+    if items.iter().any(|item| matches!(item, chrono::format::Item::Error)) {
+        return None;
+    }
+    Some(date.format_with_items(items.into_iter()).to_string())
+}
+
+/// Calls `f` with the name of every `{{...}}` placeholder in `text` and
+/// replaces it with the result, or leaves it alone if `f` returns `None`.
+pub(crate) fn replace_placeholders(text: &str, mut f: impl FnMut(&str) -> Result<Option<String>, String>) -> Result<String, String> {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(len) = rest[start + 2..].find("}}") else { break };
+        let end = start + 4 + len;
+        out.push_str(&rest[..start]);
+        match f(rest[start + 2..start + 2 + len].trim())? {
+            Some(value) => out.push_str(&value),
+            None => out.push_str(&rest[start..end]),
+        }
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Replaces the placeholders in `text`:
+///
+/// - `{{date}}`, `{{yesterday}}`, `{{tomorrow}}`: `YYYY-MM-DD`
+/// - `{{date:<strftime>}}`, e.g. `{{date:%A, %B %-d}}`
+/// - `{{year}}`, `{{month}}`, `{{day}}`, `{{week}}` (ISO week)
+/// - `{{weekday}}`, `{{month_name}}`
+/// - `{{time}}`: `HH:MM`
+/// - `{{title}}`: the title of the new note
+/// - `{{prompt:<name>}}`: a value supplied by the user
+///
+/// Unknown placeholders are left as they are; a prompt without a value is
+/// an error.
+pub fn fill_placeholders(text: &str, vars: &Placeholders) -> Result<String, String> {
+    let date = vars.date;
+    replace_placeholders(text, |name| {
+        Ok(match name {
+            "date" => Some(date.format("%Y-%m-%d").to_string()),
+            "yesterday" => Some((date - Duration::days(1)).format("%Y-%m-%d").to_string()),
+            "tomorrow" => Some((date + Duration::days(1)).format("%Y-%m-%d").to_string()),
+            "year" => Some(date.year().to_string()),
+            "month" => Some(format!("{:02}", date.month())),
+            "day" => Some(format!("{:02}", date.day())),
+            "week" => Some(format!("{:02}", date.iso_week().week())),
+            "weekday" => Some(date.format("%A").to_string()),
+            "month_name" => Some(date.format("%B").to_string()),
+            "time" => Some(vars.time.format("%H:%M").to_string()),
+            "title" => Some(vars.title.to_string()),
+            other => match other.strip_prefix(PROMPT_PREFIX) {
+                Some(prompt) => {
+                    let prompt = prompt.trim();
+                    let value = vars.values.get(prompt).ok_or(format!("Missing value for {{{{prompt:{}}}}}", prompt))?;
+                    Some(value.clone())
+                }
+                None => other.strip_prefix("date:").and_then(|pattern| format_date(date, pattern)),
+            },
+        })
+    })
+}
+
+/// Names of the prompt variables in `text`, without duplicates.
+fn prompt_variables(text: &str, variables: &mut Vec<String>) {
+    let _ = replace_placeholders(text, |name| {
+        if let Some(prompt) = name.strip_prefix(PROMPT_PREFIX) {
+            crate::import::push_unique(variables, prompt.trim().to_string());
+        }
+        Ok(None)
+    });
+}
+
+/// Template names become file names, so only allow plain ones.
+fn check_name(name: &str) -> Result<(), String> {
+    let valid = !name.is_empty()
+        && !name.starts_with('.')
+        && name.chars().all(|c| c.is_alphanumeric() || matches!(c, ' ' | '-' | '_' | '.'));
+    if !valid {
+        return Err(format!("Invalid template name {:?}", name));
+    }
+    Ok(())
+}
+
+fn read_template(path: &Path) -> Result<Template, String> {
+    let name = path.file_stem().and_then(|s| s.to_str()).ok_or("Invalid filename")?.to_string();
+    let text = vault::read_note(path)?;
+
+    let mut title = None;
+    let mut tags = Vec::new();
+    let mut content = text.as_str();
+    if let Some((first, rest)) = text.split_once('\n') {
+        if let Some(metadata) = first.strip_prefix("<!-- ").and_then(|l| l.strip_suffix(" -->")) {
+            let metadata: serde_json::Value = serde_json::from_str(metadata)
+                .map_err(|e| format!("Invalid metadata in template {}: {}", name, e))?;
+            title = metadata.get("title").and_then(|v| v.as_str()).filter(|t| !t.is_empty()).map(String::from);
+            if let Some(arr) = metadata.get("tags").and_then(|v| v.as_array()) {
+                tags = arr.iter().filter_map(|v| v.as_str().map(String::from)).collect();
+            }
+            content = rest.strip_prefix('\n').unwrap_or(rest);
+        }
+    }
+
+    let mut variables = Vec::new();
+    if let Some(title) = &title {
+        prompt_variables(title, &mut variables);
+    }
+    prompt_variables(content, &mut variables);
+
+    Ok(Template { name, title, tags, content: content.to_string(), variables })
+}
+
+/// All templates, sorted by name.
+pub fn list_templates(custom_path: Option<&Path>) -> Result<Vec<Template>, String> {
+    let dir = resolve_notes_dir(custom_path)?.join(TEMPLATES_DIR);
+    if !dir.exists() {
+        return Ok(vec![]);
+    }
+
+    let mut templates = Vec::new();
+    for entry in fs::read_dir(&dir).map_err(|e| format!("Failed to read directory: {}", e))? {
+        let path = entry.map_err(|e| format!("Failed to read entry: {}", e))?.path();
+        if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            templates.push(read_template(&path)?);
+        }
+    }
+    templates.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(templates)
+}
+
+pub fn load_template(name: &str, custom_path: Option<&Path>) -> Result<Option<Template>, String> {
+    check_name(name)?;
+    let path = resolve_notes_dir(custom_path)?.join(TEMPLATES_DIR).join(format!("{}.md", name));
+    if !path.exists() {
+        return Ok(None);
+    }
+    read_template(&path).map(Some)
+}
+
+impl Template {
+    /// A new, unsaved note from this template, placed after `existing`
+    /// notes.
+    pub fn instantiate(&self, request: &TemplateRequest, existing: usize) -> Result<NoteBlock, String> {
+        let now = Placeholders::now();
+        let mut vars = Placeholders { values: &request.values, ..now };
+
+        let title = match &self.title {
+            Some(template) => {
+                vars.title = request.title.as_deref().unwrap_or("");
+                fill_placeholders(template, &vars)?
+            }
+            None => request.title.clone().unwrap_or_default(),
+        };
+        vars.title = &title;
+        let content = fill_placeholders(&self.content, &vars)?;
+
+        let mut tags = self.tags.clone();
+        for tag in &request.tags {
+            crate::import::push_unique(&mut tags, tag.clone());
+        }
+
+        Ok(NoteBlock {
+            id: crate::generate_note_id(),
+            title,
+            content,
+            is_collapsed: false,
+            order: existing as i32,
+            tags,
+            created_at: None,
+            updated_at: None,
+            attachments: None,
+            locked: None,
+            remind_at: None,
+            notebook: None,
+        })
+    }
+}
+
+/// Creates and saves a note from the template `name`.
+pub fn create_from_template_local(name: &str, request: &TemplateRequest, custom_path: Option<&Path>) -> Result<NoteBlock, String> {
+    let template = load_template(name, custom_path)?.ok_or(format!("Template {} not found", name))?;
+    let note = template.instantiate(request, load_notes_local(None, custom_path)?.len())?;
+    let id = note.id.clone();
+    save_block_local(note, custom_path)?;
+    crate::load_note_local(&id, custom_path)?.ok_or(format!("Note {} not found", id))
+}
@@ -0,0 +1,317 @@
+//! Encryption at rest for the local notes directory.
+//!
+//! A notes directory is an encrypted vault when it contains `vault.json`,
+//! which holds the key derivation parameters (see [`crate::e2e::KeyParams`]).
+//! Every note file in an encrypted vault is stored as a single line
+//!
+//! ```text
+//! zenus-vault:v1:<key id>:<base64 nonce + ciphertext>
+//! ```
+//!
+//! wrapping the usual metadata comment and body, with the note ID as
+//! associated data. The key lives in memory only after [`unlock`] and is
+//! dropped by [`lock`] or once the vault has been idle for the auto-lock
+//! period. Attachments, share links and `vault.json` itself are not
+//! encrypted.
+
+use crate::e2e::{KeyParams, VaultKey};
+use crate::resolve_notes_dir;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::fs;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+pub const VAULT_FILE: &str = "vault.json";
+pub const VAULT_FORMAT: &str = "zenus-vault";
+pub const VAULT_VERSION: u32 = 1;
+pub const DEFAULT_AUTO_LOCK_MINUTES: u64 = 15;
+
+const FILE_PREFIX: &str = "zenus-vault:v1:";
+
+struct Unlocked {
+    key: VaultKey,
+    last_used: Instant,
+}
+
+static UNLOCKED: Mutex<Option<Unlocked>> = Mutex::new(None);
+
+// Seconds of inactivity before the key is dropped, 0 to never lock
+static AUTO_LOCK_SECS: AtomicU64 = AtomicU64::new(DEFAULT_AUTO_LOCK_MINUTES * 60);
+
+thread_local! {
+    // Set while this thread reads on behalf of a background job
+    static PASSIVE: Cell<bool> = const { Cell::new(false) };
+}
+
+#[derive(Serialize, Deserialize, Debug)]
+struct VaultFile {
+    format: String,
+    version: u32,
+    key: KeyParams,
+}
+
+#[derive(Serialize, Debug)]
+pub struct VaultStatus {
+    pub encrypted: bool,
+    pub locked: bool,
+    /// Minutes of inactivity before the vault locks itself, 0 if never
+    #[serde(rename = "autoLockMinutes")]
+    pub auto_lock_minutes: u64,
+}
+
+/// How many note files a migration rewrote.
+#[derive(Serialize, Debug, Default)]
+pub struct MigrationReport {
+    pub files: usize,
+}
+
+fn read_vault_file(notes_dir: &Path) -> Result<Option<VaultFile>, String> {
+    let path = notes_dir.join(VAULT_FILE);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let raw = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", VAULT_FILE, e))?;
+    let vault: VaultFile = serde_json::from_str(&raw).map_err(|e| format!("Invalid {}: {}", VAULT_FILE, e))?;
+    if vault.format != VAULT_FORMAT || vault.version > VAULT_VERSION {
+        return Err(format!("{} is not supported by this version of Zenus", VAULT_FILE));
+    }
+    Ok(Some(vault))
+}
+
+pub fn is_encrypted(custom_path: Option<&Path>) -> Result<bool, String> {
+    Ok(resolve_notes_dir(custom_path)?.join(VAULT_FILE).exists())
+}
+
+/// Sets the inactivity period after which the vault locks itself.
+pub fn set_auto_lock(minutes: u64) {
+    AUTO_LOCK_SECS.store(minutes * 60, Ordering::Relaxed);
+}
+
+fn idle_expired(unlocked: &Unlocked) -> bool {
+    let secs = AUTO_LOCK_SECS.load(Ordering::Relaxed);
+    secs > 0 && unlocked.last_used.elapsed() >= Duration::from_secs(secs)
+}
+
+/// Derives the key for the vault in the notes directory and keeps it in
+/// memory. Fails on a wrong passphrase or if the vault is not encrypted.
+pub fn unlock(custom_path: Option<&Path>, passphrase: &str) -> Result<(), String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    let vault = read_vault_file(&notes_dir)?.ok_or("The notes directory is not encrypted")?;
+    let key = VaultKey::unlock(&vault.key, passphrase)
+        .map_err(|_| "Wrong passphrase for the notes vault".to_string())?;
+    *UNLOCKED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Unlocked { key, last_used: Instant::now() });
+    tracing::info!("vault unlocked");
+    Ok(())
+}
+
+/// Drops the key from memory.
+pub fn lock() {
+    crate::note_lock::relock_all();
+    if UNLOCKED.lock().unwrap_or_else(|e| e.into_inner()).take().is_some() {
+        tracing::info!("vault locked");
+    }
+}
+
+/// Locks the vault if it has been idle for the auto-lock period. Returns
+/// whether it was locked by this call.
+pub fn lock_if_idle() -> bool {
+    let mut unlocked = UNLOCKED.lock().unwrap_or_else(|e| e.into_inner());
+    if unlocked.as_ref().is_some_and(idle_expired) {
+        *unlocked = None;
+        crate::note_lock::relock_all();
+        tracing::info!("vault locked after inactivity");
+        return true;
+    }
+    false
+}
+
+pub fn status(custom_path: Option<&Path>) -> Result<VaultStatus, String> {
+    lock_if_idle();
+    Ok(VaultStatus {
+        encrypted: is_encrypted(custom_path)?,
+        locked: UNLOCKED.lock().unwrap_or_else(|e| e.into_inner()).is_none(),
+        auto_lock_minutes: AUTO_LOCK_SECS.load(Ordering::Relaxed) / 60,
+    })
+}
+
+/// Whether the vault in the notes directory is encrypted and currently
+/// locked, so that reading a note would fail.
+pub fn is_locked(custom_path: Option<&Path>) -> Result<bool, String> {
+    let status = status(custom_path)?;
+    Ok(status.encrypted && status.locked)
+}
+
+/// Runs `f` without its vault reads and writes counting as activity, so
+/// background jobs such as the reminder checks don't keep the vault from
+/// locking itself.
+pub fn passively<T>(f: impl FnOnce() -> T) -> T {
+    let was = PASSIVE.with(|p| p.replace(true));
+    let result = f();
+    PASSIVE.with(|p| p.set(was));
+    result
+}
+
+/// Runs `f` with the unlocked key, counting as vault activity unless inside
+/// [`passively`].
+fn with_key<T>(key_id: &str, f: impl FnOnce(&VaultKey) -> Result<T, String>) -> Result<T, String> {
+    let mut guard = UNLOCKED.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.as_ref().is_some_and(idle_expired) {
+        *guard = None;
+        crate::note_lock::relock_all();
+        tracing::info!("vault locked after inactivity");
+    }
+    let unlocked = guard.as_mut().ok_or("The notes vault is locked")?;
+    if unlocked.key.id() != key_id {
+        return Err(format!("The unlocked vault key does not match key {}", key_id));
+    }
+    if !PASSIVE.with(Cell::get) {
+        unlocked.last_used = Instant::now();
+    }
+    f(&unlocked.key)
+}
+
+fn note_id(path: &Path) -> Result<&str, String> {
+    path.file_stem().and_then(|s| s.to_str()).ok_or("Invalid filename".to_string())
+}
+
+/// Reads a note file, decrypting it if needed. Plain files are read as is
+/// even in an encrypted vault, so a half-migrated vault stays readable.
+pub(crate) fn read_note(path: &Path) -> Result<String, String> {
+    let raw = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let Some((key_id, data)) = raw.trim_end().strip_prefix(FILE_PREFIX).and_then(|r| r.split_once(':')) else {
+        return Ok(raw);
+    };
+    let id = note_id(path)?;
+    let plain = with_key(key_id, |key| {
+        key.open(data, id.as_bytes()).map_err(|_| format!("Failed to decrypt note {}: the file was tampered with", id))
+    })?;
+    String::from_utf8(plain).map_err(|e| format!("Failed to decrypt note {}: {}", id, e))
+}
+
+/// Writes a note file into `notes_dir` (or one of its notebooks or the
+/// archive), encrypting it if the vault is encrypted.
+pub(crate) fn write_note(notes_dir: &Path, path: &Path, text: &str) -> Result<(), String> {
+    let data = match read_vault_file(notes_dir)? {
+        Some(vault) => seal(&vault.key.id, path, text)?,
+        None => text.to_string(),
+    };
+    fs::write(path, data).map_err(|e| format!("Failed to write block: {}", e))
+}
+
+fn seal(key_id: &str, path: &Path, text: &str) -> Result<String, String> {
+    let id = note_id(path)?;
+    let data = with_key(key_id, |key| key.seal(text.as_bytes(), id.as_bytes()))?;
+    Ok(format!("{}{}:{}\n", FILE_PREFIX, key_id, data))
+}
+
+/// Note files in the notes directory, its notebooks and the archive.
+fn note_files(notes_dir: &Path) -> Vec<std::path::PathBuf> {
+    [false, true].into_iter()
+        .flat_map(|archived| crate::notebooks::note_files(notes_dir, archived))
+        .map(|(path, _)| path)
+        .collect()
+}
+
+fn is_sealed(path: &Path) -> Result<bool, String> {
+    let raw = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    Ok(raw.starts_with(FILE_PREFIX.as_bytes()))
+}
+
+/// Turns the notes directory into an encrypted vault and encrypts every
+/// note. Running it again with the same passphrase finishes an interrupted
+/// migration.
+pub fn encrypt_vault(custom_path: Option<&Path>, passphrase: &str) -> Result<MigrationReport, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    fs::create_dir_all(&notes_dir).map_err(|e| format!("Failed to create directory: {}", e))?;
+
+    let key_id = match read_vault_file(&notes_dir)? {
+        Some(vault) => {
+            unlock(custom_path, passphrase)?;
+            vault.key.id
+        }
+        None => {
+            let (key, params) = VaultKey::generate(passphrase)?;
+            let vault = VaultFile { format: VAULT_FORMAT.to_string(), version: VAULT_VERSION, key: params };
+            let json = serde_json::to_string_pretty(&vault).map_err(|e| format!("Failed to serialize vault: {}", e))?;
+            fs::write(notes_dir.join(VAULT_FILE), json).map_err(|e| format!("Failed to write {}: {}", VAULT_FILE, e))?;
+
+            let id = key.id().to_string();
+            *UNLOCKED.lock().unwrap_or_else(|e| e.into_inner()) = Some(Unlocked { key, last_used: Instant::now() });
+            id
+        }
+    };
+
+    let mut report = MigrationReport::default();
+    for path in note_files(&notes_dir) {
+        if is_sealed(&path)? {
+            continue;
+        }
+        let text = fs::read_to_string(&path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        fs::write(&path, seal(&key_id, &path, &text)?).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        report.files += 1;
+    }
+    tracing::info!(files = report.files, "encrypted notes vault");
+    Ok(report)
+}
+
+/// Decrypts every note and turns the vault back into a plain notes
+/// directory.
+pub fn decrypt_vault(custom_path: Option<&Path>, passphrase: &str) -> Result<MigrationReport, String> {
+    let notes_dir = resolve_notes_dir(custom_path)?;
+    if read_vault_file(&notes_dir)?.is_none() {
+        return Err("The notes directory is not encrypted".to_string());
+    }
+    unlock(custom_path, passphrase)?;
+
+    let mut report = MigrationReport::default();
+    for path in note_files(&notes_dir) {
+        if !is_sealed(&path)? {
+            continue;
+        }
+        let text = read_note(&path)?;
+        fs::write(&path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))?;
+        report.files += 1;
+    }
+
+    // Only drop the key parameters once no file needs them any more
+    fs::remove_file(notes_dir.join(VAULT_FILE)).map_err(|e| format!("Failed to remove {}: {}", VAULT_FILE, e))?;
+    lock();
+    tracing::info!(files = report.files, "decrypted notes vault");
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // The tests never call lock(), which would also relock the notes of the
+    // note_lock tests running alongside
+    #[test]
+    fn notes_round_trip_through_the_vault() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        // Cheap KDF parameters so the test doesn't spend seconds in Argon2
+        let (_, params) = VaultKey::generate_with("secret", 64, 1, 1).unwrap();
+        let vault = VaultFile { format: VAULT_FORMAT.to_string(), version: VAULT_VERSION, key: params };
+        fs::write(dir.join(VAULT_FILE), serde_json::to_string(&vault).unwrap()).unwrap();
+        assert!(unlock(Some(dir), "wrong").is_err());
+        unlock(Some(dir), "secret").unwrap();
+
+        let text = "<!-- {\"title\":\"Diary\"} -->\n\nDear diary";
+        let (note, copy, plain) = (dir.join("1.md"), dir.join("2.md"), dir.join("3.md"));
+        write_note(dir, &note, text).unwrap();
+        fs::copy(&note, &copy).unwrap();
+        fs::write(&plain, "Not encrypted yet").unwrap();
+
+        let stored = fs::read_to_string(&note).unwrap();
+        assert!(stored.starts_with(FILE_PREFIX) && !stored.contains("Dear diary"));
+        assert_eq!(read_note(&note).unwrap(), text);
+        // The note ID is associated data, so a file can't pass for another note
+        assert!(read_note(&copy).is_err());
+        assert_eq!(read_note(&plain).unwrap(), "Not encrypted yet");
+    }
+}
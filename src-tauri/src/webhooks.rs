@@ -0,0 +1,312 @@
+//! Outgoing webhooks for note changes.
+//!
+//! Every `[[webhooks]]` table in the config file is a URL that gets a POST
+//! with a JSON [`Payload`] when a note matching its filters is saved,
+//! deleted, archived or unarchived. With a `secret`, the body is signed
+//! with HMAC-SHA256 and the signature sent as `X-Zenus-Signature:
+//! sha256=<hex>`. Failed deliveries are retried with exponential backoff,
+//! and the latest ones are kept in memory for `GET /webhooks/deliveries`.
+
+use crate::NoteBlock;
+use hmac::{Hmac, Mac};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Attempts per delivery, including the first.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// Wait before the first retry, doubled for every further one.
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+
+/// Deliveries kept for `GET /webhooks/deliveries`.
+const MAX_LOG: usize = 100;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookEvent {
+    Save,
+    Delete,
+    Archive,
+    Unarchive,
+}
+
+impl WebhookEvent {
+    fn as_str(self) -> &'static str {
+        match self {
+            WebhookEvent::Save => "save",
+            WebhookEvent::Delete => "delete",
+            WebhookEvent::Archive => "archive",
+            WebhookEvent::Unarchive => "unarchive",
+        }
+    }
+}
+
+/// One `[[webhooks]]` table of the config file.
+#[derive(Deserialize, Clone, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Webhook {
+    pub url: String,
+    /// Key for the HMAC signature; requests are unsigned without one
+    #[serde(default)]
+    pub secret: Option<String>,
+    /// Events to send, all if empty
+    #[serde(default)]
+    pub events: Vec<WebhookEvent>,
+    /// Only send for notes with one of these tags, all notes if empty
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+impl Webhook {
+    fn matches(&self, event: WebhookEvent, note: &NoteBlock) -> bool {
+        (self.events.is_empty() || self.events.contains(&event))
+            && (self.tags.is_empty() || self.tags.iter().any(|t| note.tags.contains(t)))
+    }
+}
+
+/// Body of a webhook request.
+#[derive(Serialize)]
+struct Payload<'a> {
+    /// ID of the delivery, the same for every retry
+    id: &'a str,
+    event: WebhookEvent,
+    #[serde(rename = "noteId")]
+    note_id: &'a str,
+    title: &'a str,
+    tags: &'a [String],
+    timestamp: &'a str,
+}
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum DeliveryState {
+    /// Not sent yet, or waiting for a retry
+    Pending,
+    Delivered,
+    /// Given up after the last attempt or a response that is not retried
+    Failed,
+}
+
+/// A webhook request and how it went.
+#[derive(Serialize, Clone, Debug)]
+pub struct Delivery {
+    pub id: String,
+    pub url: String,
+    pub event: WebhookEvent,
+    #[serde(rename = "noteId")]
+    pub note_id: String,
+    pub state: DeliveryState,
+    pub attempts: u32,
+    /// HTTP status of the last attempt
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// Why the last attempt failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(rename = "createdAt")]
+    pub created_at: String,
+}
+
+/// The configured webhooks and their delivery log.
+pub struct Webhooks {
+    hooks: Vec<(Webhook, reqwest::Client)>,
+    log: Mutex<VecDeque<Delivery>>,
+}
+
+/// `sha256=<hex>` HMAC of `body` with `secret`.
+fn sign(secret: &str, body: &[u8]) -> Result<String, String> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| format!("Invalid webhook secret: {}", e))?;
+    mac.update(body);
+    let hex: String = mac.finalize().into_bytes().iter().map(|b| format!("{:02x}", b)).collect();
+    Ok(format!("sha256={}", hex))
+}
+
+/// Whether a response status is worth another attempt.
+fn is_retryable(status: reqwest::StatusCode) -> bool {
+    status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS || status == reqwest::StatusCode::REQUEST_TIMEOUT
+}
+
+impl Webhooks {
+    pub fn new(hooks: Vec<Webhook>) -> Result<Webhooks, String> {
+        let hooks = hooks.into_iter()
+            .map(|hook| {
+                let client = crate::http_client(&hook.url)?;
+                Ok((hook, client))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        if !hooks.is_empty() {
+            tracing::info!(count = hooks.len(), "webhooks enabled");
+        }
+        Ok(Webhooks { hooks, log: Mutex::new(VecDeque::new()) })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.hooks.is_empty()
+    }
+
+    /// Sends `event` for `note` to every matching webhook in the background.
+    pub fn notify(self: &Arc<Self>, event: WebhookEvent, note: &NoteBlock) {
+        for index in 0..self.hooks.len() {
+            let hook = &self.hooks[index].0;
+            if !hook.matches(event, note) {
+                continue;
+            }
+
+            let delivery = Delivery {
+                id: format!("{:016x}", rand::random::<u64>()),
+                url: hook.url.clone(),
+                event,
+                note_id: note.id.clone(),
+                state: DeliveryState::Pending,
+                attempts: 0,
+                status: None,
+                error: None,
+                created_at: chrono::Utc::now().to_rfc3339(),
+            };
+            let payload = Payload {
+                id: &delivery.id,
+                event,
+                note_id: &note.id,
+                title: &note.title,
+                tags: &note.tags,
+                timestamp: &delivery.created_at,
+            };
+            let body = match serde_json::to_vec(&payload) {
+                Ok(body) => body,
+                Err(e) => {
+                    tracing::error!(error = %e, note_id = %note.id, "failed to serialize webhook payload");
+                    continue;
+                }
+            };
+            self.insert(&delivery);
+            tokio::spawn(Arc::clone(self).deliver(index, body, delivery));
+        }
+    }
+
+    /// The latest deliveries, newest first.
+    pub fn deliveries(&self) -> Vec<Delivery> {
+        self.log.lock().unwrap_or_else(|e| e.into_inner()).iter().cloned().collect()
+    }
+
+    /// Adds a new delivery to the log, dropping the oldest beyond [`MAX_LOG`].
+    fn insert(&self, delivery: &Delivery) {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        log.push_front(delivery.clone());
+        log.truncate(MAX_LOG);
+    }
+
+    /// Updates a delivery in the log. Once it has been pushed out by newer
+    /// ones it stays out.
+    fn record(&self, delivery: &Delivery) {
+        let mut log = self.log.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(entry) = log.iter_mut().find(|d| d.id == delivery.id) {
+            *entry = delivery.clone();
+        }
+    }
+
+    async fn deliver(self: Arc<Self>, index: usize, body: Vec<u8>, mut delivery: Delivery) {
+        let (hook, client) = &self.hooks[index];
+        let signature = match hook.secret.as_deref().map(|secret| sign(secret, &body)).transpose() {
+            Ok(signature) => signature,
+            Err(e) => {
+                delivery.state = DeliveryState::Failed;
+                delivery.error = Some(e);
+                self.record(&delivery);
+                return;
+            }
+        };
+
+        let mut delay = RETRY_DELAY;
+        loop {
+            delivery.attempts += 1;
+            let mut request = client.post(&hook.url)
+                .header(reqwest::header::CONTENT_TYPE, "application/json")
+                .header("X-Zenus-Event", delivery.event.as_str())
+                .header("X-Zenus-Delivery", &delivery.id)
+                .body(body.clone());
+            if let Some(signature) = &signature {
+                request = request.header("X-Zenus-Signature", signature);
+            }
+
+            let retry = match request.send().await {
+                Ok(response) => {
+                    let status = response.status();
+                    delivery.status = Some(status.as_u16());
+                    if status.is_success() {
+                        delivery.state = DeliveryState::Delivered;
+                        delivery.error = None;
+                        self.record(&delivery);
+                        tracing::info!(url = %hook.url, note_id = %delivery.note_id, event = delivery.event.as_str(), "delivered webhook");
+                        return;
+                    }
+                    delivery.error = Some(format!("Server answered {}", status));
+                    is_retryable(status)
+                }
+                Err(e) => {
+                    delivery.status = None;
+                    delivery.error = Some(format!("Failed to send request: {}", e));
+                    true
+                }
+            };
+
+            if !retry || delivery.attempts >= MAX_ATTEMPTS {
+                delivery.state = DeliveryState::Failed;
+                self.record(&delivery);
+                tracing::warn!(
+                    url = %hook.url,
+                    note_id = %delivery.note_id,
+                    attempts = delivery.attempts,
+                    error = delivery.error.as_deref().unwrap_or(""),
+                    "webhook delivery failed"
+                );
+                return;
+            }
+            self.record(&delivery);
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn delivery(id: usize) -> Delivery {
+        Delivery {
+            id: id.to_string(),
+            url: "http://localhost/hook".to_string(),
+            event: WebhookEvent::Save,
+            note_id: "1".to_string(),
+            state: DeliveryState::Pending,
+            attempts: 0,
+            status: None,
+            error: None,
+            created_at: chrono::Utc::now().to_rfc3339(),
+        }
+    }
+
+    #[test]
+    fn retries_of_dropped_deliveries_stay_out_of_the_log() {
+        let webhooks = Webhooks::new(Vec::new()).unwrap();
+        for id in 0..=MAX_LOG {
+            webhooks.insert(&delivery(id));
+        }
+
+        let mut retried = delivery(0);
+        retried.attempts = 2;
+        webhooks.record(&retried);
+        let mut latest = delivery(MAX_LOG);
+        latest.attempts = 1;
+        webhooks.record(&latest);
+
+        let log = webhooks.deliveries();
+        assert_eq!(log.len(), MAX_LOG);
+        assert!(log.iter().all(|d| d.id != "0"));
+        assert_eq!(log[0].id, MAX_LOG.to_string());
+        assert_eq!(log[0].attempts, 1);
+    }
+}